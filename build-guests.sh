@@ -0,0 +1,54 @@
+#!/bin/sh
+# Rebuilds every guest module from source into wasm-api/ and signs it with an
+# ML-DSA-65 key, so the service and kat-tests run the code in this tree.
+#
+# usage: ./build-guests.sh <secret_key> <public_key>
+#
+# Needs the wasm32-wasip1 target (rustup target add wasm32-wasip1). A secret key
+# that does not exist yet is generated together with its public key, which is
+# how CI signs the fresh modules for the tests.
+set -eu
+
+root=$(cd "$(dirname "$0")" && pwd)
+secret_key=$1
+public_key=$2
+api=$root/wasm-api
+
+# Each crate's .cargo/config.toml only applies when building from its directory
+build() {
+    crate=$1
+    shift
+    (cd "$root/$crate" && cargo build --release "$@")
+}
+
+build multivalue-xform
+xform=$root/multivalue-xform/target/release/multivalue-xform
+build test-dilithium
+signer=$root/test-dilithium/target/release/test-dilithium
+
+if [ ! -e "$secret_key" ]; then
+    "$signer" keygen --level 65 --no-encrypt --secret-key "$secret_key" --public-key "$public_key" --key-format pem
+fi
+
+# <features> <artifact>
+signing_module() {
+    build dilithium-code-signing-module --target wasm32-wasip1 --no-default-features --features "$1"
+    "$xform" "$root/dilithium-code-signing-module/target/wasm32-wasip1/release/dilithium_code_sign_module.wasm" \
+        "$api/$2" sign_data:2 generate_keypair:2 generate_keypair_from_seed:2
+}
+
+signing_module mode2 dilithium2_code_sign_module.wasm.multivalue.wasm
+signing_module mode3 dilithium3_code_sign_module.wasm.multivalue.wasm
+signing_module mode5 dilithium5_code_sign_module.wasm.multivalue.wasm
+signing_module mode2,ml-dsa mldsa44_code_sign_module.wasm.multivalue.wasm
+signing_module mode3,ml-dsa mldsa65_code_sign_module.wasm.multivalue.wasm
+signing_module mode5,ml-dsa mldsa87_code_sign_module.wasm.multivalue.wasm
+
+build kyber-kem-module --target wasm32-wasip1
+"$xform" "$root/kyber-kem-module/target/wasm32-wasip1/release/kyber_kem_module.wasm" \
+    "$api/kyber_kem_module.wasm.multivalue.wasm" keypair:2 encapsulate:2
+
+for module in "$api"/*.multivalue.wasm; do
+    "$signer" sign-wasm --level 65 --secret-key "$secret_key" --public-key "$public_key" \
+        "$module" "$module"
+done
@@ -1,4 +1,113 @@
 use pqc_dilithium::*;
+use signing_formats::framing::{context_message, prehash_message};
+#[cfg(feature = "ml-dsa")]
+use signing_formats::mldsa::{self, ParameterSet};
+
+//Length of the seed accepted by `generate_keypair_from_seed`
+const SEED_BYTES: usize = 32;
+
+//ML-DSA parameter set of this build, ML-DSA-44/65/87 for mode2/mode3/mode5
+#[cfg(feature = "ml-dsa")]
+const PARAMETER_SET: ParameterSet = if cfg!(feature = "mode2") {
+    ParameterSet::MlDsa44
+} else if cfg!(feature = "mode5") {
+    ParameterSet::MlDsa87
+} else {
+    ParameterSet::MlDsa65
+};
+
+/// What a signature covers. ML-DSA always signs the FIPS 204 message
+/// representative, so a plain message gets an empty context; round-3 Dilithium
+/// signs plain messages as is and nothing else (see `signing_formats::framing`).
+enum Message {
+    Plain(Vec<u8>),
+    //Already framed by `context_message` or `prehash_message`; only ML-DSA reads it
+    #[cfg_attr(not(feature = "ml-dsa"), allow(dead_code))]
+    Framed(Vec<u8>),
+}
+
+impl Message {
+    #[cfg(feature = "ml-dsa")]
+    fn ml_dsa(&self) -> Vec<u8> {
+        match self {
+            Message::Plain(data) => context_message(&[], data).unwrap(),
+            Message::Framed(message) => message.clone(),
+        }
+    }
+
+    fn round3(&self) -> Option<&[u8]> {
+        match self {
+            Message::Plain(data) => Some(data),
+            Message::Framed(_) => None,
+        }
+    }
+}
+
+//Generates a (public key, secret key) pair with this build's backend
+fn new_keypair() -> Option<(Vec<u8>, Vec<u8>)> {
+    #[cfg(feature = "ml-dsa")]
+    return mldsa::keypair(PARAMETER_SET);
+    #[cfg(not(feature = "ml-dsa"))]
+    {
+        let keys = Keypair::generate();
+        Some((keys.public.to_vec(), keys.expose_secret().to_vec()))
+    }
+}
+
+//Derives a (public key, secret key) pair from `seed` with this build's backend
+fn seeded_keypair(seed: &[u8; SEED_BYTES]) -> (Vec<u8>, Vec<u8>) {
+    #[cfg(feature = "ml-dsa")]
+    return mldsa::keypair_from_seed(PARAMETER_SET, seed);
+    #[cfg(not(feature = "ml-dsa"))]
+    {
+        let mut pk = vec![0u8; PUBLICKEYBYTES];
+        let mut sk = vec![0u8; SECRETKEYBYTES];
+        crypto_sign_keypair(&mut pk, &mut sk, Some(seed));
+        (pk, sk)
+    }
+}
+
+//Signs with this build's backend, or returns None if `sk` has the wrong size
+//or cannot sign this kind of message
+fn sign_message(message: &Message, sk: &[u8]) -> Option<Vec<u8>> {
+    #[cfg(feature = "ml-dsa")]
+    return match ParameterSet::of_secret_key(sk) {
+        //Keys of other levels are not keys of this build
+        Some(PARAMETER_SET) => mldsa::sign(&message.ml_dsa(), sk),
+        _ => None,
+    };
+    #[cfg(not(feature = "ml-dsa"))]
+    {
+        if sk.len() != SECRETKEYBYTES {
+            return None;
+        }
+        let mut signature = vec![0u8; SIGNBYTES];
+        crypto_sign_signature(&mut signature, message.round3()?, sk);
+        Some(signature)
+    }
+}
+
+//Verifies with the scheme `pk` is encoded for: tagged ML-DSA keys when the
+//feature is enabled, untagged round-3 keys always
+fn verify_message(message: &Message, signature: &[u8], pk: &[u8]) -> bool {
+    #[cfg(feature = "ml-dsa")]
+    if ParameterSet::of_public_key(pk) == Some(PARAMETER_SET) {
+        return mldsa::verify(&message.ml_dsa(), signature, pk);
+    }
+    //keys from another level would otherwise be sliced out of bounds
+    pk.len() == PUBLICKEYBYTES
+        && message
+            .round3()
+            .is_some_and(|message| verify(signature, message, pk).is_ok())
+}
+
+//Hands ownership of a buffer to the host, which releases it with `dealloc`
+fn into_raw(mut bytes: Vec<u8>) -> *mut u8 {
+    let ptr = bytes.as_mut_ptr();
+    std::mem::forget(bytes);
+    ptr
+}
+
 /// Allocate memory into the module's linear memory
 /// and return the offset to the start of the block.
 #[no_mangle]
@@ -14,32 +123,105 @@ pub fn alloc(len: usize) -> *mut u8 {
     std::mem::forget(buf);
     // return the pointer so the runtime
     // can write data at this offset
-    return ptr;
+    ptr
 }
 
+/// Signs `data` with a freshly generated keypair and returns pointers to the
+/// signature and public key, or a pair of nulls if no randomness is available.
+///
+/// # Safety
+///
+/// `data` must be a block of `data_len` bytes obtained from `alloc`; it is
+/// consumed by this call.
 #[no_mangle]
 pub unsafe fn sign_data(data: *mut u8, data_len: usize) -> (*mut u8, *mut u8) {
     //Reading data from linear memory
     let data = Vec::from_raw_parts(data, data_len, data_len);
     //Generating Key Pair
-    let keys = Keypair::generate();
-    //Extracting pk
-    let mut pk = keys.public.to_vec();
+    let Some((pk, sk)) = new_keypair() else {
+        return (std::ptr::null_mut(), std::ptr::null_mut());
+    };
     //Signing the data
-    let mut signature = keys.sign(&data).to_vec();
-    //Converting signature and pk to raw pointers
-    let sig_ptr = signature.as_mut_ptr();
-    let pk_ptr = pk.as_mut_ptr();
-    // take ownership of the memory block and
-    // ensure that its destructor is not
-    // called when the object goes out of scope
-    // at the end of the function
-    std::mem::forget(signature);
-    std::mem::forget(pk);
-    //Returning the pointers
-    (sig_ptr, pk_ptr)
+    let signature = sign_message(&Message::Plain(data), &sk).unwrap();
+    //Returning the pointers, owned by the host from now on
+    (into_raw(signature), into_raw(pk))
 }
 
+/// Generates a keypair and returns pointers to the public key and secret key,
+/// or a pair of nulls if no randomness is available.
+///
+/// Round-3 builds return `PUBLICKEYBYTES`/`SECRETKEYBYTES` blocks; `ml-dsa`
+/// builds return the tagged encodings of `signing_formats::mldsa`. The host is
+/// expected to copy both keys out and release the blocks with `dealloc`; the
+/// secret key can later be passed back to `sign_with_secret_key`.
+#[no_mangle]
+pub fn generate_keypair() -> (*mut u8, *mut u8) {
+    //Generating Key Pair
+    let Some((pk, sk)) = new_keypair() else {
+        return (std::ptr::null_mut(), std::ptr::null_mut());
+    };
+    // hand ownership of both blocks to the host
+    (into_raw(pk), into_raw(sk))
+}
+
+/// Derives a keypair from a 32-byte `seed` and returns pointers to the public
+/// key and secret key, in the same encodings as `generate_keypair`, or a pair
+/// of nulls if `seed_len` is not 32.
+///
+/// The seed is the one key generation would otherwise draw from the system
+/// (the FIPS 204 `ξ` for `ml-dsa` builds). Signing is deterministic in both
+/// backends, so a seed pins every signature made with its key, which keeps
+/// test fixtures stable. Seeds must be secret and random outside of tests.
+///
+/// # Safety
+///
+/// `seed` must be a block of `seed_len` bytes obtained from `alloc`; it is
+/// consumed by this call.
+#[no_mangle]
+pub unsafe fn generate_keypair_from_seed(seed: *mut u8, seed_len: usize) -> (*mut u8, *mut u8) {
+    //Reading seed from linear memory
+    let seed = Vec::from_raw_parts(seed, seed_len, seed_len);
+    let Ok(seed) = <[u8; SEED_BYTES]>::try_from(seed.as_slice()) else {
+        return (std::ptr::null_mut(), std::ptr::null_mut());
+    };
+    let (pk, sk) = seeded_keypair(&seed);
+    // hand ownership of both blocks to the host
+    (into_raw(pk), into_raw(sk))
+}
+
+/// Signs `data` with a caller-supplied secret key and returns a pointer to
+/// the signature, or null if `sk` is not a secret key of this build.
+///
+/// # Safety
+///
+/// `data` and `sk` must be blocks of `data_len` and `sk_len` bytes obtained
+/// from `alloc`; both are consumed by this call.
+#[no_mangle]
+pub unsafe fn sign_with_secret_key(
+    data: *mut u8,
+    data_len: usize,
+    sk: *mut u8,
+    sk_len: usize,
+) -> *mut u8 {
+    //Reading data and sk from linear memory
+    let data = Vec::from_raw_parts(data, data_len, data_len);
+    let sk = Vec::from_raw_parts(sk, sk_len, sk_len);
+
+    //Signing the data
+    match sign_message(&Message::Plain(data), &sk) {
+        Some(signature) => into_raw(signature),
+        None => std::ptr::null_mut(),
+    }
+}
+
+/// Verifies `signature` over `data` against `pk`, returning 1 if valid and 0
+/// otherwise. `ml-dsa` builds also accept untagged round-3 keys, so existing
+/// signatures keep verifying.
+///
+/// # Safety
+///
+/// All three pointers must be blocks of the given lengths obtained from
+/// `alloc`; they are consumed by this call.
 #[no_mangle]
 pub unsafe fn verify_data(
     data: *mut u8,
@@ -55,17 +237,319 @@ pub unsafe fn verify_data(
     let pk = Vec::from_raw_parts(pk, pk_len, pk_len);
 
     //Verifying the signature
-    match verify(&signature, &data, &pk) {
-        Ok(_) => return 1,
-        Err(_) => return 0,
+    verify_message(&Message::Plain(data), &signature, &pk) as i32
+}
+
+/// Signs `data` under `context` with a caller-supplied ML-DSA secret key and
+/// returns a pointer to the signature, or null if the context is longer than
+/// 255 bytes or `sk` is not an ML-DSA secret key of this build.
+///
+/// # Safety
+///
+/// `data`, `context` and `sk` must be blocks of the given lengths obtained
+/// from `alloc`; all three are consumed by this call.
+#[no_mangle]
+pub unsafe fn sign_with_context(
+    data: *mut u8,
+    data_len: usize,
+    context: *mut u8,
+    context_len: usize,
+    sk: *mut u8,
+    sk_len: usize,
+) -> *mut u8 {
+    //Reading data, context and sk from linear memory
+    let data = Vec::from_raw_parts(data, data_len, data_len);
+    let context = Vec::from_raw_parts(context, context_len, context_len);
+    let sk = Vec::from_raw_parts(sk, sk_len, sk_len);
+    //Signing the framed message
+    match context_message(&context, &data).and_then(|m| sign_message(&Message::Framed(m), &sk)) {
+        Some(signature) => into_raw(signature),
+        None => std::ptr::null_mut(),
+    }
+}
+
+/// Verifies `signature` over `data` under `context` against `pk`, returning 1
+/// if valid and 0 otherwise. A signature made under a different context, or
+/// with `sign_data`/`sign_with_secret_key` over any bytes, never verifies, and
+/// neither does anything under a round-3 key.
+///
+/// # Safety
+///
+/// All four pointers must be blocks of the given lengths obtained from
+/// `alloc`; they are consumed by this call.
+#[no_mangle]
+#[allow(clippy::too_many_arguments)]
+pub unsafe fn verify_with_context(
+    data: *mut u8,
+    data_len: usize,
+    context: *mut u8,
+    context_len: usize,
+    signature: *mut u8,
+    signature_len: usize,
+    pk: *mut u8,
+    pk_len: usize,
+) -> i32 {
+    //Reading data, context, signature and pk from linear memory
+    let data = Vec::from_raw_parts(data, data_len, data_len);
+    let context = Vec::from_raw_parts(context, context_len, context_len);
+    let signature = Vec::from_raw_parts(signature, signature_len, signature_len);
+    let pk = Vec::from_raw_parts(pk, pk_len, pk_len);
+    //Verifying the signature
+    match context_message(&context, &data) {
+        Some(message) => verify_message(&Message::Framed(message), &signature, &pk) as i32,
+        None => 0,
+    }
+}
+
+/// Signs a digest computed by the host with `hash_alg` (1 = SHA-256,
+/// 2 = SHA-512, 3 = SHAKE256 with 64 bytes of output) and returns a pointer to
+/// the signature, or null if the algorithm, digest length or secret key is
+/// invalid. Only ML-DSA keys sign digests.
+///
+/// # Safety
+///
+/// `digest` and `sk` must be blocks of `digest_len` and `sk_len` bytes
+/// obtained from `alloc`; both are consumed by this call.
+#[no_mangle]
+pub unsafe fn sign_prehashed(
+    digest: *mut u8,
+    digest_len: usize,
+    hash_alg: i32,
+    sk: *mut u8,
+    sk_len: usize,
+) -> *mut u8 {
+    //Reading digest and sk from linear memory
+    let digest = Vec::from_raw_parts(digest, digest_len, digest_len);
+    let sk = Vec::from_raw_parts(sk, sk_len, sk_len);
+    //Signing the framed digest
+    match prehash_message(hash_alg, &digest).and_then(|m| sign_message(&Message::Framed(m), &sk)) {
+        Some(signature) => into_raw(signature),
+        None => std::ptr::null_mut(),
+    }
+}
+
+/// Verifies `signature` over a digest computed with `hash_alg` against `pk`,
+/// returning 1 if valid and 0 otherwise (including for an unknown algorithm, a
+/// digest of the wrong length or a round-3 key).
+///
+/// # Safety
+///
+/// All three pointers must be blocks of the given lengths obtained from
+/// `alloc`; they are consumed by this call.
+#[no_mangle]
+pub unsafe fn verify_prehashed(
+    digest: *mut u8,
+    digest_len: usize,
+    hash_alg: i32,
+    signature: *mut u8,
+    signature_len: usize,
+    pk: *mut u8,
+    pk_len: usize,
+) -> i32 {
+    //Reading digest, signature and pk from linear memory
+    let digest = Vec::from_raw_parts(digest, digest_len, digest_len);
+    let signature = Vec::from_raw_parts(signature, signature_len, signature_len);
+    let pk = Vec::from_raw_parts(pk, pk_len, pk_len);
+    //Verifying the signature
+    match prehash_message(hash_alg, &digest) {
+        Some(message) => verify_message(&Message::Framed(message), &signature, &pk) as i32,
+        None => 0,
     }
 }
 
 //deallocator function for a memory block by calling std::mem::drop using the desired value,
 //which effectively takes ownership of data and goes out of scope.
+/// # Safety
+///
+/// `ptr` must be a block of `size` bytes obtained from `alloc` or returned by
+/// one of the exported functions.
 #[no_mangle]
 pub unsafe fn dealloc(ptr: *mut u8, size: usize) {
     let data = Vec::from_raw_parts(ptr, size, size);
 
     std::mem::drop(data);
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use signing_formats::framing::HASH_SHAKE256;
+
+    //Sizes of what this build hands back
+    #[cfg(feature = "ml-dsa")]
+    const SIZES: (usize, usize, usize) = (
+        PARAMETER_SET.public_key_bytes(),
+        mldsa::SECRET_KEY_BYTES,
+        PARAMETER_SET.signature_bytes(),
+    );
+    #[cfg(not(feature = "ml-dsa"))]
+    const SIZES: (usize, usize, usize) = (PUBLICKEYBYTES, SECRETKEYBYTES, SIGNBYTES);
+
+    //Copies `bytes` into a block from `alloc`, as the host does
+    fn block(bytes: &[u8]) -> (*mut u8, usize) {
+        let ptr = alloc(bytes.len());
+        unsafe { ptr.copy_from_nonoverlapping(bytes.as_ptr(), bytes.len()) };
+        (ptr, bytes.len())
+    }
+
+    //Takes back a block of `len` bytes returned by an export
+    fn take(ptr: *mut u8, len: usize) -> Option<Vec<u8>> {
+        (!ptr.is_null()).then(|| unsafe { Vec::from_raw_parts(ptr, len, len) })
+    }
+
+    fn keypair() -> (Vec<u8>, Vec<u8>) {
+        let (seed, seed_len) = block(&[7; SEED_BYTES]);
+        let (pk, sk) = unsafe { generate_keypair_from_seed(seed, seed_len) };
+        (take(pk, SIZES.0).unwrap(), take(sk, SIZES.1).unwrap())
+    }
+
+    fn sign_prehashed_digest(digest: &[u8], hash_alg: i32, sk: &[u8]) -> Option<Vec<u8>> {
+        let ((digest, digest_len), (sk, sk_len)) = (block(digest), block(sk));
+        let signature = unsafe { sign_prehashed(digest, digest_len, hash_alg, sk, sk_len) };
+        take(signature, SIZES.2)
+    }
+
+    fn verify_prehashed_digest(digest: &[u8], hash_alg: i32, signature: &[u8], pk: &[u8]) -> bool {
+        let (digest, digest_len) = block(digest);
+        let ((signature, signature_len), (pk, pk_len)) = (block(signature), block(pk));
+        unsafe {
+            verify_prehashed(
+                digest,
+                digest_len,
+                hash_alg,
+                signature,
+                signature_len,
+                pk,
+                pk_len,
+            ) == 1
+        }
+    }
+
+    fn sign_plain(data: &[u8], sk: &[u8]) -> Option<Vec<u8>> {
+        let ((data, data_len), (sk, sk_len)) = (block(data), block(sk));
+        take(
+            unsafe { sign_with_secret_key(data, data_len, sk, sk_len) },
+            SIZES.2,
+        )
+    }
+
+    fn verify_plain(data: &[u8], signature: &[u8], pk: &[u8]) -> bool {
+        let (data, data_len) = block(data);
+        let ((signature, signature_len), (pk, pk_len)) = (block(signature), block(pk));
+        unsafe { verify_data(data, data_len, signature, signature_len, pk, pk_len) == 1 }
+    }
+
+    fn sign_under_context(data: &[u8], context: &[u8], sk: &[u8]) -> Option<Vec<u8>> {
+        let ((data, data_len), (context, context_len)) = (block(data), block(context));
+        let (sk, sk_len) = block(sk);
+        let signature =
+            unsafe { sign_with_context(data, data_len, context, context_len, sk, sk_len) };
+        take(signature, SIZES.2)
+    }
+
+    fn verify_under_context(data: &[u8], context: &[u8], signature: &[u8], pk: &[u8]) -> bool {
+        let ((data, data_len), (context, context_len)) = (block(data), block(context));
+        let ((signature, signature_len), (pk, pk_len)) = (block(signature), block(pk));
+        unsafe {
+            verify_with_context(
+                data,
+                data_len,
+                context,
+                context_len,
+                signature,
+                signature_len,
+                pk,
+                pk_len,
+            ) == 1
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "ml-dsa")]
+    fn default_build_signs_digests() {
+        let (pk, sk) = keypair();
+        let digest = [0x5a; 64];
+        let signature = sign_prehashed_digest(&digest, HASH_SHAKE256, &sk).unwrap();
+        assert!(verify_prehashed_digest(
+            &digest,
+            HASH_SHAKE256,
+            &signature,
+            &pk
+        ));
+        assert!(!verify_prehashed_digest(
+            &[0xa5; 64],
+            HASH_SHAKE256,
+            &signature,
+            &pk
+        ));
+        //The hash function is part of what is signed
+        assert!(
+            sign_prehashed_digest(&digest, signing_formats::framing::HASH_SHA256, &sk).is_none()
+        );
+        assert!(!verify_prehashed_digest(
+            &digest[..32],
+            signing_formats::framing::HASH_SHA256,
+            &signature,
+            &pk
+        ));
+
+        //A digest signature is no signature over the framed bytes, and vice versa
+        let framed = prehash_message(HASH_SHAKE256, &digest).unwrap();
+        assert!(!verify_plain(&framed, &signature, &pk));
+        let plain = sign_plain(&framed, &sk).unwrap();
+        assert!(!verify_prehashed_digest(
+            &digest,
+            HASH_SHAKE256,
+            &plain,
+            &pk
+        ));
+    }
+
+    #[test]
+    #[cfg(not(feature = "ml-dsa"))]
+    fn round3_builds_refuse_digests() {
+        let (pk, sk) = keypair();
+        let digest = [0x5a; 64];
+        assert!(sign_prehashed_digest(&digest, HASH_SHAKE256, &sk).is_none());
+        //A plain signature over the framed digest must not pass for a digest signature
+        let framed = prehash_message(HASH_SHAKE256, &digest).unwrap();
+        let plain = sign_plain(&framed, &sk).unwrap();
+        assert!(verify_plain(&framed, &plain, &pk));
+        assert!(!verify_prehashed_digest(
+            &digest,
+            HASH_SHAKE256,
+            &plain,
+            &pk
+        ));
+    }
+
+    #[test]
+    #[cfg(feature = "ml-dsa")]
+    fn default_build_signs_under_a_context() {
+        let (pk, sk) = keypair();
+        let (data, context) = (b"release.tar", b"config-file");
+        let signature = sign_under_context(data, context, &sk).unwrap();
+        assert!(verify_under_context(data, context, &signature, &pk));
+        assert!(!verify_under_context(data, b"other", &signature, &pk));
+        assert!(sign_under_context(data, &[0; 256], &sk).is_none());
+
+        //A context signature is no signature over the framed bytes, and vice versa
+        let framed = context_message(context, data).unwrap();
+        assert!(!verify_plain(&framed, &signature, &pk));
+        let plain = sign_plain(&framed, &sk).unwrap();
+        assert!(!verify_under_context(data, context, &plain, &pk));
+    }
+
+    #[test]
+    #[cfg(not(feature = "ml-dsa"))]
+    fn round3_builds_refuse_contexts() {
+        let (pk, sk) = keypair();
+        let (data, context) = (b"release.tar", b"config-file");
+        assert!(sign_under_context(data, context, &sk).is_none());
+        //A plain signature over the framed message must not pass under the context
+        let framed = context_message(context, data).unwrap();
+        let plain = sign_plain(&framed, &sk).unwrap();
+        assert!(verify_plain(&framed, &plain, &pk));
+        assert!(!verify_under_context(data, context, &plain, &pk));
+    }
+}
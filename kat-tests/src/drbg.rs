@@ -0,0 +1,74 @@
+//! The AES-256 CTR DRBG of the NIST PQC submission package (`rng.c`), which
+//! produced every seed, message and random buffer in the KAT files.
+
+use aes::cipher::{BlockEncrypt, KeyInit};
+use aes::Aes256;
+
+/// Deterministic generator seeded with 48 bytes of entropy, without a
+/// personalization string.
+pub struct Drbg {
+    key: [u8; 32],
+    v: [u8; 16],
+}
+
+impl Drbg {
+    pub fn new(entropy: &[u8; 48]) -> Drbg {
+        let mut drbg = Drbg {
+            key: [0u8; 32],
+            v: [0u8; 16],
+        };
+        drbg.update(Some(entropy));
+        drbg
+    }
+
+    /// The generator PQCgenKAT starts from: entropy `0, 1, ..., 47`. The
+    /// per-entry `seed` values of a KAT file are its successive outputs.
+    pub fn nist() -> Drbg {
+        let mut entropy = [0u8; 48];
+        for (i, byte) in entropy.iter_mut().enumerate() {
+            *byte = i as u8;
+        }
+        Drbg::new(&entropy)
+    }
+
+    pub fn fill(&mut self, out: &mut [u8]) {
+        for chunk in out.chunks_mut(16) {
+            let block = self.next_block();
+            chunk.copy_from_slice(&block[..chunk.len()]);
+        }
+        self.update(None);
+    }
+
+    pub fn bytes<const N: usize>(&mut self) -> [u8; N] {
+        let mut out = [0u8; N];
+        self.fill(&mut out);
+        out
+    }
+
+    //Increments V as a big-endian counter and encrypts it
+    fn next_block(&mut self) -> [u8; 16] {
+        for byte in self.v.iter_mut().rev() {
+            *byte = byte.wrapping_add(1);
+            if *byte != 0 {
+                break;
+            }
+        }
+        let mut block = self.v.into();
+        Aes256::new(&self.key.into()).encrypt_block(&mut block);
+        block.into()
+    }
+
+    fn update(&mut self, provided: Option<&[u8; 48]>) {
+        let mut temp = [0u8; 48];
+        for chunk in temp.chunks_mut(16) {
+            chunk.copy_from_slice(&self.next_block());
+        }
+        if let Some(provided) = provided {
+            for (t, p) in temp.iter_mut().zip(provided) {
+                *t ^= p;
+            }
+        }
+        self.key.copy_from_slice(&temp[..32]);
+        self.v.copy_from_slice(&temp[32..]);
+    }
+}
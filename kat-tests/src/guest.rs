@@ -0,0 +1,64 @@
+//! A minimal wasmtime host for the guest modules, speaking the same ABI as
+//! wasm-api: inputs are copied into blocks from the `alloc` export and consumed
+//! by the call, outputs are read back and released with `dealloc`.
+
+use std::path::Path;
+
+use anyhow::Context;
+use wasmtime::*;
+use wasmtime_wasi::{WasiCtx, WasiCtxBuilder};
+
+/// One instance of a guest module in its own store.
+pub struct Guest {
+    store: Store<WasiCtx>,
+    instance: Instance,
+}
+
+impl Guest {
+    pub fn load(path: &Path) -> anyhow::Result<Guest> {
+        let engine = Engine::default();
+        let module = Module::from_file(&engine, path)
+            .with_context(|| format!("failed to load guest module `{}`", path.display()))?;
+        let mut linker = Linker::new(&engine);
+        wasmtime_wasi::add_to_linker(&mut linker, |wasi| wasi)?;
+        let mut store = Store::new(&engine, WasiCtxBuilder::new().inherit_stdio().build());
+        let instance = linker.instantiate(&mut store, &module)?;
+        Ok(Guest { store, instance })
+    }
+
+    /// Calls the export `name`.
+    pub fn call<Params, Results>(&mut self, name: &str, params: Params) -> anyhow::Result<Results>
+    where
+        Params: WasmParams,
+        Results: WasmResults,
+    {
+        let func = self
+            .instance
+            .get_typed_func::<Params, Results>(&mut self.store, name)
+            .with_context(|| format!("export `{}`", name))?;
+        func.call(&mut self.store, params)
+    }
+
+    /// Copies `bytes` into a fresh guest block, returning its pointer and length
+    /// as the exports expect them.
+    pub fn write(&mut self, bytes: &[u8]) -> anyhow::Result<(i32, i32)> {
+        let ptr: i32 = self.call("alloc", bytes.len() as i32)?;
+        self.memory()?.write(&mut self.store, ptr as usize, bytes)?;
+        Ok((ptr, bytes.len() as i32))
+    }
+
+    /// Copies `len` bytes out of the block at `ptr` and releases it.
+    pub fn read(&mut self, ptr: i32, len: usize) -> anyhow::Result<Vec<u8>> {
+        anyhow::ensure!(ptr != 0, "guest rejected the input");
+        let mut bytes = vec![0u8; len];
+        self.memory()?.read(&self.store, ptr as usize, &mut bytes)?;
+        self.call::<(i32, i32), ()>("dealloc", (ptr, len as i32))?;
+        Ok(bytes)
+    }
+
+    fn memory(&mut self) -> anyhow::Result<Memory> {
+        self.instance
+            .get_memory(&mut self.store, "memory")
+            .context("failed to find `memory` export")
+    }
+}
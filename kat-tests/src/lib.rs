@@ -0,0 +1,37 @@
+//! Known-answer tests for the native pqc_dilithium/pqc_kyber builds and the
+//! wasm guests compiled from them.
+//!
+//! The vectors in `vectors/` are the first entry of the NIST round-3 KAT
+//! files. They are replayed through the NIST AES-256 CTR DRBG (`drbg`), and
+//! the same inputs are fed to the guests in `../wasm-api` through wasmtime
+//! (`guest`), so the wasm build is checked byte for byte against both the
+//! reference values and the native crate.
+//!
+//! The FIPS 204 and FIPS 203 code is checked against NIST ACVP vectors
+//! (`ML-DSA-*.rsp`, `ML-KEM-*.rsp`), trimmed from the published JSON to a few
+//! cases per parameter set; each file names its source. ML-DSA keys go
+//! through `signing_formats` and the ML-DSA guests, ML-KEM through
+//! test-kyber. Both keep secret keys as seeds, so the cases that start from
+//! an expanded key (ML-DSA sigGen, ML-KEM decapsulation) are run on the
+//! ml-dsa and ml-kem versions they are built on.
+
+pub mod drbg;
+pub mod guest;
+pub mod rsp;
+
+use std::path::PathBuf;
+
+/// Path of a file under `vectors/`.
+pub fn vector_path(name: &str) -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("vectors")
+        .join(name)
+}
+
+/// Path of a compiled guest module next to the service that loads it.
+pub fn guest_path(name: &str) -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("..")
+        .join("wasm-api")
+        .join(name)
+}
@@ -0,0 +1,61 @@
+//! Reader for NIST `.rsp` response files: `name = value` lines grouped into
+//! entries that each start with `count`. The ACVP vectors are kept in the same
+//! form, with the test case id as `count`.
+
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+/// One KAT entry, e.g. `count`, `seed`, `pk`, `sk`, `ct`, `ss`.
+pub struct Entry(HashMap<String, String>);
+
+impl Entry {
+    /// A hex-encoded field, decoded.
+    pub fn bytes(&self, name: &str) -> Vec<u8> {
+        hex::decode(self.field(name)).unwrap_or_else(|e| panic!("`{}` is not hex: {}", name, e))
+    }
+
+    /// A decimal field such as `count` or `mlen`.
+    pub fn number(&self, name: &str) -> usize {
+        self.field(name)
+            .parse()
+            .unwrap_or_else(|e| panic!("`{}` is not a number: {}", name, e))
+    }
+
+    /// A field as written, such as an ACVP `parameterSet` or `testPassed`.
+    pub fn field(&self, name: &str) -> &str {
+        self.0
+            .get(name)
+            .unwrap_or_else(|| panic!("entry has no `{}`", name))
+    }
+}
+
+/// Parses `text`, skipping blank lines and `#` comments.
+pub fn parse(text: &str) -> Vec<Entry> {
+    let mut entries: Vec<Entry> = Vec::new();
+    for line in text.lines().map(str::trim) {
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let (name, value) = line
+            .split_once(" = ")
+            .unwrap_or_else(|| panic!("malformed line `{}`", line));
+        if name == "count" {
+            entries.push(Entry(HashMap::new()));
+        }
+        let entry = entries
+            .last_mut()
+            .unwrap_or_else(|| panic!("`{}` appears before the first `count`", name));
+        entry.0.insert(name.to_string(), value.to_string());
+    }
+    entries
+}
+
+/// Reads a vector file, returning its raw text alongside the parsed entries
+/// so that callers can check the file against a published digest.
+pub fn load(path: &Path) -> (String, Vec<Entry>) {
+    let text = fs::read_to_string(path)
+        .unwrap_or_else(|e| panic!("cannot read {}: {}", path.display(), e));
+    let entries = parse(&text);
+    (text, entries)
+}
@@ -0,0 +1,173 @@
+use kat_tests::drbg::Drbg;
+use kat_tests::guest::Guest;
+use kat_tests::rsp::{self, Entry};
+use kat_tests::{guest_path, vector_path};
+use pqc_dilithium::{
+    crypto_sign_keypair, crypto_sign_signature, crypto_sign_verify, PUBLICKEYBYTES, SECRETKEYBYTES,
+    SIGNBYTES,
+};
+use sha2::{Digest, Sha256};
+
+/// Vector file, SHA-256 of its contents as published in PQClean's `META.yml`
+/// (`nistkat-sha256`) for round 3.1, and the round-3 and ML-DSA guests of the
+/// same level.
+const LEVELS: [(&str, &str, &str, &str); 3] = [
+    (
+        "PQCsignKAT_Dilithium2.rsp",
+        "faa8998108fa541309c9df5044018c5d26cc23654594bef639dd64b838646cbd",
+        "dilithium2_code_sign_module.wasm.multivalue.wasm",
+        "mldsa44_code_sign_module.wasm.multivalue.wasm",
+    ),
+    (
+        "PQCsignKAT_Dilithium3.rsp",
+        "8439f580566c46b99449b2cbbd597ce59bcd5d184b90c1108b79a08f6bdbbcb1",
+        "dilithium3_code_sign_module.wasm.multivalue.wasm",
+        "mldsa65_code_sign_module.wasm.multivalue.wasm",
+    ),
+    (
+        "PQCsignKAT_Dilithium5.rsp",
+        "984ea5f06b13778292f60ecc07301af76e375f1bb9f4a39d676513439e1e83a2",
+        "dilithium5_code_sign_module.wasm.multivalue.wasm",
+        "mldsa87_code_sign_module.wasm.multivalue.wasm",
+    ),
+];
+
+//The level pqc_dilithium was compiled with, picked out by its key size
+fn compiled_level() -> (&'static str, &'static str, &'static str, &'static str) {
+    match PUBLICKEYBYTES {
+        1312 => LEVELS[0],
+        2592 => LEVELS[2],
+        _ => LEVELS[1],
+    }
+}
+
+fn load(file: &str) -> Vec<Entry> {
+    rsp::load(&vector_path(file)).1
+}
+
+#[test]
+fn vectors_are_the_published_nist_entries() {
+    for (file, digest, _, _) in LEVELS {
+        let (text, entries) = rsp::load(&vector_path(file));
+        assert_eq!(
+            hex::encode(Sha256::digest(text.as_bytes())),
+            digest,
+            "{}",
+            file
+        );
+        assert!(!entries.is_empty(), "{}", file);
+    }
+}
+
+#[test]
+fn drbg_reproduces_request_values() {
+    //PQCgenKAT draws each entry's seed and then its message from one generator
+    for (file, _, _, _) in LEVELS {
+        let mut drbg = Drbg::nist();
+        for entry in load(file) {
+            assert_eq!(drbg.bytes::<48>().to_vec(), entry.bytes("seed"), "{}", file);
+            let mut msg = vec![0u8; entry.number("mlen")];
+            drbg.fill(&mut msg);
+            assert_eq!(msg, entry.bytes("msg"), "{}", file);
+        }
+    }
+}
+
+#[test]
+fn native_matches_vectors() {
+    let (file, _, _, _) = compiled_level();
+    for entry in load(file) {
+        let seed: [u8; 48] = entry.bytes("seed").try_into().unwrap();
+        let keygen_seed = Drbg::new(&seed).bytes::<32>();
+
+        let mut pk = [0u8; PUBLICKEYBYTES];
+        let mut sk = [0u8; SECRETKEYBYTES];
+        crypto_sign_keypair(&mut pk, &mut sk, Some(&keygen_seed));
+        assert_eq!(pk.to_vec(), entry.bytes("pk"));
+        assert_eq!(sk.to_vec(), entry.bytes("sk"));
+
+        //sm = signature || msg
+        let msg = entry.bytes("msg");
+        let sm = entry.bytes("sm");
+        assert_eq!(sm.len(), entry.number("smlen"));
+        let mut signature = [0u8; SIGNBYTES];
+        crypto_sign_signature(&mut signature, &msg, &sk);
+        assert_eq!(signature[..], sm[..SIGNBYTES]);
+        assert_eq!(sm[SIGNBYTES..], msg[..]);
+        assert!(crypto_sign_verify(&signature, &msg, &pk).is_ok());
+    }
+}
+
+fn guest_sign(guest: &mut Guest, msg: &[u8], sk: &[u8], len: usize) -> Vec<u8> {
+    let (msg_ptr, msg_len) = guest.write(msg).unwrap();
+    let (sk_ptr, sk_len) = guest.write(sk).unwrap();
+    let signature: i32 = guest
+        .call("sign_with_secret_key", (msg_ptr, msg_len, sk_ptr, sk_len))
+        .unwrap();
+    guest.read(signature, len).unwrap()
+}
+
+fn guest_verify(guest: &mut Guest, msg: &[u8], signature: &[u8], pk: &[u8]) -> bool {
+    let (msg_ptr, msg_len) = guest.write(msg).unwrap();
+    let (sig_ptr, sig_len) = guest.write(signature).unwrap();
+    let (pk_ptr, pk_len) = guest.write(pk).unwrap();
+    let valid: i32 = guest
+        .call(
+            "verify_data",
+            (msg_ptr, msg_len, sig_ptr, sig_len, pk_ptr, pk_len),
+        )
+        .unwrap();
+    valid == 1
+}
+
+#[test]
+fn guests_match_vectors() {
+    for (file, _, round3, ml_dsa) in LEVELS {
+        let mut guest = Guest::load(&guest_path(round3)).unwrap();
+        //ML-DSA builds keep verifying untagged round-3 signatures
+        let mut ml_dsa = Guest::load(&guest_path(ml_dsa)).unwrap();
+        for entry in load(file) {
+            let (msg, pk, sk) = (entry.bytes("msg"), entry.bytes("pk"), entry.bytes("sk"));
+            let sm = entry.bytes("sm");
+            let signature = &sm[..sm.len() - msg.len()];
+
+            assert_eq!(
+                guest_sign(&mut guest, &msg, &sk, signature.len()),
+                signature,
+                "{}",
+                round3
+            );
+            assert!(guest_verify(&mut guest, &msg, signature, &pk), "{}", round3);
+            assert!(guest_verify(&mut ml_dsa, &msg, signature, &pk), "{}", file);
+
+            let mut tampered = msg.clone();
+            tampered[0] ^= 1;
+            assert!(
+                !guest_verify(&mut guest, &tampered, signature, &pk),
+                "{}",
+                round3
+            );
+        }
+    }
+}
+
+#[test]
+fn guest_matches_native() {
+    //Beyond the vectors: arbitrary keys and messages sign identically in both builds
+    let (_, _, round3, _) = compiled_level();
+    let mut guest = Guest::load(&guest_path(round3)).unwrap();
+    let mut drbg = Drbg::nist();
+    for len in [0, 1, 64, 4096] {
+        let keygen_seed = drbg.bytes::<32>();
+        let mut msg = vec![0u8; len];
+        drbg.fill(&mut msg);
+
+        let mut pk = [0u8; PUBLICKEYBYTES];
+        let mut sk = [0u8; SECRETKEYBYTES];
+        crypto_sign_keypair(&mut pk, &mut sk, Some(&keygen_seed));
+        let mut signature = [0u8; SIGNBYTES];
+        crypto_sign_signature(&mut signature, &msg, &sk);
+
+        assert_eq!(guest_sign(&mut guest, &msg, &sk, SIGNBYTES), signature);
+    }
+}
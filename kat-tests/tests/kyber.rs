@@ -0,0 +1,121 @@
+use kat_tests::drbg::Drbg;
+use kat_tests::guest::Guest;
+use kat_tests::rsp::{self, Entry};
+use kat_tests::{guest_path, vector_path};
+use pqc_kyber::{
+    crypto_kem_enc, crypto_kem_keypair, decapsulate, KYBER_CIPHERTEXTBYTES, KYBER_K,
+    KYBER_PUBLICKEYBYTES, KYBER_SECRETKEYBYTES, KYBER_SSBYTES,
+};
+use sha2::{Digest, Sha256};
+
+/// Vector file and SHA-256 of its contents as published in PQClean's
+/// `META.yml` (`nistkat-sha256`) for round-3 Kyber512, Kyber768 and Kyber1024.
+const LEVELS: [(&str, &str); 3] = [
+    (
+        "PQCkemKAT_1632.rsp",
+        "bb0481d3325d828817900b709d23917cefbc10026fc857f098979451f67bb0ca",
+    ),
+    (
+        "PQCkemKAT_2400.rsp",
+        "89e82a5bf2d4ddb2c6444e10409e6d9ca65dafbca67d1a0db2c9b54920a29172",
+    ),
+    (
+        "PQCkemKAT_3168.rsp",
+        "5afcf2a568ad32d49b55105b032af1850f03f3888ff9e2a72f4059c58e968f60",
+    ),
+];
+
+//The KEM guest is only built for Kyber768
+const GUEST_VECTORS: &str = "PQCkemKAT_2400.rsp";
+const GUEST_MODULE: &str = "kyber_kem_module.wasm.multivalue.wasm";
+
+fn load(file: &str) -> Vec<Entry> {
+    rsp::load(&vector_path(file)).1
+}
+
+#[test]
+fn vectors_are_the_published_nist_entries() {
+    for (file, digest) in LEVELS {
+        let (text, entries) = rsp::load(&vector_path(file));
+        assert_eq!(
+            hex::encode(Sha256::digest(text.as_bytes())),
+            digest,
+            "{}",
+            file
+        );
+        assert!(!entries.is_empty(), "{}", file);
+    }
+}
+
+#[test]
+fn native_matches_vectors() {
+    let (file, _) = LEVELS[KYBER_K - 2];
+    let mut requests = Drbg::nist();
+    let mut rng = rand::thread_rng();
+    for entry in load(file) {
+        let seed = requests.bytes::<48>();
+        assert_eq!(seed.to_vec(), entry.bytes("seed"));
+
+        //Key generation draws the IND-CPA seed and then z, encapsulation the message
+        let mut drbg = Drbg::new(&seed);
+        let (indcpa, z, coins) = (drbg.bytes::<32>(), drbg.bytes::<32>(), drbg.bytes::<32>());
+
+        let mut pk = [0u8; KYBER_PUBLICKEYBYTES];
+        let mut sk = [0u8; KYBER_SECRETKEYBYTES];
+        crypto_kem_keypair(&mut pk, &mut sk, &mut rng, Some((&indcpa, &z))).unwrap();
+        assert_eq!(pk.to_vec(), entry.bytes("pk"));
+        assert_eq!(sk.to_vec(), entry.bytes("sk"));
+
+        let mut ct = [0u8; KYBER_CIPHERTEXTBYTES];
+        let mut ss = [0u8; KYBER_SSBYTES];
+        crypto_kem_enc(&mut ct, &mut ss, &pk, &mut rng, Some(&coins)).unwrap();
+        assert_eq!(ct.to_vec(), entry.bytes("ct"));
+        assert_eq!(ss.to_vec(), entry.bytes("ss"));
+
+        assert_eq!(decapsulate(&ct, &sk).unwrap().to_vec(), entry.bytes("ss"));
+    }
+}
+
+fn guest_decapsulate(guest: &mut Guest, ct: &[u8], sk: &[u8]) -> Vec<u8> {
+    let (ct_ptr, ct_len) = guest.write(ct).unwrap();
+    let (sk_ptr, sk_len) = guest.write(sk).unwrap();
+    let ss: i32 = guest
+        .call("decapsulate", (ct_ptr, ct_len, sk_ptr, sk_len))
+        .unwrap();
+    guest.read(ss, KYBER_SSBYTES).unwrap()
+}
+
+#[test]
+fn guest_matches_vectors() {
+    let mut guest = Guest::load(&guest_path(GUEST_MODULE)).unwrap();
+    for entry in load(GUEST_VECTORS) {
+        let ss = guest_decapsulate(&mut guest, &entry.bytes("ct"), &entry.bytes("sk"));
+        assert_eq!(ss, entry.bytes("ss"));
+
+        //Implicit rejection: a corrupted ciphertext yields a different, deterministic secret
+        let mut ct = entry.bytes("ct");
+        ct[0] ^= 1;
+        let rejected = guest_decapsulate(&mut guest, &ct, &entry.bytes("sk"));
+        assert_ne!(rejected, entry.bytes("ss"));
+        if KYBER_K == 3 {
+            assert_eq!(
+                rejected,
+                decapsulate(&ct, &entry.bytes("sk")).unwrap().to_vec()
+            );
+        }
+    }
+}
+
+#[test]
+fn guest_encapsulation_decapsulates_natively() {
+    if KYBER_K != 3 {
+        return;
+    }
+    let entry = &load(GUEST_VECTORS)[0];
+    let mut guest = Guest::load(&guest_path(GUEST_MODULE)).unwrap();
+    let (pk_ptr, pk_len) = guest.write(&entry.bytes("pk")).unwrap();
+    let (ct, ss): (i32, i32) = guest.call("encapsulate", (pk_ptr, pk_len)).unwrap();
+    let ct = guest.read(ct, KYBER_CIPHERTEXTBYTES).unwrap();
+    let ss = guest.read(ss, KYBER_SSBYTES).unwrap();
+    assert_eq!(decapsulate(&ct, &entry.bytes("sk")).unwrap().to_vec(), ss);
+}
@@ -0,0 +1,143 @@
+use kat_tests::guest::Guest;
+use kat_tests::rsp::{self, Entry};
+use kat_tests::{guest_path, vector_path};
+use ml_dsa::{
+    ExpandedSigningKey, ExpandedSigningKeyBytes, MlDsa44, MlDsa65, MlDsa87, MlDsaParams,
+    SigningKey, B32,
+};
+use signing_formats::mldsa::{self, ParameterSet, SECRET_KEY_BYTES};
+
+/// Each parameter set and its ML-DSA guest.
+const GUESTS: [(ParameterSet, &str); 3] = [
+    (
+        ParameterSet::MlDsa44,
+        "mldsa44_code_sign_module.wasm.multivalue.wasm",
+    ),
+    (
+        ParameterSet::MlDsa65,
+        "mldsa65_code_sign_module.wasm.multivalue.wasm",
+    ),
+    (
+        ParameterSet::MlDsa87,
+        "mldsa87_code_sign_module.wasm.multivalue.wasm",
+    ),
+];
+
+fn load(file: &str) -> Vec<Entry> {
+    rsp::load(&vector_path(file)).1
+}
+
+fn parameter_set(entry: &Entry) -> ParameterSet {
+    let name = entry.field("parameterSet");
+    ParameterSet::ALL
+        .into_iter()
+        .find(|set| set.name() == name)
+        .unwrap_or_else(|| panic!("unknown parameter set `{}`", name))
+}
+
+//Keys and signatures are stored behind the tag of their parameter set
+fn tagged(set: ParameterSet, bytes: &[u8]) -> Vec<u8> {
+    [&[set.tag()], bytes].concat()
+}
+
+#[allow(deprecated)]
+fn expanded_secret_key<P: MlDsaParams>(seed: &[u8; 32]) -> Vec<u8> {
+    SigningKey::<P>::from_seed(&(*seed).into())
+        .expanded_key()
+        .to_expanded()
+        .to_vec()
+}
+
+#[allow(deprecated)]
+fn sign_expanded<P: MlDsaParams>(sk: &[u8], message: &[u8], rnd: &B32) -> Vec<u8> {
+    let sk = ExpandedSigningKeyBytes::<P>::try_from(sk).unwrap();
+    ExpandedSigningKey::<P>::from_expanded(&sk)
+        .sign_internal(&[message], rnd)
+        .encode()
+        .to_vec()
+}
+
+fn guest_keypair(guest: &mut Guest, seed: &[u8], set: ParameterSet) -> (Vec<u8>, Vec<u8>) {
+    let (seed_ptr, seed_len) = guest.write(seed).unwrap();
+    let (pk, sk): (i32, i32) = guest
+        .call("generate_keypair_from_seed", (seed_ptr, seed_len))
+        .unwrap();
+    (
+        guest.read(pk, set.public_key_bytes()).unwrap(),
+        guest.read(sk, SECRET_KEY_BYTES).unwrap(),
+    )
+}
+
+#[test]
+fn key_generation_matches_acvp() {
+    for entry in load("ML-DSA-keyGen.rsp") {
+        let (set, count) = (parameter_set(&entry), entry.number("count"));
+        let seed: [u8; 32] = entry.bytes("seed").try_into().unwrap();
+        let (pk, sk) = mldsa::keypair_from_seed(set, &seed);
+        assert_eq!(pk, tagged(set, &entry.bytes("pk")), "{}", count);
+        assert_eq!(sk, tagged(set, &seed), "{}", count);
+
+        //The stored seed expands to the published secret key
+        let expanded = match set {
+            ParameterSet::MlDsa44 => expanded_secret_key::<MlDsa44>(&seed),
+            ParameterSet::MlDsa65 => expanded_secret_key::<MlDsa65>(&seed),
+            ParameterSet::MlDsa87 => expanded_secret_key::<MlDsa87>(&seed),
+        };
+        assert_eq!(expanded, entry.bytes("sk"), "{}", count);
+    }
+}
+
+#[test]
+fn guests_generate_acvp_keys() {
+    for (set, module) in GUESTS {
+        let mut guest = Guest::load(&guest_path(module)).unwrap();
+        for entry in load("ML-DSA-keyGen.rsp") {
+            if parameter_set(&entry) != set {
+                continue;
+            }
+            let seed = entry.bytes("seed");
+            let (pk, sk) = guest_keypair(&mut guest, &seed, set);
+            assert_eq!(pk, tagged(set, &entry.bytes("pk")), "{}", module);
+            assert_eq!(sk, tagged(set, &seed), "{}", module);
+        }
+    }
+}
+
+#[test]
+fn signing_matches_acvp() {
+    for entry in load("ML-DSA-sigGen.rsp") {
+        //Deterministic signing, which is what the service does, takes rnd = 0
+        let rnd = match entry.field("deterministic") {
+            "true" => B32::default(),
+            _ => B32::try_from(&entry.bytes("rnd")[..]).unwrap(),
+        };
+        let (sk, message) = (entry.bytes("sk"), entry.bytes("message"));
+        let signature = match parameter_set(&entry) {
+            ParameterSet::MlDsa44 => sign_expanded::<MlDsa44>(&sk, &message, &rnd),
+            ParameterSet::MlDsa65 => sign_expanded::<MlDsa65>(&sk, &message, &rnd),
+            ParameterSet::MlDsa87 => sign_expanded::<MlDsa87>(&sk, &message, &rnd),
+        };
+        assert_eq!(
+            signature,
+            entry.bytes("signature"),
+            "{}",
+            entry.number("count")
+        );
+    }
+}
+
+#[test]
+fn verification_matches_acvp() {
+    for entry in load("ML-DSA-sigVer.rsp") {
+        let set = parameter_set(&entry);
+        let pk = tagged(set, &entry.bytes("pk"));
+        let signature = tagged(set, &entry.bytes("signature"));
+        assert_eq!(
+            mldsa::verify(&entry.bytes("message"), &signature, &pk),
+            entry.field("testPassed") == "true",
+            "{} ({})",
+            entry.number("count"),
+            entry.field("reason")
+        );
+    }
+}
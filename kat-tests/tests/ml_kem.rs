@@ -0,0 +1,136 @@
+use kat_tests::rsp::{self, Entry};
+use kat_tests::vector_path;
+//Decapsulation keys are only published in the expanded form
+#[allow(deprecated)]
+use ml_kem::ExpandedKeyEncoding;
+use ml_kem::{Decapsulate, DecapsulationKey1024, DecapsulationKey512, DecapsulationKey768};
+use rand::{CryptoRng, RngCore};
+use test_kyber::ParamSet;
+
+const PARAMETER_SETS: [ParamSet; 3] = [ParamSet::MlKem512, ParamSet::MlKem768, ParamSet::MlKem1024];
+
+/// Hands out fixed bytes as randomness, which is how ACVP pins d, z and m.
+struct Replay(Vec<u8>);
+
+impl RngCore for Replay {
+    fn next_u32(&mut self) -> u32 {
+        unimplemented!()
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        unimplemented!()
+    }
+
+    fn fill_bytes(&mut self, out: &mut [u8]) {
+        assert!(out.len() <= self.0.len(), "drew more than the vector holds");
+        let rest = self.0.split_off(out.len());
+        out.copy_from_slice(&self.0);
+        self.0 = rest;
+    }
+
+    fn try_fill_bytes(&mut self, out: &mut [u8]) -> Result<(), rand::Error> {
+        self.fill_bytes(out);
+        Ok(())
+    }
+}
+
+impl CryptoRng for Replay {}
+
+fn load(file: &str) -> Vec<Entry> {
+    rsp::load(&vector_path(file)).1
+}
+
+fn parameter_set(entry: &Entry) -> ParamSet {
+    let name = entry.field("parameterSet");
+    PARAMETER_SETS
+        .into_iter()
+        .find(|set| set.to_string() == name)
+        .unwrap_or_else(|| panic!("unknown parameter set `{}`", name))
+}
+
+#[allow(deprecated)]
+fn expanded_decapsulation_key(set: ParamSet, seed: &[u8]) -> Vec<u8> {
+    let seed = seed.try_into().unwrap();
+    match set {
+        ParamSet::MlKem512 => DecapsulationKey512::from_seed(seed)
+            .to_expanded_bytes()
+            .to_vec(),
+        ParamSet::MlKem768 => DecapsulationKey768::from_seed(seed)
+            .to_expanded_bytes()
+            .to_vec(),
+        _ => DecapsulationKey1024::from_seed(seed)
+            .to_expanded_bytes()
+            .to_vec(),
+    }
+}
+
+#[allow(deprecated)]
+fn decapsulate_expanded(set: ParamSet, dk: &[u8], c: &[u8]) -> Vec<u8> {
+    match set {
+        ParamSet::MlKem512 => DecapsulationKey512::from_expanded_bytes(&dk.try_into().unwrap())
+            .unwrap()
+            .decapsulate(c.try_into().unwrap())
+            .to_vec(),
+        ParamSet::MlKem768 => DecapsulationKey768::from_expanded_bytes(&dk.try_into().unwrap())
+            .unwrap()
+            .decapsulate(c.try_into().unwrap())
+            .to_vec(),
+        _ => DecapsulationKey1024::from_expanded_bytes(&dk.try_into().unwrap())
+            .unwrap()
+            .decapsulate(c.try_into().unwrap())
+            .to_vec(),
+    }
+}
+
+#[test]
+fn key_generation_matches_acvp() {
+    for entry in load("ML-KEM-keyGen.rsp") {
+        let (set, count) = (parameter_set(&entry), entry.number("count"));
+        //The secret key is the seed d || z
+        let seed = [entry.bytes("d"), entry.bytes("z")].concat();
+        let mut rng = Replay(seed.clone());
+        let (public_key, secret_key) = set.keypair(&mut rng);
+        assert!(rng.0.is_empty(), "{}", count);
+        assert_eq!(public_key, entry.bytes("ek"), "{}", count);
+        assert_eq!(secret_key, seed, "{}", count);
+        assert_eq!(
+            expanded_decapsulation_key(set, &seed),
+            entry.bytes("dk"),
+            "{}",
+            count
+        );
+    }
+}
+
+#[test]
+fn encapsulation_matches_acvp() {
+    for entry in load("ML-KEM-encapDecap.rsp") {
+        if entry.field("function") != "encapsulation" {
+            continue;
+        }
+        let (set, count) = (parameter_set(&entry), entry.number("count"));
+        let mut rng = Replay(entry.bytes("m"));
+        let (ciphertext, shared_secret) = set.encapsulate(&entry.bytes("ek"), &mut rng).unwrap();
+        assert!(rng.0.is_empty(), "{}", count);
+        assert_eq!(ciphertext, entry.bytes("c"), "{}", count);
+        assert_eq!(shared_secret.to_vec(), entry.bytes("k"), "{}", count);
+    }
+}
+
+#[test]
+fn decapsulation_matches_acvp() {
+    //Modified ciphertexts are implicitly rejected to the published k
+    for entry in load("ML-KEM-encapDecap.rsp") {
+        if entry.field("function") != "decapsulation" {
+            continue;
+        }
+        let set = parameter_set(&entry);
+        assert_eq!(
+            decapsulate_expanded(set, &entry.bytes("dk"), &entry.bytes("c")),
+            entry.bytes("k"),
+            "{} ({})",
+            entry.number("count"),
+            entry.field("reason")
+        );
+    }
+}
@@ -0,0 +1,109 @@
+/// Allocate memory into the module's linear memory
+/// and return the offset to the start of the block.
+#[no_mangle]
+pub fn alloc(len: usize) -> *mut u8 {
+    // create a new mutable buffer with capacity `len`
+    let mut buf = Vec::with_capacity(len);
+    // take a mutable pointer to the buffer
+    let ptr = buf.as_mut_ptr();
+    // take ownership of the memory block and
+    // ensure that its destructor is not
+    // called when the object goes out of scope
+    // at the end of the function
+    std::mem::forget(buf);
+    // return the pointer so the runtime
+    // can write data at this offset
+    ptr
+}
+
+/// Generates a keypair and returns pointers to the public key
+/// (`KYBER_PUBLICKEYBYTES`) and secret key (`KYBER_SECRETKEYBYTES`),
+/// or a pair of nulls if the random number generator fails.
+#[no_mangle]
+pub fn keypair() -> (*mut u8, *mut u8) {
+    //Create a Random number generator
+    let mut rng = rand::thread_rng();
+    //Generating Key Pair
+    let keys = match pqc_kyber::keypair(&mut rng) {
+        Ok(keys) => keys,
+        Err(_) => return (std::ptr::null_mut(), std::ptr::null_mut()),
+    };
+    //Extracting pk and sk
+    let mut pk = keys.public.to_vec();
+    let mut sk = keys.secret.to_vec();
+    //Converting pk and sk to raw pointers
+    let pk_ptr = pk.as_mut_ptr();
+    let sk_ptr = sk.as_mut_ptr();
+    // hand ownership of both blocks to the host
+    std::mem::forget(pk);
+    std::mem::forget(sk);
+    //Returning the pointers
+    (pk_ptr, sk_ptr)
+}
+
+/// Encapsulates a fresh shared secret to `pk` and returns pointers to the
+/// ciphertext (`KYBER_CIPHERTEXTBYTES`) and shared secret (`KYBER_SSBYTES`),
+/// or a pair of nulls if `pk_len` is not `KYBER_PUBLICKEYBYTES`.
+///
+/// # Safety
+///
+/// `pk` must be a block of `pk_len` bytes obtained from `alloc`; it is
+/// consumed by this call.
+#[no_mangle]
+pub unsafe fn encapsulate(pk: *mut u8, pk_len: usize) -> (*mut u8, *mut u8) {
+    //Reading pk from linear memory
+    let pk = Vec::from_raw_parts(pk, pk_len, pk_len);
+    //Create a Random number generator
+    let mut rng = rand::thread_rng();
+    //Encapsulate a shared secret
+    let (ciphertext, shared_secret) = match pqc_kyber::encapsulate(&pk, &mut rng) {
+        Ok(encapsulated) => encapsulated,
+        Err(_) => return (std::ptr::null_mut(), std::ptr::null_mut()),
+    };
+    let mut ciphertext = ciphertext.to_vec();
+    let mut shared_secret = shared_secret.to_vec();
+    //Converting ciphertext and shared secret to raw pointers
+    let ct_ptr = ciphertext.as_mut_ptr();
+    let ss_ptr = shared_secret.as_mut_ptr();
+    // hand ownership of both blocks to the host
+    std::mem::forget(ciphertext);
+    std::mem::forget(shared_secret);
+    //Returning the pointers
+    (ct_ptr, ss_ptr)
+}
+
+/// Decapsulates `ct` with `sk` and returns a pointer to the shared secret
+/// (`KYBER_SSBYTES`), or null if either input has the wrong length.
+///
+/// # Safety
+///
+/// `ct` and `sk` must be blocks of `ct_len` and `sk_len` bytes obtained from
+/// `alloc`; both are consumed by this call.
+#[no_mangle]
+pub unsafe fn decapsulate(ct: *mut u8, ct_len: usize, sk: *mut u8, sk_len: usize) -> *mut u8 {
+    //Reading ct and sk from linear memory
+    let ct = Vec::from_raw_parts(ct, ct_len, ct_len);
+    let sk = Vec::from_raw_parts(sk, sk_len, sk_len);
+    //Decapsulate shared secret
+    let mut shared_secret = match pqc_kyber::decapsulate(&ct, &sk) {
+        Ok(shared_secret) => shared_secret.to_vec(),
+        Err(_) => return std::ptr::null_mut(),
+    };
+    //Converting shared secret to a raw pointer owned by the host
+    let ss_ptr = shared_secret.as_mut_ptr();
+    std::mem::forget(shared_secret);
+    ss_ptr
+}
+
+//deallocator function for a memory block by calling std::mem::drop using the desired value,
+//which effectively takes ownership of data and goes out of scope.
+/// # Safety
+///
+/// `ptr` must be a block of `size` bytes obtained from `alloc` or returned by
+/// one of the exported functions.
+#[no_mangle]
+pub unsafe fn dealloc(ptr: *mut u8, size: usize) {
+    let data = Vec::from_raw_parts(ptr, size, size);
+
+    std::mem::drop(data);
+}
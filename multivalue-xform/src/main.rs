@@ -0,0 +1,50 @@
+//! Rewrites guest exports that return two pointers through a return area into
+//! multi-value functions, producing the `.multivalue.wasm` artifacts the
+//! service loads.
+//!
+//! Usage: `multivalue-xform <input.wasm> <output.wasm> <export>:<results>...`,
+//! where `<results>` is the number of i32 values the export returns.
+
+use walrus::{ExportItem, ValType};
+
+fn main() -> anyhow::Result<()> {
+    let args: Vec<String> = std::env::args().collect();
+    if args.len() < 4 {
+        anyhow::bail!(
+            "usage: {} <input.wasm> <output.wasm> <export>:<results>...",
+            args[0]
+        );
+    }
+    let mut module = walrus::Module::from_file(&args[1])?;
+    let stack_pointer = wasm_bindgen_wasm_conventions::get_stack_pointer(&module)
+        .ok_or_else(|| anyhow::anyhow!("{} has no stack pointer", args[1]))?;
+    let memory = wasm_bindgen_wasm_conventions::get_memory(&module)?;
+
+    let mut to_xform = vec![];
+    let mut exports = vec![];
+    for spec in &args[3..] {
+        let (name, results) = spec
+            .split_once(':')
+            .ok_or_else(|| anyhow::anyhow!("expected <export>:<results>, got {}", spec))?;
+        let results: usize = results.parse()?;
+        let export = module
+            .exports
+            .iter()
+            .find(|e| e.name == name)
+            .ok_or_else(|| anyhow::anyhow!("no export named {}", name))?;
+        let ExportItem::Function(function) = export.item else {
+            anyhow::bail!("export {} is not a function", name);
+        };
+        //The return area pointer is the first parameter
+        to_xform.push((function, 0, vec![ValType::I32; results]));
+        exports.push(export.id());
+    }
+
+    let wrappers =
+        wasm_bindgen_multi_value_xform::run(&mut module, memory, stack_pointer, &to_xform)?;
+    for (export, wrapper) in exports.into_iter().zip(wrappers) {
+        module.exports.get_mut(export).item = ExportItem::Function(wrapper);
+    }
+    module.emit_wasm_file(&args[2])?;
+    Ok(())
+}
@@ -0,0 +1,104 @@
+//! The messages ML-DSA signs. FIPS 204 never signs data as is:
+//!
+//! ```text
+//! plain      0x00 || 0x00 || data
+//! context    0x00 || len(ctx) || ctx || data
+//! prehash    0x01 || 0x00 || DER(hash OID) || digest
+//! ```
+//!
+//! The first byte keeps a signature over a digest from ever verifying as one
+//! over a message, the context binds a signature to its purpose and the OID
+//! binds a digest to its hash function. Prehash signatures are HashML-DSA
+//! with an empty context.
+//!
+//! Round-3 Dilithium predates this and signs messages unframed. A round-3
+//! signature over the framed bytes would be a plain signature over those same
+//! bytes, so anyone who gets a file of their choice signed would hold a
+//! signature under any context, or over any digest. Round-3 keys therefore
+//! only sign and verify plain messages; contexts and digests take an ML-DSA
+//! key. Every crate that refuses round-3 keys for them does so for this reason.
+
+/// Longest context string FIPS 204 allows.
+pub const MAX_CONTEXT_BYTES: usize = 255;
+
+/// Hash algorithm identifiers of prehash signatures, as the signing module's
+/// `sign_prehashed` and `verify_prehashed` take them.
+pub const HASH_SHA256: i32 = 1;
+pub const HASH_SHA512: i32 = 2;
+/// SHAKE256 with 64 bytes of output.
+pub const HASH_SHAKE256: i32 = 3;
+
+/// Frames `data` under `context`, an empty context for a plain signature.
+/// Returns `None` if the context is longer than [`MAX_CONTEXT_BYTES`].
+pub fn context_message(context: &[u8], data: &[u8]) -> Option<Vec<u8>> {
+    if context.len() > MAX_CONTEXT_BYTES {
+        return None;
+    }
+    let mut message = Vec::with_capacity(2 + context.len() + data.len());
+    message.push(0x00);
+    message.push(context.len() as u8);
+    message.extend_from_slice(context);
+    message.extend_from_slice(data);
+    Some(message)
+}
+
+/// Frames a digest computed with `hash_alg`. Returns `None` for an unknown
+/// algorithm or a digest of the wrong length.
+pub fn prehash_message(hash_alg: i32, digest: &[u8]) -> Option<Vec<u8>> {
+    let (oid, digest_len) = hash_oid(hash_alg)?;
+    if digest.len() != digest_len {
+        return None;
+    }
+    let mut message = Vec::with_capacity(2 + oid.len() + digest.len());
+    message.push(0x01);
+    message.push(0x00);
+    message.extend_from_slice(oid);
+    message.extend_from_slice(digest);
+    Some(message)
+}
+
+/// DER encoded object identifier and digest length of a hash algorithm.
+pub fn hash_oid(hash_alg: i32) -> Option<(&'static [u8], usize)> {
+    //Under 2.16.840.1.101.3.4.2
+    const SHA256: [u8; 11] = [
+        0x06, 0x09, 0x60, 0x86, 0x48, 0x01, 0x65, 0x03, 0x04, 0x02, 0x01,
+    ];
+    const SHA512: [u8; 11] = [
+        0x06, 0x09, 0x60, 0x86, 0x48, 0x01, 0x65, 0x03, 0x04, 0x02, 0x03,
+    ];
+    const SHAKE256: [u8; 11] = [
+        0x06, 0x09, 0x60, 0x86, 0x48, 0x01, 0x65, 0x03, 0x04, 0x02, 0x0c,
+    ];
+    match hash_alg {
+        HASH_SHA256 => Some((&SHA256, 32)),
+        HASH_SHA512 => Some((&SHA512, 64)),
+        HASH_SHAKE256 => Some((&SHAKE256, 64)),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn plain_messages_have_an_empty_context() {
+        assert_eq!(context_message(b"", b"data").unwrap(), b"\x00\x00data");
+        assert_eq!(
+            context_message(b"ctx", b"data").unwrap(),
+            b"\x00\x03ctxdata"
+        );
+        assert!(context_message(&[0; MAX_CONTEXT_BYTES], b"").is_some());
+        assert!(context_message(&[0; MAX_CONTEXT_BYTES + 1], b"").is_none());
+    }
+
+    #[test]
+    fn digests_must_match_their_hash() {
+        let message = prehash_message(HASH_SHA256, &[0x5a; 32]).unwrap();
+        assert_eq!(message[..2], [0x01, 0x00]);
+        assert_eq!(message.len(), 2 + 11 + 32);
+        assert!(prehash_message(HASH_SHA256, &[0x5a; 64]).is_none());
+        assert!(prehash_message(HASH_SHAKE256, &[0x5a; 64]).is_some());
+        assert!(prehash_message(0, &[0x5a; 32]).is_none());
+    }
+}
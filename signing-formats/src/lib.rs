@@ -0,0 +1,12 @@
+//! Encodings shared by the signing module, test-dilithium and wasm-api, so
+//! that keys, signatures and signed messages are built in one place and the
+//! three can never drift apart.
+//!
+//! - [`mldsa`]: FIPS 204 ML-DSA with tagged keys and signatures, at any level
+//! - [`framing`]: the messages ML-DSA signs for plain, contextual and
+//!   prehashed signatures, and why round-3 Dilithium keys sign none of them
+//! - [`wasm_section`]: signatures embedded in wasm modules
+
+pub mod framing;
+pub mod mldsa;
+pub mod wasm_section;
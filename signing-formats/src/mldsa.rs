@@ -0,0 +1,231 @@
+//! FIPS 204 ML-DSA at any of its three levels, chosen at run time.
+//!
+//! Every key and signature starts with a one-byte algorithm identifier so
+//! that they can never be mistaken for the untagged round-3 encodings:
+//! 0x44 for ML-DSA-44, 0x65 for ML-DSA-65 and 0x87 for ML-DSA-87. Secret keys
+//! are the identifier followed by the 32-byte FIPS 204 seed, so the public key
+//! can always be derived from them.
+//!
+//! Messages are signed as the FIPS 204 message representative `M'`, framing
+//! included (see [`crate::framing`]), and deterministically, as round-3
+//! signing is.
+
+use ml_dsa::{
+    EncodedVerifyingKey, Keypair, MlDsa44, MlDsa65, MlDsa87, MlDsaParams, Signature, SigningKey,
+    VerifyingKey, B32,
+};
+
+/// Length of a FIPS 204 seed `ξ`.
+pub const SEED_BYTES: usize = 32;
+/// Length of a tagged secret key, the same at every level.
+pub const SECRET_KEY_BYTES: usize = 1 + SEED_BYTES;
+
+/// An ML-DSA parameter set.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum ParameterSet {
+    MlDsa44,
+    MlDsa65,
+    MlDsa87,
+}
+
+impl ParameterSet {
+    pub const ALL: [ParameterSet; 3] = [
+        ParameterSet::MlDsa44,
+        ParameterSet::MlDsa65,
+        ParameterSet::MlDsa87,
+    ];
+
+    /// Identifier prefixed to keys and signatures, which is also the level.
+    pub const fn tag(self) -> u8 {
+        match self {
+            ParameterSet::MlDsa44 => 0x44,
+            ParameterSet::MlDsa65 => 0x65,
+            ParameterSet::MlDsa87 => 0x87,
+        }
+    }
+
+    pub fn from_tag(tag: u8) -> Option<ParameterSet> {
+        ParameterSet::ALL.into_iter().find(|set| set.tag() == tag)
+    }
+
+    /// The parameter set of a level as written in decimal: 44, 65 or 87.
+    pub fn from_level(level: u8) -> Option<ParameterSet> {
+        ParameterSet::ALL
+            .into_iter()
+            .find(|set| set.level() == level)
+    }
+
+    pub const fn level(self) -> u8 {
+        match self {
+            ParameterSet::MlDsa44 => 44,
+            ParameterSet::MlDsa65 => 65,
+            ParameterSet::MlDsa87 => 87,
+        }
+    }
+
+    pub const fn name(self) -> &'static str {
+        match self {
+            ParameterSet::MlDsa44 => "ML-DSA-44",
+            ParameterSet::MlDsa65 => "ML-DSA-65",
+            ParameterSet::MlDsa87 => "ML-DSA-87",
+        }
+    }
+
+    /// Length of a tagged public key.
+    pub const fn public_key_bytes(self) -> usize {
+        1 + match self {
+            ParameterSet::MlDsa44 => 1312,
+            ParameterSet::MlDsa65 => 1952,
+            ParameterSet::MlDsa87 => 2592,
+        }
+    }
+
+    /// Length of a tagged signature.
+    pub const fn signature_bytes(self) -> usize {
+        1 + match self {
+            ParameterSet::MlDsa44 => 2420,
+            ParameterSet::MlDsa65 => 3309,
+            ParameterSet::MlDsa87 => 4627,
+        }
+    }
+
+    /// The parameter set of a tagged public key, if `pk` is one.
+    pub fn of_public_key(pk: &[u8]) -> Option<ParameterSet> {
+        let set = ParameterSet::from_tag(*pk.first()?)?;
+        (pk.len() == set.public_key_bytes()).then_some(set)
+    }
+
+    /// The parameter set of a tagged secret key, if `sk` is one.
+    pub fn of_secret_key(sk: &[u8]) -> Option<ParameterSet> {
+        let set = ParameterSet::from_tag(*sk.first()?)?;
+        (sk.len() == SECRET_KEY_BYTES).then_some(set)
+    }
+
+    /// The parameter set of a tagged signature, if `signature` is one.
+    pub fn of_signature(signature: &[u8]) -> Option<ParameterSet> {
+        let set = ParameterSet::from_tag(*signature.first()?)?;
+        (signature.len() == set.signature_bytes()).then_some(set)
+    }
+}
+
+/// Generates a keypair from a fresh random seed, returning the tagged public
+/// and secret keys, or `None` if no randomness is available.
+pub fn keypair(set: ParameterSet) -> Option<(Vec<u8>, Vec<u8>)> {
+    let mut seed = [0u8; SEED_BYTES];
+    getrandom::getrandom(&mut seed).ok()?;
+    Some(keypair_from_seed(set, &seed))
+}
+
+/// Derives the keypair of the FIPS 204 seed `ξ`, returning the tagged public
+/// and secret keys.
+pub fn keypair_from_seed(set: ParameterSet, seed: &[u8; SEED_BYTES]) -> (Vec<u8>, Vec<u8>) {
+    let public_key = match set {
+        ParameterSet::MlDsa44 => raw_public_key::<MlDsa44>(seed),
+        ParameterSet::MlDsa65 => raw_public_key::<MlDsa65>(seed),
+        ParameterSet::MlDsa87 => raw_public_key::<MlDsa87>(seed),
+    };
+    (tagged(set, &public_key), tagged(set, seed))
+}
+
+/// Derives the tagged public key of a tagged secret key.
+pub fn public_key(sk: &[u8]) -> Option<Vec<u8>> {
+    let set = ParameterSet::of_secret_key(sk)?;
+    Some(keypair_from_seed(set, sk[1..].try_into().ok()?).0)
+}
+
+/// Signs the message representative `message` with a tagged secret key, at
+/// the key's level. Returns `None` if `sk` is not a tagged secret key.
+pub fn sign(message: &[u8], sk: &[u8]) -> Option<Vec<u8>> {
+    let set = ParameterSet::of_secret_key(sk)?;
+    let seed = sk[1..].try_into().ok()?;
+    let signature = match set {
+        ParameterSet::MlDsa44 => raw_signature::<MlDsa44>(seed, message),
+        ParameterSet::MlDsa65 => raw_signature::<MlDsa65>(seed, message),
+        ParameterSet::MlDsa87 => raw_signature::<MlDsa87>(seed, message),
+    };
+    Some(tagged(set, &signature))
+}
+
+/// Verifies a tagged signature over the message representative `message`
+/// against a tagged public key of the same level.
+pub fn verify(message: &[u8], signature: &[u8], pk: &[u8]) -> bool {
+    let Some(set) = ParameterSet::of_public_key(pk) else {
+        return false;
+    };
+    if ParameterSet::of_signature(signature) != Some(set) {
+        return false;
+    }
+    let (signature, pk) = (&signature[1..], &pk[1..]);
+    match set {
+        ParameterSet::MlDsa44 => verify_raw::<MlDsa44>(message, signature, pk),
+        ParameterSet::MlDsa65 => verify_raw::<MlDsa65>(message, signature, pk),
+        ParameterSet::MlDsa87 => verify_raw::<MlDsa87>(message, signature, pk),
+    }
+}
+
+fn raw_public_key<P: MlDsaParams>(seed: &[u8; SEED_BYTES]) -> Vec<u8> {
+    let signing_key = SigningKey::<P>::from_seed(&(*seed).into());
+    signing_key.verifying_key().encode().to_vec()
+}
+
+fn raw_signature<P: MlDsaParams>(seed: &[u8; SEED_BYTES], message: &[u8]) -> Vec<u8> {
+    let signing_key = SigningKey::<P>::from_seed(&(*seed).into());
+    signing_key
+        .expanded_key()
+        .sign_internal(&[message], &B32::default())
+        .encode()
+        .to_vec()
+}
+
+fn verify_raw<P: MlDsaParams>(message: &[u8], signature: &[u8], pk: &[u8]) -> bool {
+    let (Ok(signature), Ok(pk)) = (
+        Signature::<P>::try_from(signature),
+        EncodedVerifyingKey::<P>::try_from(pk),
+    ) else {
+        return false;
+    };
+    VerifyingKey::<P>::decode(&pk).verify_internal(message, &signature)
+}
+
+fn tagged(set: ParameterSet, bytes: &[u8]) -> Vec<u8> {
+    let mut tagged = Vec::with_capacity(1 + bytes.len());
+    tagged.push(set.tag());
+    tagged.extend_from_slice(bytes);
+    tagged
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::framing::context_message;
+
+    #[test]
+    fn every_level_signs_and_verifies() {
+        for set in ParameterSet::ALL {
+            let (pk, sk) = keypair_from_seed(set, &[7; SEED_BYTES]);
+            assert_eq!(ParameterSet::of_public_key(&pk), Some(set));
+            assert_eq!(ParameterSet::of_secret_key(&sk), Some(set));
+            assert_eq!(public_key(&sk).unwrap(), pk);
+
+            let message = context_message(b"", b"data").unwrap();
+            let signature = sign(&message, &sk).unwrap();
+            assert_eq!(ParameterSet::of_signature(&signature), Some(set));
+            //Deterministic, as round-3 signing
+            assert_eq!(sign(&message, &sk).unwrap(), signature);
+            assert!(verify(&message, &signature, &pk));
+            assert!(!verify(b"\x00\x00other", &signature, &pk));
+        }
+    }
+
+    #[test]
+    fn levels_never_mix() {
+        let (pk44, _) = keypair_from_seed(ParameterSet::MlDsa44, &[7; SEED_BYTES]);
+        let (_, sk65) = keypair_from_seed(ParameterSet::MlDsa65, &[7; SEED_BYTES]);
+        let signature = sign(b"message", &sk65).unwrap();
+        assert!(!verify(b"message", &signature, &pk44));
+
+        //Untagged seeds are not keys
+        assert!(sign(b"message", &sk65[1..]).is_none());
+        assert!(public_key(&sk65[1..]).is_none());
+    }
+}
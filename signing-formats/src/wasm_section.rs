@@ -0,0 +1,268 @@
+//! Signatures embedded in wasm modules as a `signature` custom section, in the
+//! spirit of the WebAssembly module signatures proposal. The section holds
+//!
+//! ```text
+//! version (1) | content type (1, module) | hash function (1, SHA-256) | hash (32)
+//!   | count (varuint32)
+//!   | count × { key id (varuint32 length, bytes) | algorithm (1) | signature (varuint32 length, bytes) }
+//! ```
+//!
+//! The hash covers the module file with its signature sections cut out and
+//! every other byte left as is, so the section can be added, extended or
+//! stripped without touching what was signed, while whatever a runtime
+//! compiles was hashed. Every signature covers the 35 bytes from the version
+//! to the end of the hash. The key id is the SHA-256 of the signer's raw public
+//! key and the algorithm its level (2, 3, 5, 44, 65 or 87).
+//!
+//! test-dilithium writes the section and wasm-api checks it, both with this
+//! parser, so they cannot disagree on what a module's signed bytes are.
+
+use sha2::{Digest, Sha256};
+
+pub const SECTION_NAME: &str = "signature";
+
+const VERSION: u8 = 1;
+const CONTENT_TYPE_MODULE: u8 = 1;
+const HASH_SHA256: u8 = 1;
+const HASH_BYTES: usize = 32;
+const CUSTOM_SECTION_ID: u8 = 0;
+//Magic number and version of binary modules
+const MODULE_HEADER: [u8; 8] = *b"\0asm\x01\0\0\0";
+
+/// One signature of a module.
+pub struct Entry {
+    pub key_id: Vec<u8>,
+    pub algorithm: u8,
+    pub signature: Vec<u8>,
+}
+
+/// Decoded contents of a signature section.
+pub struct Section {
+    pub hash: [u8; HASH_BYTES],
+    pub signatures: Vec<Entry>,
+}
+
+/// Splits a binary module into its bytes without signature sections, which is
+/// what the hash covers, and the contents of the first signature section.
+/// Sections are only delimited, never decoded and re-encoded, so the hashed
+/// bytes are the file's own; duplicate signature sections are dropped along
+/// with the first. Returns `None` unless the sections are well delimited.
+pub fn split(wasm: &[u8]) -> Option<(Vec<u8>, Option<&[u8]>)> {
+    let mut reader = Reader(wasm);
+    if reader.bytes(MODULE_HEADER.len())? != MODULE_HEADER {
+        return None;
+    }
+    let mut unsigned = MODULE_HEADER.to_vec();
+    let mut section = None;
+    while !reader.0.is_empty() {
+        let start = reader.0;
+        let id = reader.bytes(1)?[0];
+        let contents = reader.prefixed()?;
+        if id == CUSTOM_SECTION_ID {
+            let mut custom = Reader(contents);
+            if custom.prefixed()? == SECTION_NAME.as_bytes() {
+                section.get_or_insert(custom.0);
+                continue;
+            }
+        }
+        unsigned.extend_from_slice(&start[..start.len() - reader.0.len()]);
+    }
+    Some((unsigned, section))
+}
+
+/// SHA-256 of a module without its signature section, as `split` returns it.
+pub fn hash(unsigned: &[u8]) -> [u8; HASH_BYTES] {
+    Sha256::digest(unsigned).into()
+}
+
+/// Appends `section` to a module without a signature section. Splitting the
+/// result gives back exactly `unsigned`.
+pub fn emit(mut unsigned: Vec<u8>, section: &Section) -> Vec<u8> {
+    let mut contents = Vec::new();
+    write_varuint(&mut contents, SECTION_NAME.len());
+    contents.extend_from_slice(SECTION_NAME.as_bytes());
+    contents.extend(section.encode());
+
+    unsigned.push(CUSTOM_SECTION_ID);
+    write_varuint(&mut unsigned, contents.len());
+    unsigned.extend(contents);
+    unsigned
+}
+
+/// Key id of a raw public key.
+pub fn key_id(public_key: &[u8]) -> Vec<u8> {
+    Sha256::digest(public_key).to_vec()
+}
+
+impl Section {
+    pub fn new(hash: [u8; HASH_BYTES]) -> Section {
+        Section {
+            hash,
+            signatures: Vec::new(),
+        }
+    }
+
+    /// The bytes every signature in the section covers.
+    pub fn signed_message(&self) -> Vec<u8> {
+        let mut message = vec![VERSION, CONTENT_TYPE_MODULE, HASH_SHA256];
+        message.extend_from_slice(&self.hash);
+        message
+    }
+
+    pub fn encode(&self) -> Vec<u8> {
+        let mut data = self.signed_message();
+        write_varuint(&mut data, self.signatures.len());
+        for entry in &self.signatures {
+            write_varuint(&mut data, entry.key_id.len());
+            data.extend_from_slice(&entry.key_id);
+            data.push(entry.algorithm);
+            write_varuint(&mut data, entry.signature.len());
+            data.extend_from_slice(&entry.signature);
+        }
+        data
+    }
+
+    /// Decodes a section, or returns None when it is malformed or of a
+    /// version, content type or hash function this parser does not know.
+    pub fn decode(data: &[u8]) -> Option<Section> {
+        let mut reader = Reader(data);
+        if reader.bytes(3)? != [VERSION, CONTENT_TYPE_MODULE, HASH_SHA256] {
+            return None;
+        }
+        let hash = reader.bytes(HASH_BYTES)?.try_into().ok()?;
+        let count = reader.varuint()?;
+        let mut signatures = Vec::new();
+        for _ in 0..count {
+            let key_id = reader.prefixed()?.to_vec();
+            let algorithm = reader.bytes(1)?[0];
+            let signature = reader.prefixed()?.to_vec();
+            signatures.push(Entry {
+                key_id,
+                algorithm,
+                signature,
+            });
+        }
+        reader.0.is_empty().then_some(Section { hash, signatures })
+    }
+}
+
+fn write_varuint(data: &mut Vec<u8>, mut value: usize) {
+    loop {
+        let byte = (value & 0x7f) as u8;
+        value >>= 7;
+        if value == 0 {
+            data.push(byte);
+            return;
+        }
+        data.push(byte | 0x80);
+    }
+}
+
+struct Reader<'a>(&'a [u8]);
+
+impl<'a> Reader<'a> {
+    fn bytes(&mut self, len: usize) -> Option<&'a [u8]> {
+        if self.0.len() < len {
+            return None;
+        }
+        let (bytes, rest) = self.0.split_at(len);
+        self.0 = rest;
+        Some(bytes)
+    }
+
+    //LEB128, at most five bytes as for varuint32
+    fn varuint(&mut self) -> Option<usize> {
+        let mut value = 0usize;
+        for shift in (0..35).step_by(7) {
+            let byte = self.bytes(1)?[0];
+            value |= ((byte & 0x7f) as usize) << shift;
+            if byte & 0x80 == 0 {
+                return Some(value);
+            }
+        }
+        None
+    }
+
+    fn prefixed(&mut self) -> Option<&'a [u8]> {
+        let len = self.varuint()?;
+        self.bytes(len)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    //(module (func (export "add") (param i32 i32) (result i32) local.get 0 local.get 1 i32.add))
+    const ADD_MODULE: &[u8] = &[
+        0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00, 0x01, 0x07, 0x01, 0x60, 0x02, 0x7f, 0x7f,
+        0x01, 0x7f, 0x03, 0x02, 0x01, 0x00, 0x07, 0x07, 0x01, 0x03, 0x61, 0x64, 0x64, 0x00, 0x00,
+        0x0a, 0x09, 0x01, 0x07, 0x00, 0x20, 0x00, 0x20, 0x01, 0x6a, 0x0b,
+    ];
+
+    fn signed(wasm: &[u8]) -> Vec<u8> {
+        let (unsigned, _) = split(wasm).unwrap();
+        let mut section = Section::new(hash(&unsigned));
+        section.signatures.push(Entry {
+            key_id: key_id(b"public key"),
+            algorithm: 65,
+            signature: vec![0x5a; 100],
+        });
+        emit(unsigned, &section)
+    }
+
+    #[test]
+    fn round_trip() {
+        let wasm = signed(ADD_MODULE);
+        let (unsigned, contents) = split(&wasm).unwrap();
+        //The module without its section is the file as it was
+        assert_eq!(unsigned, ADD_MODULE);
+        let section = Section::decode(contents.unwrap()).unwrap();
+        assert_eq!(section.hash, hash(ADD_MODULE));
+        let [entry] = &section.signatures[..] else {
+            panic!("one signature expected");
+        };
+        assert_eq!(entry.key_id, key_id(b"public key"));
+        assert_eq!(entry.algorithm, 65);
+        assert_eq!(entry.signature, [0x5a; 100]);
+        assert_eq!(section.encode(), contents.unwrap());
+    }
+
+    #[test]
+    fn tampered_section_is_rejected() {
+        let wasm = signed(ADD_MODULE);
+        let contents = split(&wasm).unwrap().1.unwrap().to_vec();
+
+        let mut version = contents.clone();
+        version[0] = 2;
+        assert!(Section::decode(&version).is_none());
+        let mut trailing = contents.clone();
+        trailing.push(0);
+        assert!(Section::decode(&trailing).is_none());
+        assert!(Section::decode(&contents[..contents.len() - 1]).is_none());
+
+        //A changed hash no longer matches the module
+        let mut changed = contents.clone();
+        changed[3] ^= 1;
+        let section = Section::decode(&changed).unwrap();
+        assert_ne!(section.hash, hash(ADD_MODULE));
+    }
+
+    #[test]
+    fn modified_module_no_longer_matches() {
+        let mut wasm = signed(ADD_MODULE);
+        //i32.add becomes i32.sub
+        let add = wasm.iter().position(|byte| *byte == 0x6a).unwrap();
+        wasm[add] = 0x6b;
+        let (unsigned, contents) = split(&wasm).unwrap();
+        let section = Section::decode(contents.unwrap()).unwrap();
+        assert_ne!(section.hash, hash(&unsigned));
+    }
+
+    #[test]
+    fn truncated_sections_do_not_split() {
+        let wasm = signed(ADD_MODULE);
+        assert!(split(&wasm[..wasm.len() - 1]).is_none());
+        assert!(split(&ADD_MODULE[4..]).is_none());
+    }
+}
@@ -0,0 +1,112 @@
+//! Why a command failed, and the exit code each failure maps to (see
+//! `report` for the full list).
+
+use std::fmt;
+use std::io;
+
+use crate::manifest;
+
+#[derive(Debug)]
+pub enum Error {
+    /// Wrong number of arguments; displays the usage of the given program.
+    Usage(String),
+    /// Bad options, or a key, signature, module or manifest that cannot be used.
+    Input(String),
+    /// A file could not be read or written.
+    Io(String, io::Error),
+}
+
+impl Error {
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            Error::Usage(_) | Error::Input(_) => 2,
+            Error::Io(..) => 3,
+        }
+    }
+
+    /// `status` of the JSON output.
+    pub fn status(&self) -> &'static str {
+        match self {
+            Error::Usage(_) | Error::Input(_) => "malformed_input",
+            Error::Io(..) => "io_error",
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Usage(program) => usage(f, program),
+            Error::Input(reason) => write!(f, "Invalid input: {}", reason),
+            Error::Io(path, e) => write!(f, "Cannot access {}: {}", path, e),
+        }
+    }
+}
+
+impl From<manifest::Error> for Error {
+    fn from(e: manifest::Error) -> Self {
+        match e {
+            manifest::Error::Io(path, e) => Error::Io(path.display().to_string(), e),
+            e => Error::Input(e.to_string()),
+        }
+    }
+}
+
+fn usage(f: &mut fmt::Formatter<'_>, program: &str) -> fmt::Result {
+    writeln!(
+        f,
+        "Usage: {} <command> [--level <2|3|5|44|65|87>] [--context <string>] [--seed <hex>] [--secret-key <file>] [--public-key <file>] [--format <text|json>] <input_file> <signature_file>",
+        program
+    )?;
+    writeln!(
+        f,
+        "       {} keygen [--level <level>] [--seed <hex>] [--secret-key <file>] [--public-key <file>] <--passphrase-file <file> | --no-encrypt> [--key-format <raw|der|pem>]",
+        program
+    )?;
+    writeln!(
+        f,
+        "       {} sign-wasm [--secret-key <file>] [--public-key <file>] [--passphrase-file <file>] <input.wasm> <output.wasm>",
+        program
+    )?;
+    writeln!(
+        f,
+        "       {} verify-wasm [--public-key <file>] <module.wasm>",
+        program
+    )?;
+    writeln!(
+        f,
+        "       {} sign-dir [--secret-key <file>] [--public-key <file>] [--passphrase-file <file>] <directory> <manifest_file>",
+        program
+    )?;
+    writeln!(
+        f,
+        "       {} verify-dir [--public-key <file>] <directory> <manifest_file>",
+        program
+    )?;
+    writeln!(
+        f,
+        "       {} convert-key --key-format <raw|der|pem> [--passphrase-file <file>] <input_key> <output_key>",
+        program
+    )?;
+    writeln!(
+        f,
+        "       {} change-passphrase [--secret-key <file>] [--passphrase-file <file>] [--new-passphrase-file <file>]",
+        program
+    )?;
+    writeln!(
+        f,
+        "Commands: sign, verify, sign-wasm, verify-wasm, sign-dir, verify-dir, keygen, change-passphrase or convert-key"
+    )?;
+    writeln!(
+        f,
+        "--level picks ML-DSA 44, 65 (the default) or 87, or {} (level {}), the round-3 level of this build; keys must be of that level",
+        crate::level::Level::Round3,
+        crate::level::ROUND3_LEVEL
+    )?;
+    write!(
+        f,
+        "Passphrases can also be given in {} and {}",
+        crate::PASSPHRASE_VAR,
+        crate::NEW_PASSPHRASE_VAR
+    )
+}
@@ -0,0 +1,303 @@
+//! Standard key encodings: SubjectPublicKeyInfo for public keys and PKCS#8 for
+//! secret keys, each as DER or PEM.
+//!
+//! ML-DSA keys use the FIPS 204 OIDs (2.16.840.1.101.3.4.3.17, .18 and .19)
+//! and the encodings OpenSSL 3.5 reads: the raw public key as the SPKI bit
+//! string, and the 32-byte seed as the `seed [0]` choice of the PKCS#8 private
+//! key. Keys in the `both` choice (seed and expanded key) are accepted too.
+//!
+//! Round-3 Dilithium has no standard OID; it uses the ones assigned by the
+//! Open Quantum Safe project (1.3.6.1.4.1.2.267.7.4.4, .7.6.5 and .7.8.7) with
+//! the raw key bytes in both structures.
+//!
+//! Keys decode to the bytes `keygen` writes in the raw format, so untagged for
+//! round 3 and prefixed with the algorithm identifier for ML-DSA.
+
+use std::fmt;
+use std::str::FromStr;
+
+use pkcs8::der::asn1::{BitStringRef, OctetString, OctetStringRef};
+use pkcs8::der::pem::{self, LineEnding};
+use pkcs8::der::{Decode, Encode};
+use pkcs8::spki::{AlgorithmIdentifierRef, ObjectIdentifier, SubjectPublicKeyInfoRef};
+use pkcs8::PrivateKeyInfoRef;
+
+const PUBLIC_KEY_LABEL: &str = "PUBLIC KEY";
+const PRIVATE_KEY_LABEL: &str = "PRIVATE KEY";
+
+//Tag and length of the `seed [0] IMPLICIT OCTET STRING` choice of ML-DSA keys
+const SEED_TAG: u8 = 0x80;
+const SEED_BYTES: usize = 32;
+
+struct Algorithm {
+    name: &'static str,
+    //Level as in `--level`
+    level: u8,
+    oid: ObjectIdentifier,
+    //Identifier prefixed to ML-DSA keys, None for untagged round-3 keys
+    tag: Option<u8>,
+    //Untagged sizes; ML-DSA secret keys are their seed
+    public_key_bytes: usize,
+    secret_key_bytes: usize,
+}
+
+const ALGORITHMS: [Algorithm; 6] = [
+    Algorithm {
+        name: "Dilithium2",
+        level: 2,
+        oid: ObjectIdentifier::new_unwrap("1.3.6.1.4.1.2.267.7.4.4"),
+        tag: None,
+        public_key_bytes: 1312,
+        secret_key_bytes: 2528,
+    },
+    Algorithm {
+        name: "Dilithium3",
+        level: 3,
+        oid: ObjectIdentifier::new_unwrap("1.3.6.1.4.1.2.267.7.6.5"),
+        tag: None,
+        public_key_bytes: 1952,
+        secret_key_bytes: 4000,
+    },
+    Algorithm {
+        name: "Dilithium5",
+        level: 5,
+        oid: ObjectIdentifier::new_unwrap("1.3.6.1.4.1.2.267.7.8.7"),
+        tag: None,
+        public_key_bytes: 2592,
+        secret_key_bytes: 4864,
+    },
+    Algorithm {
+        name: "ML-DSA-44",
+        level: 44,
+        oid: ObjectIdentifier::new_unwrap("2.16.840.1.101.3.4.3.17"),
+        tag: Some(0x44),
+        public_key_bytes: 1312,
+        secret_key_bytes: SEED_BYTES,
+    },
+    Algorithm {
+        name: "ML-DSA-65",
+        level: 65,
+        oid: ObjectIdentifier::new_unwrap("2.16.840.1.101.3.4.3.18"),
+        tag: Some(0x65),
+        public_key_bytes: 1952,
+        secret_key_bytes: SEED_BYTES,
+    },
+    Algorithm {
+        name: "ML-DSA-87",
+        level: 87,
+        oid: ObjectIdentifier::new_unwrap("2.16.840.1.101.3.4.3.19"),
+        tag: Some(0x87),
+        public_key_bytes: 2592,
+        secret_key_bytes: SEED_BYTES,
+    },
+];
+
+impl Algorithm {
+    //Strips the identifier of a key of this algorithm whose untagged size is `bytes`
+    fn untag<'a>(&self, key: &'a [u8], bytes: usize) -> Option<&'a [u8]> {
+        match self.tag {
+            Some(tag) if key.len() == 1 + bytes && key[0] == tag => Some(&key[1..]),
+            None if key.len() == bytes => Some(key),
+            _ => None,
+        }
+    }
+
+    fn tag(&self, key: &[u8]) -> Vec<u8> {
+        self.tag.into_iter().chain(key.iter().copied()).collect()
+    }
+
+    fn by_oid(oid: ObjectIdentifier) -> Result<&'static Algorithm, Error> {
+        ALGORITHMS
+            .iter()
+            .find(|algorithm| algorithm.oid == oid)
+            .ok_or(Error::Algorithm(oid))
+    }
+
+    fn identifier(&self) -> AlgorithmIdentifierRef<'static> {
+        AlgorithmIdentifierRef {
+            oid: self.oid,
+            parameters: None,
+        }
+    }
+}
+
+/// How keys are written to disk.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum KeyFormat {
+    Raw,
+    Der,
+    Pem,
+}
+
+impl FromStr for KeyFormat {
+    type Err = String;
+
+    fn from_str(format: &str) -> Result<Self, Self::Err> {
+        match format {
+            "raw" => Ok(KeyFormat::Raw),
+            "der" => Ok(KeyFormat::Der),
+            "pem" => Ok(KeyFormat::Pem),
+            _ => Err(format!(
+                "unknown key format {}; use raw, der or pem",
+                format
+            )),
+        }
+    }
+}
+
+/// A decoded key, in the raw encoding.
+pub enum Key {
+    Public(Vec<u8>),
+    Secret(Vec<u8>),
+}
+
+#[derive(Debug)]
+pub enum Error {
+    /// Malformed DER or PEM.
+    Der(pkcs8::der::Error),
+    /// A PEM block other than a public or private key.
+    Label(String),
+    /// A well-formed key of an algorithm this tool does not know.
+    Algorithm(ObjectIdentifier),
+    /// Key bytes that do not fit any known algorithm.
+    Key,
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Der(e) => write!(f, "malformed key encoding: {}", e),
+            Error::Label(label) => write!(f, "unexpected PEM block {}", label),
+            Error::Algorithm(oid) => write!(f, "unsupported key algorithm {}", oid),
+            Error::Key => write!(f, "not a Dilithium or ML-DSA key"),
+        }
+    }
+}
+
+impl From<pkcs8::der::Error> for Error {
+    fn from(e: pkcs8::der::Error) -> Self {
+        Error::Der(e)
+    }
+}
+
+impl From<pem::Error> for Error {
+    fn from(e: pem::Error) -> Self {
+        Error::Der(e.into())
+    }
+}
+
+/// Name of the algorithm a raw key belongs to, for messages.
+pub fn algorithm_name(key: &Key) -> &'static str {
+    identify(key).map_or("unknown", |(algorithm, _)| algorithm.name)
+}
+
+/// Level (2, 3, 5, 44, 65 or 87) of the algorithm a raw key belongs to.
+pub fn level(key: &Key) -> Option<u8> {
+    identify(key).map(|(algorithm, _)| algorithm.level)
+}
+
+//Finds the algorithm of a raw key and its untagged bytes
+fn identify(key: &Key) -> Option<(&'static Algorithm, &[u8])> {
+    ALGORITHMS.iter().find_map(|algorithm| {
+        let untagged = match key {
+            Key::Public(key) => algorithm.untag(key, algorithm.public_key_bytes),
+            Key::Secret(key) => algorithm.untag(key, algorithm.secret_key_bytes),
+        };
+        untagged.map(|untagged| (algorithm, untagged))
+    })
+}
+
+/// Encodes a raw key in `format`.
+pub fn encode(key: &Key, format: KeyFormat) -> Result<Vec<u8>, Error> {
+    if format == KeyFormat::Raw {
+        return match key {
+            Key::Public(key) | Key::Secret(key) => Ok(key.clone()),
+        };
+    }
+    let (algorithm, untagged) = identify(key).ok_or(Error::Key)?;
+    let (label, der) = match key {
+        Key::Public(_) => {
+            let spki = SubjectPublicKeyInfoRef {
+                algorithm: algorithm.identifier(),
+                subject_public_key: BitStringRef::from_bytes(untagged)?,
+            };
+            (PUBLIC_KEY_LABEL, spki.to_der()?)
+        }
+        Key::Secret(_) => {
+            let seed_choice;
+            let private_key = match algorithm.tag {
+                Some(_) => {
+                    seed_choice = [&[SEED_TAG, SEED_BYTES as u8], untagged].concat();
+                    &seed_choice[..]
+                }
+                None => untagged,
+            };
+            let pkcs8 = PrivateKeyInfoRef {
+                algorithm: algorithm.identifier(),
+                private_key: OctetStringRef::new(private_key)?,
+                public_key: None,
+            };
+            (PRIVATE_KEY_LABEL, pkcs8.to_der()?)
+        }
+    };
+    match format {
+        KeyFormat::Pem => Ok(pem::encode_string(label, LineEnding::LF, &der)?.into_bytes()),
+        _ => Ok(der),
+    }
+}
+
+/// Decodes a key file in any format to a raw key. Raw keys are recognised by
+/// their size, anything else must be a DER or PEM SubjectPublicKeyInfo or
+/// PKCS#8 structure.
+pub fn decode(file: &[u8]) -> Result<Key, Error> {
+    if file.starts_with(b"-----BEGIN") {
+        let (label, der) = pem::decode_vec(file)?;
+        return match label {
+            PUBLIC_KEY_LABEL => decode_public_key(&der),
+            PRIVATE_KEY_LABEL => decode_secret_key(&der),
+            _ => Err(Error::Label(label.to_string())),
+        };
+    }
+    for key in [Key::Public(file.to_vec()), Key::Secret(file.to_vec())] {
+        if identify(&key).is_some() {
+            return Ok(key);
+        }
+    }
+    decode_public_key(file).or_else(|e| match e {
+        Error::Der(_) => decode_secret_key(file),
+        e => Err(e),
+    })
+}
+
+fn decode_public_key(der: &[u8]) -> Result<Key, Error> {
+    let spki = SubjectPublicKeyInfoRef::from_der(der)?;
+    let algorithm = Algorithm::by_oid(spki.algorithm.oid)?;
+    match spki.subject_public_key.as_bytes() {
+        Some(key) if key.len() == algorithm.public_key_bytes => Ok(Key::Public(algorithm.tag(key))),
+        _ => Err(Error::Key),
+    }
+}
+
+fn decode_secret_key(der: &[u8]) -> Result<Key, Error> {
+    let pkcs8 = PrivateKeyInfoRef::from_der(der)?;
+    let algorithm = Algorithm::by_oid(pkcs8.algorithm.oid)?;
+    let private_key = pkcs8.private_key.as_bytes();
+    let key = match algorithm.tag {
+        None => private_key.to_vec(),
+        //seed [0], or both: SEQUENCE { seed, expandedKey }; expanded-only keys carry no seed
+        Some(_) => match private_key.split_first() {
+            Some((&SEED_TAG, seed)) if seed.first() == Some(&(SEED_BYTES as u8)) => {
+                seed[1..].to_vec()
+            }
+            Some((0x30, _)) => Vec::<OctetString>::from_der(private_key)?
+                .first()
+                .map(|seed| seed.as_bytes().to_vec())
+                .ok_or(Error::Key)?,
+            _ => return Err(Error::Key),
+        },
+    };
+    if key.len() != algorithm.secret_key_bytes {
+        return Err(Error::Key);
+    }
+    Ok(Key::Secret(algorithm.tag(&key)))
+}
@@ -0,0 +1,222 @@
+//! Passphrase-protected secret key files.
+//!
+//! A key file is a fixed header followed by the sealed secret key:
+//!
+//! ```text
+//! "PQSK" | version | algorithm | m_cost | t_cost | p_cost | salt | nonce | sealed key
+//!   4        1          1          4        4        4       16     12
+//! ```
+//!
+//! The algorithm byte is the level the key belongs to (2, 3, 5, 44, 65 or 87),
+//! and the costs are the Argon2id parameters (little endian) that derive the
+//! ChaCha20-Poly1305 key from the passphrase and salt. The header is
+//! authenticated as associated data, so changing the algorithm or weakening
+//! the parameters makes opening the file fail like a wrong passphrase does.
+
+use std::fmt;
+
+use argon2::{Algorithm, Argon2, Params, Version};
+use chacha20poly1305::aead::rand_core::RngCore;
+use chacha20poly1305::aead::{Aead, AeadCore, KeyInit, OsRng, Payload};
+use chacha20poly1305::{ChaCha20Poly1305, Key, Nonce};
+
+const MAGIC: &[u8; 4] = b"PQSK";
+const VERSION: u8 = 1;
+const SALT_BYTES: usize = 16;
+const NONCE_BYTES: usize = 12;
+const SALT_OFFSET: usize = 4 + 1 + 1 + 3 * 4;
+const HEADER_BYTES: usize = SALT_OFFSET + SALT_BYTES + NONCE_BYTES;
+
+//Refuse files asking for more than 1 GiB of Argon2 memory, 16 passes or 16
+//lanes, so a crafted file cannot keep `open` busy for hours
+const MAX_M_COST: u32 = 1 << 20;
+const MAX_T_COST: u32 = 16;
+const MAX_P_COST: u32 = 16;
+
+#[derive(Debug)]
+pub enum Error {
+    /// Not a key file, or one of a version this build does not read.
+    Format,
+    /// The key belongs to another level.
+    Algorithm(u8),
+    /// Wrong passphrase, or the file was modified.
+    Passphrase,
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Format => write!(f, "not a supported encrypted key file"),
+            Error::Algorithm(level) => write!(f, "key file holds a level {} key", level),
+            Error::Passphrase => write!(f, "wrong passphrase or corrupted key file"),
+        }
+    }
+}
+
+/// Whether `file` is an encrypted key file rather than a raw secret key.
+pub fn is_encrypted(file: &[u8]) -> bool {
+    file.starts_with(MAGIC)
+}
+
+/// The level an encrypted key file names in its header, which is only
+/// authenticated once [`open`] succeeds.
+pub fn algorithm(file: &[u8]) -> Option<u8> {
+    file.get(5).copied().filter(|_| is_encrypted(file))
+}
+
+/// Seals `secret_key` of the given level under `passphrase`, with a fresh salt
+/// and nonce and Argon2's default cost parameters.
+pub fn seal(secret_key: &[u8], algorithm: u8, passphrase: &[u8]) -> Vec<u8> {
+    let params = Params::default();
+    let mut salt = [0u8; SALT_BYTES];
+    OsRng.fill_bytes(&mut salt);
+    let nonce = ChaCha20Poly1305::generate_nonce(&mut OsRng);
+
+    let mut file = Vec::with_capacity(HEADER_BYTES + secret_key.len() + 16);
+    file.extend_from_slice(MAGIC);
+    file.push(VERSION);
+    file.push(algorithm);
+    for cost in [params.m_cost(), params.t_cost(), params.p_cost()] {
+        file.extend_from_slice(&cost.to_le_bytes());
+    }
+    file.extend_from_slice(&salt);
+    file.extend_from_slice(&nonce);
+
+    let sealed = cipher(passphrase, &salt, params)
+        .and_then(|cipher| {
+            let payload = Payload {
+                msg: secret_key,
+                aad: &file,
+            };
+            cipher.encrypt(&nonce, payload).map_err(|_| Error::Format)
+        })
+        .expect("default parameters are valid");
+    file.extend(sealed);
+    file
+}
+
+/// Opens a key file sealed by [`seal`], checking that it holds a key of the
+/// given level.
+pub fn open(file: &[u8], algorithm: u8, passphrase: &[u8]) -> Result<Vec<u8>, Error> {
+    if file.len() < HEADER_BYTES || !is_encrypted(file) || file[4] != VERSION {
+        return Err(Error::Format);
+    }
+    if file[5] != algorithm {
+        return Err(Error::Algorithm(file[5]));
+    }
+    let (header, sealed) = file.split_at(HEADER_BYTES);
+    let cost = |i: usize| u32::from_le_bytes(header[6 + 4 * i..10 + 4 * i].try_into().unwrap());
+    if cost(0) > MAX_M_COST || cost(1) > MAX_T_COST || cost(2) > MAX_P_COST {
+        return Err(Error::Format);
+    }
+    let params = Params::new(cost(0), cost(1), cost(2), None).map_err(|_| Error::Format)?;
+    let (salt, nonce) = header[SALT_OFFSET..].split_at(SALT_BYTES);
+    let payload = Payload {
+        msg: sealed,
+        aad: header,
+    };
+    cipher(passphrase, salt, params)?
+        .decrypt(Nonce::from_slice(nonce), payload)
+        .map_err(|_| Error::Passphrase)
+}
+
+//Derives the file key from the passphrase with Argon2id
+fn cipher(passphrase: &[u8], salt: &[u8], params: Params) -> Result<ChaCha20Poly1305, Error> {
+    let mut key = [0u8; 32];
+    Argon2::new(Algorithm::Argon2id, Version::V0x13, params)
+        .hash_password_into(passphrase, salt, &mut key)
+        .map_err(|_| Error::Format)?;
+    Ok(ChaCha20Poly1305::new(Key::from_slice(&key)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const KEY: &[u8] = b"not really a secret key";
+    const LEVEL: u8 = 65;
+
+    //Offsets of the level and the costs in the header
+    const ALGORITHM_OFFSET: usize = 5;
+    const M_COST_OFFSET: usize = 6;
+    const T_COST_OFFSET: usize = 10;
+    const P_COST_OFFSET: usize = 14;
+
+    #[test]
+    fn round_trip() {
+        let file = seal(KEY, LEVEL, b"passphrase");
+        assert!(is_encrypted(&file));
+        assert_eq!(open(&file, LEVEL, b"passphrase").unwrap(), KEY);
+    }
+
+    #[test]
+    fn wrong_passphrase_is_refused() {
+        let file = seal(KEY, LEVEL, b"passphrase");
+        assert!(matches!(
+            open(&file, LEVEL, b"passphrasf"),
+            Err(Error::Passphrase)
+        ));
+    }
+
+    #[test]
+    fn tampered_header_is_refused() {
+        let file = seal(KEY, LEVEL, b"passphrase");
+
+        let mut tampered = file.clone();
+        tampered[ALGORITHM_OFFSET] = 44;
+        assert!(matches!(
+            open(&tampered, 44, b"passphrase"),
+            Err(Error::Passphrase)
+        ));
+        assert!(matches!(
+            open(&tampered, LEVEL, b"passphrase"),
+            Err(Error::Algorithm(44))
+        ));
+
+        //Weaker parameters derive another key, and the header is authenticated anyway
+        let mut tampered = file.clone();
+        tampered[T_COST_OFFSET] -= 1;
+        assert!(matches!(
+            open(&tampered, LEVEL, b"passphrase"),
+            Err(Error::Passphrase)
+        ));
+
+        //The nonce only feeds the AEAD, which then fails to authenticate
+        let mut tampered = file.clone();
+        tampered[HEADER_BYTES - 1] ^= 1;
+        assert!(matches!(
+            open(&tampered, LEVEL, b"passphrase"),
+            Err(Error::Passphrase)
+        ));
+    }
+
+    #[test]
+    fn excessive_costs_are_refused_before_hashing() {
+        let file = seal(KEY, LEVEL, b"passphrase");
+        for (offset, cost) in [
+            (M_COST_OFFSET, MAX_M_COST + 1),
+            (T_COST_OFFSET, MAX_T_COST + 1),
+            (P_COST_OFFSET, MAX_P_COST + 1),
+        ] {
+            let mut tampered = file.clone();
+            tampered[offset..offset + 4].copy_from_slice(&cost.to_le_bytes());
+            assert!(matches!(
+                open(&tampered, LEVEL, b"passphrase"),
+                Err(Error::Format)
+            ));
+        }
+    }
+
+    #[test]
+    fn truncated_file_is_refused() {
+        let file = seal(KEY, LEVEL, b"passphrase");
+        assert!(matches!(
+            open(&file[..HEADER_BYTES - 1], LEVEL, b"passphrase"),
+            Err(Error::Format)
+        ));
+        assert!(matches!(
+            open(&file[..file.len() - 1], LEVEL, b"passphrase"),
+            Err(Error::Passphrase)
+        ));
+    }
+}
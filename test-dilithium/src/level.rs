@@ -0,0 +1,129 @@
+//! The parameter set keys are made and signed with, chosen with `--level`.
+//!
+//! ML-DSA-44, ML-DSA-65 (the default) and ML-DSA-87 are dispatched at run time,
+//! so every build has all three. Round-3 Dilithium comes from pqc_dilithium,
+//! which fixes its sizes at compile time: a build has the round-3 level of its
+//! `mode2`/`mode5` feature (Dilithium3 without either) and no other.
+
+use std::fmt;
+use std::str::FromStr;
+
+use pqc_dilithium::{crypto_sign_keypair, PUBLICKEYBYTES, SECRETKEYBYTES};
+use signing_formats::mldsa::{self, ParameterSet};
+
+/// The round-3 level pqc_dilithium was built for.
+pub const ROUND3_LEVEL: u8 = if cfg!(feature = "mode2") {
+    2
+} else if cfg!(feature = "mode5") {
+    5
+} else {
+    3
+};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Level {
+    MlDsa(ParameterSet),
+    /// Round-3 Dilithium at [`ROUND3_LEVEL`].
+    Round3,
+}
+
+impl Default for Level {
+    fn default() -> Self {
+        Level::MlDsa(ParameterSet::MlDsa65)
+    }
+}
+
+impl FromStr for Level {
+    type Err = String;
+
+    fn from_str(level: &str) -> Result<Level, String> {
+        let unknown = || {
+            format!(
+                "unknown level {}; use 44, 65, 87 or {}",
+                level, ROUND3_LEVEL
+            )
+        };
+        match level.parse::<u8>().map_err(|_| unknown())? {
+            number if number == ROUND3_LEVEL => Ok(Level::Round3),
+            number @ (2 | 3 | 5) => Err(format!(
+                "Dilithium{} is not in this build, which has Dilithium{}: pqc_dilithium fixes the round-3 level at compile time (--features mode2 or mode5); ML-DSA levels 44, 65 and 87 are always available",
+                number, ROUND3_LEVEL
+            )),
+            number => ParameterSet::from_level(number)
+                .map(Level::MlDsa)
+                .ok_or_else(unknown),
+        }
+    }
+}
+
+impl fmt::Display for Level {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl Level {
+    /// The level as in `--level`, which is also the algorithm byte of
+    /// encrypted key files and embedded signatures.
+    pub fn number(self) -> u8 {
+        match self {
+            Level::MlDsa(set) => set.level(),
+            Level::Round3 => ROUND3_LEVEL,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Level::MlDsa(set) => set.name(),
+            Level::Round3 => match ROUND3_LEVEL {
+                2 => "Dilithium2",
+                5 => "Dilithium5",
+                _ => "Dilithium3",
+            },
+        }
+    }
+
+    /// The level of a raw secret key, if this build can sign with it.
+    pub fn of_secret_key(secret_key: &[u8]) -> Option<Level> {
+        match ParameterSet::of_secret_key(secret_key) {
+            Some(set) => Some(Level::MlDsa(set)),
+            None => (secret_key.len() == SECRETKEYBYTES).then_some(Level::Round3),
+        }
+    }
+
+    /// Generates a (public key, secret key) pair, derived from `seed` when
+    /// given (the FIPS 204 seed for ML-DSA, the seed round-3 key generation
+    /// draws from the system otherwise).
+    pub fn keypair(self, seed: Option<&[u8; 32]>) -> (Vec<u8>, Vec<u8>) {
+        match (self, seed) {
+            (Level::MlDsa(set), Some(seed)) => mldsa::keypair_from_seed(set, seed),
+            (Level::MlDsa(set), None) => mldsa::keypair(set).expect("no randomness available"),
+            (Level::Round3, seed) => {
+                let mut public_key = vec![0u8; PUBLICKEYBYTES];
+                let mut secret_key = vec![0u8; SECRETKEYBYTES];
+                crypto_sign_keypair(&mut public_key, &mut secret_key, seed.map(|seed| &seed[..]));
+                (public_key, secret_key)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_this_builds_round3_level_parses() {
+        for level in [2, 3, 5] {
+            let parsed = level.to_string().parse::<Level>();
+            match level == ROUND3_LEVEL {
+                true => assert_eq!(parsed, Ok(Level::Round3)),
+                false => assert!(parsed.unwrap_err().contains("compile time")),
+            }
+        }
+        for set in ParameterSet::ALL {
+            assert_eq!(set.level().to_string().parse(), Ok(Level::MlDsa(set)));
+        }
+        assert!("65x".parse::<Level>().is_err());
+    }
+}
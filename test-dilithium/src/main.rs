@@ -1,56 +1,914 @@
 use pqc_dilithium::*;
+use serde_json::json;
+use sha2::{Digest, Sha256};
+use signing_formats::framing;
+use signing_formats::mldsa::{self, ParameterSet};
+use std::collections::HashMap;
+use std::io::Write;
+use std::path::PathBuf;
 use std::process;
 
+use error::Error;
+use key_format::{Key, KeyFormat};
+use level::Level;
+use report::{OutputFormat, Report, Status};
+use wasm_signature::{Entry, Section, SignedModule};
+
+mod error;
+mod key_format;
+mod keyfile;
+mod level;
+mod manifest;
+mod report;
+mod wasm_signature;
+
+//Length of a `--seed`
+const SEED_BYTES: usize = 32;
+
+//Environment variables consulted when no passphrase file is given
+const PASSPHRASE_VAR: &str = "TEST_DILITHIUM_PASSPHRASE";
+const NEW_PASSPHRASE_VAR: &str = "TEST_DILITHIUM_NEW_PASSPHRASE";
+
+//Why is in `signing_formats::framing`
+const ROUND3_CONTEXT: &str =
+    "round-3 Dilithium takes no --context; use an ML-DSA level (44, 65 or 87)";
+
 fn main() {
-    //Read command line arguments - input_file, signature_file and command verify or sign
     let args: Vec<String> = std::env::args().collect();
-    if args.len() != 4 {
-        println!("Usage: {} <command> <input_file> <signature_file>", args[0]);
-        println!("Commands: sign or verify");
-        process::exit(1);
+    //Looked up first so that even errors in the other arguments come out as asked
+    let format = match args.windows(2).find(|pair| pair[0] == "--format") {
+        Some(pair) => pair[1].parse().map_err(Error::Input),
+        None => Ok(OutputFormat::Text),
+    };
+    let (format, result) = match format {
+        Ok(format) => (format, run(&args)),
+        Err(e) => (OutputFormat::Text, Err(e)),
+    };
+    let code = match result {
+        Ok(report) => {
+            report.print(format);
+            report.exit_code()
+        }
+        Err(e) => {
+            report::print_error(&e, format);
+            e.exit_code()
+        }
+    };
+    process::exit(code);
+}
+
+fn run(args: &[String]) -> Result<Report, Error> {
+    //Read command line arguments - input_file, signature_file and command verify or sign
+    let (positional, options) = parse_args(&args[1..])?;
+    let arity = match positional.first() {
+        Some(&"keygen") | Some(&"change-passphrase") => 1,
+        Some(&"verify-wasm") => 2,
+        _ => 3,
+    };
+    if positional.len() != arity {
+        return Err(Error::Usage(args[0].clone()));
+    }
+    let command = positional[0];
+
+    //A misspelt option would otherwise be ignored, e.g. signing without the intended context
+    if let Some(allowed) = options_of(command) {
+        let mut unknown: Vec<_> = options
+            .keys()
+            .filter(|name| **name != "format" && !allowed.contains(name))
+            .collect();
+        unknown.sort();
+        if let Some(name) = unknown.first() {
+            return Err(Error::Input(format!(
+                "unknown option --{} for {}",
+                name, command
+            )));
+        }
+    }
+
+    //ML-DSA levels are picked at run time; keys bring their own level unless one is asked for
+    let level = options
+        .get("level")
+        .map(|level| level.parse::<Level>().map_err(Error::Input))
+        .transpose()?;
+
+    //FIPS 204 caps context strings at 255 bytes
+    let context = options.get("context").copied();
+    if context.is_some_and(|context| context.len() > 255) {
+        return Err(Error::Input(
+            "--context must be at most 255 bytes".to_string(),
+        ));
+    }
+
+    //Refused before anything is written; keys of round-3 levels refuse it when signing too
+    if context.is_some()
+        && level == Some(Level::Round3)
+        && matches!(command, "sign" | "sign-wasm" | "sign-dir")
+    {
+        return Err(Error::Input(ROUND3_CONTEXT.to_string()));
+    }
+
+    //A fixed seed makes keys, and therefore signatures, reproducible
+    let seed = options
+        .get("seed")
+        .map(|seed| parse_seed(seed))
+        .transpose()?;
+
+    //Key files default to the working directory
+    let secret_key_file = options.get("secret-key").copied();
+    let public_key_file = options
+        .get("public-key")
+        .copied()
+        .unwrap_or("public_key.txt");
+    if seed.is_some()
+        && secret_key_file.is_some()
+        && matches!(command, "sign" | "sign-wasm" | "sign-dir")
+    {
+        return Err(Error::Input(
+            "use either --seed or --secret-key to sign".to_string(),
+        ));
+    }
+    //Without --secret-key signing makes a one-off key, which may go to a new
+    //--public-key file but must never replace the key a user named
+    let one_off_key_file = options.get("public-key").copied();
+    if secret_key_file.is_none()
+        && matches!(command, "sign" | "sign-wasm" | "sign-dir")
+        && one_off_key_file.is_some_and(|file| std::fs::symlink_metadata(file).is_ok())
+    {
+        return Err(Error::Input(format!(
+            "{} already exists; pass --secret-key to sign with its key, or name a new file for the one-off public key",
+            public_key_file
+        )));
     }
-    let command = &args[1];
-    let input_file = &args[2];
-    let signature_file = &args[3];
+
+    //Keys are written raw unless asked otherwise, and read in any format
+    let key_format = match options.get("key-format") {
+        Some(format) => format.parse().map_err(Error::Input)?,
+        None => KeyFormat::Raw,
+    };
+
+    //Secret key files are sealed under a passphrase when one is given
+    let passphrase = read_passphrase(&options, "passphrase-file", PASSPHRASE_VAR)?;
 
     //Based on the command sign or verify the input file
-    match command.as_str() {
-        "sign" => sign_file(input_file, signature_file),
-        "verify" => verify_file(input_file, signature_file),
-        _ => {
-            println!("Invalid command. Use 'sign' or 'verify'.");
+    match command {
+        "keygen" => keygen(
+            level.unwrap_or_default(),
+            seed.as_ref(),
+            secret_key_file.unwrap_or("secret_key.txt"),
+            public_key_file,
+            passphrase.as_deref(),
+            options.contains_key("no-encrypt"),
+            key_format,
+        ),
+        "convert-key" => convert_key(
+            positional[1],
+            positional[2],
+            level,
+            passphrase.as_deref(),
+            key_format,
+        ),
+        "change-passphrase" => change_passphrase(
+            secret_key_file.unwrap_or("secret_key.txt"),
+            passphrase.as_deref(),
+            read_passphrase(&options, "new-passphrase-file", NEW_PASSPHRASE_VAR)?.as_deref(),
+        ),
+        "sign" => match secret_key_file {
+            Some(secret_key_file) => sign_with_key_file(
+                positional[1],
+                positional[2],
+                context,
+                level,
+                secret_key_file,
+                passphrase.as_deref(),
+            ),
+            None => sign_file(
+                positional[1],
+                positional[2],
+                context,
+                level.unwrap_or_default(),
+                seed.as_ref(),
+                one_off_key_file,
+                key_format,
+            ),
+        },
+        "verify" => verify_file(positional[1], positional[2], context, level, public_key_file),
+        "sign-wasm" => {
+            //Embedded signatures name their key, so a one-off key is only useful if kept
+            let (public_key, secret_key) = match secret_key_file {
+                Some(secret_key_file) => (
+                    read_public_key(public_key_file, level)?,
+                    read_secret_key(secret_key_file, level, passphrase.as_deref())?,
+                ),
+                None => {
+                    let (public_key, secret_key) = level.unwrap_or_default().keypair(seed.as_ref());
+                    write_one_off_key(one_off_key_file, &public_key, key_format)?;
+                    (public_key, secret_key)
+                }
+            };
+            sign_wasm(
+                positional[1],
+                positional[2],
+                context,
+                &public_key,
+                &secret_key,
+            )
+        }
+        "verify-wasm" => verify_wasm(positional[1], context, level, public_key_file),
+        //The manifest is signed like any other file, into `<manifest_file>.sig`
+        "sign-dir" => {
+            let signature_file = format!("{}.sig", positional[2]);
+            let files = write_manifest(positional[1], positional[2])?;
+            let mut report = match secret_key_file {
+                Some(secret_key_file) => sign_with_key_file(
+                    positional[2],
+                    &signature_file,
+                    context,
+                    level,
+                    secret_key_file,
+                    passphrase.as_deref(),
+                ),
+                None => sign_file(
+                    positional[2],
+                    &signature_file,
+                    context,
+                    level.unwrap_or_default(),
+                    seed.as_ref(),
+                    one_off_key_file,
+                    key_format,
+                ),
+            }?;
+            report.lines.push(format!(
+                "Wrote manifest of {} files to {} and its signature to {}",
+                files, positional[2], signature_file
+            ));
+            report.fields.insert("files".into(), json!(files));
+            Ok(report)
+        }
+        "verify-dir" => verify_dir(positional[1], positional[2], context, level, public_key_file),
+        _ => Err(Error::Input(format!(
+            "unknown command {}; use 'sign', 'verify', 'sign-wasm', 'verify-wasm', 'sign-dir', 'verify-dir', 'keygen', 'change-passphrase' or 'convert-key'",
+            command
+        ))),
+    }
+}
+
+fn read_file(file: &str) -> Result<Vec<u8>, Error> {
+    std::fs::read(file).map_err(|e| Error::Io(file.to_string(), e))
+}
+
+fn write_file(file: &str, contents: &[u8]) -> Result<(), Error> {
+    std::fs::write(file, contents).map_err(|e| Error::Io(file.to_string(), e))
+}
+
+//Hex SHA-256, for the fingerprints and digests of reports
+fn digest(data: &[u8]) -> String {
+    hex::encode(Sha256::digest(data))
+}
+
+//Reads a passphrase from the file named by `--<option>`, falling back to the
+//environment variable `var`
+fn read_passphrase(
+    options: &HashMap<&str, &str>,
+    option: &str,
+    var: &str,
+) -> Result<Option<Vec<u8>>, Error> {
+    let mut passphrase = match options.get(option) {
+        Some(file) => read_file(file)?,
+        None => match std::env::var(var) {
+            Ok(passphrase) => passphrase.into_bytes(),
+            Err(_) => return Ok(None),
+        },
+    };
+    //Drop the line ending most editors leave at the end of a file
+    if passphrase.ends_with(b"\n") {
+        passphrase.pop();
+        if passphrase.ends_with(b"\r") {
+            passphrase.pop();
+        }
+    }
+    if passphrase.is_empty() {
+        return Err(Error::Input("empty passphrase".to_string()));
+    }
+    Ok(Some(passphrase))
+}
+
+//Decodes a `--seed` of 32 hex-encoded bytes
+fn parse_seed(seed: &str) -> Result<[u8; SEED_BYTES], Error> {
+    let seed = hex::decode(seed).ok().and_then(|seed| seed.try_into().ok());
+    seed.ok_or_else(|| Error::Input(format!("--seed must be {} hex-encoded bytes", SEED_BYTES)))
+}
+
+//Options each command takes besides --format, None for unknown commands
+fn options_of(command: &str) -> Option<&'static [&'static str]> {
+    Some(match command {
+        "keygen" => &[
+            "level",
+            "seed",
+            "secret-key",
+            "public-key",
+            "key-format",
+            "passphrase-file",
+            "no-encrypt",
+        ],
+        "sign" | "sign-wasm" | "sign-dir" => &[
+            "level",
+            "context",
+            "seed",
+            "secret-key",
+            "public-key",
+            "key-format",
+            "passphrase-file",
+        ],
+        "verify" | "verify-wasm" | "verify-dir" => &["level", "context", "public-key"],
+        "convert-key" => &["level", "key-format", "passphrase-file"],
+        "change-passphrase" => &["secret-key", "passphrase-file", "new-passphrase-file"],
+        _ => return None,
+    })
+}
+
+//Options that stand alone
+const FLAGS: [&str; 1] = ["no-encrypt"];
+
+//Splits arguments into positional arguments and `--name value` options
+fn parse_args(args: &[String]) -> Result<(Vec<&str>, HashMap<&str, &str>), Error> {
+    let mut positional = Vec::new();
+    let mut options = HashMap::new();
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.strip_prefix("--") {
+            //Flags take no value
+            Some(name) if FLAGS.contains(&name) => {
+                options.insert(name, "");
+            }
+            Some(name) => {
+                let value = args
+                    .next()
+                    .ok_or_else(|| Error::Input(format!("missing value for --{}", name)))?;
+                options.insert(name, value.as_str());
+            }
+            None => positional.push(arg.as_str()),
+        }
+    }
+    Ok((positional, options))
+}
+
+//The FIPS 204 message representative ML-DSA signs for `data`, as the signing
+//module's `sign_with_context` frames it. Contexts are checked against the limit
+//up front.
+fn message(data: &[u8], context: &str) -> Vec<u8> {
+    framing::context_message(context.as_bytes(), data).expect("context of at most 255 bytes")
+}
+
+//Signs `data` at the level of `secret_key`. Both backends sign
+//deterministically. ML-DSA always signs the FIPS 204 framing, with an empty
+//context when none is given; round-3 Dilithium takes no context.
+fn sign(data: Vec<u8>, context: Option<&str>, secret_key: &[u8]) -> Result<Vec<u8>, Error> {
+    match Level::of_secret_key(secret_key) {
+        Some(Level::MlDsa(_)) => {
+            let message = message(&data, context.unwrap_or(""));
+            Ok(mldsa::sign(&message, secret_key).expect("secret key of a known level"))
+        }
+        Some(Level::Round3) if context.is_some() => Err(Error::Input(ROUND3_CONTEXT.to_string())),
+        Some(Level::Round3) => {
+            let mut signature = vec![0u8; SIGNBYTES];
+            crypto_sign_signature(&mut signature, &data, secret_key);
+            Ok(signature)
+        }
+        None => Err(Error::Input(format!(
+            "secret key is neither an ML-DSA nor a {} key",
+            Level::Round3
+        ))),
+    }
+}
+
+//Refuses a key of another level than `--level` asked for
+fn check_level(level: Option<Level>, key: &Key, key_file: &str) -> Result<(), Error> {
+    match (level, key_format::level(key)) {
+        (Some(level), Some(actual)) if level.number() != actual => Err(Error::Input(format!(
+            "{} holds a {} key, not {}",
+            key_file,
+            key_format::algorithm_name(key),
+            level
+        ))),
+        _ => Ok(()),
+    }
+}
+
+//Writes a secret key file readable by the owner only, replacing any previous
+//file in one step so an interrupted write never loses the old key
+fn write_secret_key(secret_key_file: &str, contents: &[u8]) -> Result<(), Error> {
+    let temporary = format!("{}.tmp", secret_key_file);
+    let mut options = std::fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+    options
+        .open(&temporary)
+        .and_then(|mut file| file.write_all(contents))
+        .and_then(|_| std::fs::rename(&temporary, secret_key_file))
+        .map_err(|e| Error::Io(secret_key_file.to_string(), e))
+}
+
+//Writes the public key of a one-off keypair to `public_key.txt`, or to a
+//--public-key file that must not exist yet
+fn write_one_off_key(
+    public_key_file: Option<&str>,
+    public_key: &[u8],
+    key_format: KeyFormat,
+) -> Result<(), Error> {
+    let encoded = encode(Key::Public(public_key.to_vec()), key_format);
+    let Some(public_key_file) = public_key_file else {
+        return write_file("public_key.txt", &encoded);
+    };
+    std::fs::OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(public_key_file)
+        .and_then(|mut file| file.write_all(&encoded))
+        .map_err(|e| Error::Io(public_key_file.to_string(), e))
+}
+
+//Reads a key file in any format, failing unless it holds a public key, of
+//`level` when one is given
+fn read_public_key(public_key_file: &str, level: Option<Level>) -> Result<Vec<u8>, Error> {
+    let key = key_format::decode(&read_file(public_key_file)?)
+        .map_err(|e| Error::Input(format!("{}: {}", public_key_file, e)))?;
+    check_level(level, &key, public_key_file)?;
+    match key {
+        Key::Public(public_key) => Ok(public_key),
+        Key::Secret(_) => Err(Error::Input(format!(
+            "{} holds a secret key",
+            public_key_file
+        ))),
+    }
+}
+
+//Reads a secret key file in any format, opening it with `passphrase` when it is
+//encrypted, and fails unless it holds a secret key, of `level` when one is given
+fn read_secret_key(
+    secret_key_file: &str,
+    level: Option<Level>,
+    passphrase: Option<&[u8]>,
+) -> Result<Vec<u8>, Error> {
+    let contents = open_secret_key_file(secret_key_file, level, passphrase)?;
+    let key = key_format::decode(&contents)
+        .map_err(|e| Error::Input(format!("{}: {}", secret_key_file, e)))?;
+    check_level(level, &key, secret_key_file)?;
+    match key {
+        Key::Secret(secret_key) => Ok(secret_key),
+        Key::Public(_) => Err(Error::Input(format!(
+            "{} holds a public key",
+            secret_key_file
+        ))),
+    }
+}
+
+//Reads a key file, decrypting it with `passphrase` when it is encrypted. An
+//encrypted file must hold a key of `level` when one is given.
+fn open_secret_key_file(
+    secret_key_file: &str,
+    level: Option<Level>,
+    passphrase: Option<&[u8]>,
+) -> Result<Vec<u8>, Error> {
+    let contents = read_file(secret_key_file)?;
+    if !keyfile::is_encrypted(&contents) {
+        return Ok(contents);
+    }
+    let Some(passphrase) = passphrase else {
+        return Err(Error::Input(format!(
+            "{} is encrypted; pass --passphrase-file or set {}",
+            secret_key_file, PASSPHRASE_VAR
+        )));
+    };
+    //Without --level the header names it; it is authenticated when the key is
+    //opened, and files too short to name one fail to open either way
+    let algorithm = match level {
+        Some(level) => level.number(),
+        None => keyfile::algorithm(&contents).unwrap_or_default(),
+    };
+    keyfile::open(&contents, algorithm, passphrase).map_err(|e| Error::Input(e.to_string()))
+}
+
+fn keygen(
+    level: Level,
+    seed: Option<&[u8; SEED_BYTES]>,
+    secret_key_file: &str,
+    public_key_file: &str,
+    passphrase: Option<&[u8]>,
+    no_encrypt: bool,
+    key_format: KeyFormat,
+) -> Result<Report, Error> {
+    //Plaintext secret keys are only written when asked for by name
+    match (passphrase, no_encrypt) {
+        (Some(_), true) => {
+            return Err(Error::Input(
+                "use either a passphrase or --no-encrypt".to_string(),
+            ))
+        }
+        (None, false) => {
+            return Err(Error::Input(format!(
+                "{} would not be encrypted; pass --passphrase-file or set {}, or --no-encrypt to write it in plaintext",
+                secret_key_file, PASSPHRASE_VAR
+            )))
         }
+        _ => {}
     }
+    let (public_key, secret_key) = level.keypair(seed);
+    let key_fingerprint = digest(&public_key);
+    let secret_key = encode(Key::Secret(secret_key), key_format);
+    match passphrase {
+        Some(passphrase) => write_secret_key(
+            secret_key_file,
+            &keyfile::seal(&secret_key, level.number(), passphrase),
+        )?,
+        None => write_secret_key(secret_key_file, &secret_key)?,
+    }
+    let public_key = encode(Key::Public(public_key), key_format);
+    write_file(public_key_file, &public_key)?;
+    Ok(Report {
+        algorithm: Some(level.name()),
+        key_fingerprint: Some(key_fingerprint),
+        lines: vec![format!(
+            "Wrote {} secret key to {} and public key to {}",
+            level, secret_key_file, public_key_file
+        )],
+        ..Report::default()
+    })
+}
+
+//Encodes a key made here, which always has a known algorithm
+fn encode(key: Key, key_format: KeyFormat) -> Vec<u8> {
+    key_format::encode(&key, key_format).expect("keys made here encode")
 }
 
-fn sign_file(input_file: &str, signature_file: &str) {
+//Rewrites a public or secret key file in another format. Encrypted secret keys
+//are exported in plaintext, e.g. for OpenSSL, so they need their passphrase.
+fn convert_key(
+    input_key_file: &str,
+    output_key_file: &str,
+    level: Option<Level>,
+    passphrase: Option<&[u8]>,
+    key_format: KeyFormat,
+) -> Result<Report, Error> {
+    let contents = open_secret_key_file(input_key_file, level, passphrase)?;
+    let invalid = |e: key_format::Error| Error::Input(format!("{}: {}", input_key_file, e));
+    let key = key_format::decode(&contents).map_err(invalid)?;
+    check_level(level, &key, input_key_file)?;
+    let name = key_format::algorithm_name(&key);
+    let encoded = key_format::encode(&key, key_format).map_err(invalid)?;
+    let key_fingerprint = match &key {
+        Key::Public(public_key) => {
+            write_file(output_key_file, &encoded)?;
+            Some(digest(public_key))
+        }
+        Key::Secret(_) => {
+            eprintln!(
+                "Warning: {} is not encrypted; protect it with change-passphrase",
+                output_key_file
+            );
+            write_secret_key(output_key_file, &encoded)?;
+            None
+        }
+    };
+    Ok(Report {
+        algorithm: Some(name),
+        key_fingerprint,
+        lines: vec![format!("Wrote {} key to {}", name, output_key_file)],
+        ..Report::default()
+    })
+}
+
+//Re-seals a secret key file under a new passphrase; plaintext key files are
+//encrypted for the first time
+fn change_passphrase(
+    secret_key_file: &str,
+    passphrase: Option<&[u8]>,
+    new_passphrase: Option<&[u8]>,
+) -> Result<Report, Error> {
+    let Some(new_passphrase) = new_passphrase else {
+        return Err(Error::Input(format!(
+            "missing new passphrase; pass --new-passphrase-file or set {}",
+            NEW_PASSPHRASE_VAR
+        )));
+    };
+    //Sealed keys keep the format and level they were written in
+    let secret_key = open_secret_key_file(secret_key_file, None, passphrase)?;
+    let algorithm = match key_format::decode(&secret_key) {
+        Ok(key @ Key::Secret(_)) => key_format::level(&key),
+        _ => None,
+    };
+    let Some(algorithm) = algorithm else {
+        return Err(Error::Input(format!(
+            "{} holds no secret key",
+            secret_key_file
+        )));
+    };
+    let sealed = keyfile::seal(&secret_key, algorithm, new_passphrase);
+    write_secret_key(secret_key_file, &sealed)?;
+    Ok(Report {
+        lines: vec![format!("Changed the passphrase of {}", secret_key_file)],
+        ..Report::default()
+    })
+}
+
+//Signs with a one-off keypair and writes its public key next to the signature
+fn sign_file(
+    input_file: &str,
+    signature_file: &str,
+    context: Option<&str>,
+    level: Level,
+    seed: Option<&[u8; SEED_BYTES]>,
+    public_key_file: Option<&str>,
+    key_format: KeyFormat,
+) -> Result<Report, Error> {
     //read the input file
-    let data = std::fs::read(input_file).unwrap();
-    //generate a keypair
-    let keypair = Keypair::generate();
-    //public key
-    let public_key = keypair.public;
-    //sign the data
-    let signature = keypair.sign(&data);
+    let data = read_file(input_file)?;
+    let file_digest = digest(&data);
+    //generate a keypair and sign the data
+    let (public_key, secret_key) = level.keypair(seed);
+    let signature = sign(data, context, &secret_key)?;
     //write to signature file
-    std::fs::write(signature_file, signature).unwrap();
+    write_file(signature_file, &signature)?;
     //wirte to public key file
-    std::fs::write("public_key.txt", public_key).unwrap();
+    let key_fingerprint = digest(&public_key);
+    write_one_off_key(public_key_file, &public_key, key_format)?;
+    Ok(Report {
+        algorithm: Some(level.name()),
+        key_fingerprint: Some(key_fingerprint),
+        file_digest: Some(file_digest),
+        ..Report::default()
+    })
 }
 
-fn verify_file(input_file: &str, signature_file: &str) {
+//Signs with a secret key from `keygen`, leaving its public key untouched
+fn sign_with_key_file(
+    input_file: &str,
+    signature_file: &str,
+    context: Option<&str>,
+    level: Option<Level>,
+    secret_key_file: &str,
+    passphrase: Option<&[u8]>,
+) -> Result<Report, Error> {
+    let data = read_file(input_file)?;
+    let file_digest = digest(&data);
+    let secret_key = read_secret_key(secret_key_file, level, passphrase)?;
+    let signature = sign(data, context, &secret_key)?;
+    write_file(signature_file, &signature)?;
+    //ML-DSA secret keys are their seed, which the public key derives from;
+    //round-3 secret keys lack t1, so their reports name no key
+    let key_fingerprint = mldsa::public_key(&secret_key).map(|public_key| digest(&public_key));
+    Ok(Report {
+        algorithm: Level::of_secret_key(&secret_key).map(Level::name),
+        key_fingerprint,
+        file_digest: Some(file_digest),
+        ..Report::default()
+    })
+}
+
+fn verify_file(
+    input_file: &str,
+    signature_file: &str,
+    context: Option<&str>,
+    level: Option<Level>,
+    public_key_file: &str,
+) -> Result<Report, Error> {
     //read the input file
-    let data = std::fs::read(input_file).unwrap();
+    let data = read_file(input_file)?;
+    let file_digest = digest(&data);
     //read the signature file
-    let signature = std::fs::read(signature_file).unwrap();
+    let signature = read_file(signature_file)?;
     //read the public key file
-    let public_key = std::fs::read("public_key.txt").unwrap();
+    let public_key = read_public_key(public_key_file, level)?;
+    let valid = check_signature(data, &signature, &public_key, context)?;
+    Ok(verdict(valid, &public_key, file_digest))
+}
+
+//Checks `signature` over `data`, returning whether it is valid, or why the
+//input cannot be checked
+fn check_signature(
+    data: Vec<u8>,
+    signature: &[u8],
+    public_key: &[u8],
+    context: Option<&str>,
+) -> Result<bool, Error> {
+    //tagged ML-DSA keys of any level go to the ML-DSA backend, untagged ones are round-3
+    if let Some(set) = ParameterSet::of_public_key(public_key) {
+        //Like round-3 signatures, ones of the wrong size are malformed rather than invalid
+        if signature.len() != set.signature_bytes() {
+            return Err(Error::Input("malformed signature".to_string()));
+        }
+        let data = message(&data, context.unwrap_or(""));
+        return Ok(mldsa::verify(&data, signature, public_key));
+    }
+    if context.is_some() {
+        return Err(Error::Input(ROUND3_CONTEXT.to_string()));
+    }
+    //keys from another level would otherwise be sliced out of bounds
+    if public_key.len() != PUBLICKEYBYTES {
+        return Err(Error::Input(format!(
+            "public key is neither an ML-DSA nor a {} key",
+            Level::Round3
+        )));
+    }
     //verify the signature
-    match verify(&signature, &data, &public_key) {
-        Ok(_) => println!("Signature verified"),
-        Err(e) => match e {
-            SignError::Input => println!("Invalid input"),
-            SignError::Verify => println!("Invalid signature"),
-        },
+    match verify(signature, &data, public_key) {
+        Ok(_) => Ok(true),
+        Err(SignError::Verify) => Ok(false),
+        Err(SignError::Input) => Err(Error::Input("malformed signature".to_string())),
+    }
+}
+
+//Report of a verification, naming the key checked against and what it covered
+fn verdict(valid: bool, public_key: &[u8], file_digest: String) -> Report {
+    let (status, line) = match valid {
+        true => (Status::Valid, "Signature verified"),
+        false => (Status::Invalid, "Invalid signature"),
+    };
+    Report {
+        status,
+        algorithm: Some(key_format::algorithm_name(&Key::Public(
+            public_key.to_vec(),
+        ))),
+        key_fingerprint: Some(digest(public_key)),
+        file_digest: Some(file_digest),
+        lines: vec![line.to_string()],
+        ..Report::default()
+    }
+}
+
+fn parse_module(wasm_file: &str) -> Result<SignedModule, Error> {
+    SignedModule::parse(&read_file(wasm_file)?)
+        .map_err(|e| Error::Input(format!("{}: {}", wasm_file, e)))
+}
+
+//Embeds a signature in a wasm module instead of writing a detached file. A
+//module that is already signed and unchanged keeps its other signatures.
+fn sign_wasm(
+    input_file: &str,
+    output_file: &str,
+    context: Option<&str>,
+    public_key: &[u8],
+    secret_key: &[u8],
+) -> Result<Report, Error> {
+    let module = parse_module(input_file)?;
+    let hash = module.hash();
+    let key_id = wasm_signature::key_id(public_key);
+    let mut section = module
+        .section
+        .as_deref()
+        .and_then(Section::decode)
+        .filter(|section| section.hash == hash)
+        .unwrap_or_else(|| Section::new(hash));
+    section.signatures.retain(|entry| entry.key_id != key_id);
+
+    let signature = sign(section.signed_message(), context, secret_key)?;
+    //A signature under the wrong public key would name a key nobody can verify with
+    let verdict = check_signature(section.signed_message(), &signature, public_key, context);
+    let key = Key::Public(public_key.to_vec());
+    let (Ok(true), Some(algorithm)) = (verdict, key_format::level(&key)) else {
+        return Err(Error::Input(
+            "the public key does not belong to the secret key".to_string(),
+        ));
+    };
+    section.signatures.push(Entry {
+        key_id,
+        algorithm,
+        signature,
+    });
+
+    let signed = module.emit(&section);
+    write_file(output_file, &signed)?;
+    let signatures = section.signatures.len();
+    let name = key_format::algorithm_name(&key);
+    let mut report = Report {
+        algorithm: Some(name),
+        key_fingerprint: Some(digest(public_key)),
+        file_digest: Some(hex::encode(hash)),
+        lines: vec![format!(
+            "Signed {} with {} ({} signature{})",
+            output_file,
+            name,
+            signatures,
+            if signatures == 1 { "" } else { "s" }
+        )],
+        ..Report::default()
+    };
+    report.fields.insert("signatures".into(), json!(signatures));
+    Ok(report)
+}
+
+//The digest of a module is its hash without the signature section, which is
+//what signatures cover
+fn verify_wasm(
+    wasm_file: &str,
+    context: Option<&str>,
+    level: Option<Level>,
+    public_key_file: &str,
+) -> Result<Report, Error> {
+    let public_key = read_public_key(public_key_file, level)?;
+    let module = parse_module(wasm_file)?;
+    let mut report = verdict(false, &public_key, hex::encode(module.hash()));
+    let Some(section) = module.section.as_deref() else {
+        report.lines = vec![format!(
+            "Invalid signature: {} has no signature section",
+            wasm_file
+        )];
+        return Ok(report);
+    };
+    let Some(section) = Section::decode(section) else {
+        return Err(Error::Input("malformed signature section".to_string()));
+    };
+    if section.hash != module.hash() {
+        report.lines =
+            vec!["Invalid signature: the module changed after it was signed".to_string()];
+        return Ok(report);
+    }
+    let key_id = wasm_signature::key_id(&public_key);
+    let Some(entry) = section
+        .signatures
+        .iter()
+        .find(|entry| entry.key_id == key_id)
+    else {
+        report.lines = vec!["Invalid signature: no signature by this public key".to_string()];
+        return Ok(report);
+    };
+    if Some(entry.algorithm) != key_format::level(&Key::Public(public_key.clone())) {
+        return Err(Error::Input(
+            "signature algorithm does not match the public key".to_string(),
+        ));
+    }
+    let valid = check_signature(
+        section.signed_message(),
+        &entry.signature,
+        &public_key,
+        context,
+    )?;
+    Ok(verdict(valid, &public_key, hex::encode(module.hash())))
+}
+
+//Hashes every file under `dir` and writes the manifest, which never lists
+//itself or its signature when they are kept inside the directory. Returns the
+//number of files listed.
+fn write_manifest(dir: &str, manifest_file: &str) -> Result<usize, Error> {
+    let entries = manifest::scan(dir, &excluded(manifest_file))?;
+    write_file(manifest_file, &manifest::encode(&entries))?;
+    Ok(entries.len())
+}
+
+//A manifest and its signature, as `manifest::scan` excludes them
+fn excluded(manifest_file: &str) -> Vec<PathBuf> {
+    [manifest_file, &format!("{}.sig", manifest_file)]
+        .into_iter()
+        .filter_map(manifest::absolute)
+        .collect()
+}
+
+fn verify_dir(
+    dir: &str,
+    manifest_file: &str,
+    context: Option<&str>,
+    level: Option<Level>,
+    public_key_file: &str,
+) -> Result<Report, Error> {
+    let manifest = read_file(manifest_file)?;
+    let signature = read_file(&format!("{}.sig", manifest_file))?;
+    let public_key = read_public_key(public_key_file, level)?;
+    //Nothing in the manifest is trusted before its signature is
+    let valid = check_signature(manifest.clone(), &signature, &public_key, context)?;
+    let mut report = verdict(valid, &public_key, digest(&manifest));
+    if !valid {
+        return Ok(report);
+    }
+    let Some(expected) = manifest::decode(&manifest) else {
+        return Err(Error::Input(format!("{} is not a manifest", manifest_file)));
+    };
+
+    let actual = manifest::scan(dir, &excluded(manifest_file))?;
+    let diff = manifest::compare(&expected, &actual);
+    report.lines.clear();
+    for (change, paths) in [
+        ("added", &diff.added),
+        ("missing", &diff.missing),
+        ("modified", &diff.modified),
+    ] {
+        for path in paths {
+            report.lines.push(format!("{}: {}", change, path));
+        }
+        report.fields.insert(change.into(), json!(paths));
+    }
+    report.fields.insert("files".into(), json!(expected.len()));
+    if diff.is_empty() {
+        report
+            .lines
+            .push(format!("Signature verified ({} files)", expected.len()));
+    } else {
+        report.status = Status::Invalid;
+        report.lines.push(format!(
+            "Invalid signature: {} added, {} missing and {} modified files",
+            diff.added.len(),
+            diff.missing.len(),
+            diff.modified.len()
+        ));
     }
+    Ok(report)
 }
@@ -0,0 +1,346 @@
+//! Manifests of whole directories, so that a release bundle is signed once
+//! instead of file by file.
+//!
+//! A manifest is UTF-8 text: a header line, then one line per regular file,
+//! sorted by path:
+//!
+//! ```text
+//! test-dilithium manifest v1
+//! <size> <SHA-256> <SHA3-256> <path>
+//! ```
+//!
+//! Sizes are decimal, hashes lowercase hex and paths relative to the directory
+//! with `/` separators, never `.`, `..` or absolute. A set of files has exactly
+//! one manifest, and manifests in any other form are rejected, so what is
+//! signed is always the files rather than how the manifest happened to be
+//! written. Empty directories are not recorded; symbolic links and other
+//! special files are refused rather than followed.
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs;
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+
+use sha2::{Digest, Sha256};
+use sha3::Sha3_256;
+
+const HEADER: &str = "test-dilithium manifest v1";
+
+/// One file of a manifest.
+#[derive(PartialEq, Eq)]
+pub struct Entry {
+    pub path: String,
+    size: u64,
+    sha256: [u8; 32],
+    sha3_256: [u8; 32],
+}
+
+/// How a directory differs from its manifest, as sorted lists of paths.
+#[derive(Default)]
+pub struct Diff {
+    pub added: Vec<String>,
+    pub missing: Vec<String>,
+    pub modified: Vec<String>,
+}
+
+impl Diff {
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.missing.is_empty() && self.modified.is_empty()
+    }
+}
+
+#[derive(Debug)]
+pub enum Error {
+    /// A file or directory could not be read.
+    Io(PathBuf, io::Error),
+    /// A symbolic link, device, socket or pipe.
+    Special(PathBuf),
+    /// A name that is not UTF-8 or contains a line break.
+    Name(PathBuf),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Io(path, e) => write!(f, "{}: {}", path.display(), e),
+            Error::Special(path) => {
+                write!(f, "{}: not a regular file or directory", path.display())
+            }
+            Error::Name(path) => write!(
+                f,
+                "{}: file names must be UTF-8 without line breaks",
+                path.display()
+            ),
+        }
+    }
+}
+
+/// Hashes every regular file under `dir` except the `excluded` ones (given
+/// as absolute paths, see [`absolute`]), sorted by path.
+pub fn scan(dir: &str, excluded: &[PathBuf]) -> Result<Vec<Entry>, Error> {
+    let root = Path::new(dir)
+        .canonicalize()
+        .map_err(|e| Error::Io(dir.into(), e))?;
+    let mut entries = Vec::new();
+    walk(&root, "", excluded, &mut entries)?;
+    entries.sort_by(|a, b| a.path.cmp(&b.path));
+    Ok(entries)
+}
+
+fn walk(
+    dir: &Path,
+    prefix: &str,
+    excluded: &[PathBuf],
+    entries: &mut Vec<Entry>,
+) -> Result<(), Error> {
+    let io = |e| Error::Io(dir.to_path_buf(), e);
+    for item in fs::read_dir(dir).map_err(io)? {
+        let item = item.map_err(io)?;
+        let path = item.path();
+        let name = match item.file_name().into_string() {
+            Ok(name) if !name.contains(['\n', '\r']) => name,
+            _ => return Err(Error::Name(path)),
+        };
+        let relative = format!("{}{}", prefix, name);
+        //Not followed: a link could point anywhere, including out of the directory
+        let file_type = item.file_type().map_err(io)?;
+        if file_type.is_dir() {
+            walk(&path, &format!("{}/", relative), excluded, entries)?;
+        } else if !file_type.is_file() {
+            return Err(Error::Special(path));
+        } else if !excluded.contains(&path) {
+            entries.push(hash(&path, relative)?);
+        }
+    }
+    Ok(())
+}
+
+//Streams a file through both hashes, so large files are never held in memory
+fn hash(path: &Path, relative: String) -> Result<Entry, Error> {
+    let io = |e| Error::Io(path.to_path_buf(), e);
+    let mut file = fs::File::open(path).map_err(io)?;
+    let mut sha256 = Sha256::new();
+    let mut sha3_256 = Sha3_256::new();
+    let mut size = 0u64;
+    let mut buffer = vec![0u8; 64 * 1024];
+    loop {
+        let read = file.read(&mut buffer).map_err(io)?;
+        if read == 0 {
+            break;
+        }
+        sha256.update(&buffer[..read]);
+        sha3_256.update(&buffer[..read]);
+        size += read as u64;
+    }
+    Ok(Entry {
+        path: relative,
+        size,
+        sha256: sha256.finalize().into(),
+        sha3_256: sha3_256.finalize().into(),
+    })
+}
+
+/// Absolute path of a file that may not exist yet, for excluding a manifest
+/// and its signature from the directory they describe.
+pub fn absolute(file: &str) -> Option<PathBuf> {
+    let path = Path::new(file);
+    let parent = match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    };
+    Some(parent.canonicalize().ok()?.join(path.file_name()?))
+}
+
+pub fn encode(entries: &[Entry]) -> Vec<u8> {
+    let mut manifest = format!("{}\n", HEADER);
+    for entry in entries {
+        manifest.push_str(&format!(
+            "{} {} {} {}\n",
+            entry.size,
+            hex::encode(entry.sha256),
+            hex::encode(entry.sha3_256),
+            entry.path
+        ));
+    }
+    manifest.into_bytes()
+}
+
+/// Parses a manifest, or returns None unless it is exactly as [`encode`]
+/// writes it.
+pub fn decode(manifest: &[u8]) -> Option<Vec<Entry>> {
+    let text = std::str::from_utf8(manifest).ok()?;
+    let mut lines = text.strip_suffix('\n')?.split('\n');
+    if lines.next()? != HEADER {
+        return None;
+    }
+    let mut entries = Vec::new();
+    for line in lines {
+        let mut fields = line.splitn(4, ' ');
+        let size = fields.next()?.parse().ok()?;
+        let sha256 = hex::decode(fields.next()?).ok()?.try_into().ok()?;
+        let sha3_256 = hex::decode(fields.next()?).ok()?.try_into().ok()?;
+        let path = fields.next().filter(|path| is_relative(path))?.to_string();
+        entries.push(Entry {
+            path,
+            size,
+            sha256,
+            sha3_256,
+        });
+    }
+    //Sorted without duplicates, with every number and hash in its one spelling
+    let canonical =
+        entries.windows(2).all(|pair| pair[0].path < pair[1].path) && encode(&entries) == manifest;
+    canonical.then_some(entries)
+}
+
+//Whether `path` names a file inside the directory the way `scan` spells it,
+//so no entry can point out of the directory
+fn is_relative(path: &str) -> bool {
+    path.split('/').all(|part| !matches!(part, "" | "." | ".."))
+}
+
+/// Compares the files a manifest lists with the files found now.
+pub fn compare(expected: &[Entry], actual: &[Entry]) -> Diff {
+    let expected: BTreeMap<_, _> = expected.iter().map(|e| (&e.path, e)).collect();
+    let actual: BTreeMap<_, _> = actual.iter().map(|e| (&e.path, e)).collect();
+    let mut diff = Diff::default();
+    for (path, entry) in &actual {
+        match expected.get(path) {
+            None => diff.added.push(path.to_string()),
+            Some(expected) if expected != entry => diff.modified.push(path.to_string()),
+            Some(_) => {}
+        }
+    }
+    diff.missing = expected
+        .keys()
+        .filter(|path| !actual.contains_key(*path))
+        .map(|path| path.to_string())
+        .collect();
+    diff
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    //A directory of its own per test, removed when dropped
+    struct TempDir(PathBuf);
+
+    impl TempDir {
+        fn new(name: &str) -> TempDir {
+            let dir = std::env::temp_dir().join(format!(
+                "test-dilithium-manifest-{}-{}",
+                std::process::id(),
+                name
+            ));
+            fs::create_dir_all(dir.join("sub")).unwrap();
+            fs::write(dir.join("a.txt"), b"first file").unwrap();
+            fs::write(dir.join("sub").join("b.bin"), [0u8, 1, 2, 3]).unwrap();
+            TempDir(dir)
+        }
+
+        fn path(&self) -> &str {
+            self.0.to_str().unwrap()
+        }
+    }
+
+    impl Drop for TempDir {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    fn manifest_of(dir: &TempDir) -> String {
+        String::from_utf8(encode(&scan(dir.path(), &[]).unwrap())).unwrap()
+    }
+
+    //The manifest with `field` of the entry for `path` replaced
+    fn with_field(manifest: &str, path: &str, field: usize, value: &str) -> String {
+        manifest
+            .lines()
+            .map(|line| {
+                let mut fields: Vec<_> = line.splitn(4, ' ').collect();
+                if fields.get(3) == Some(&path) {
+                    fields[field] = value;
+                }
+                format!("{}\n", fields.join(" "))
+            })
+            .collect()
+    }
+
+    #[test]
+    fn encode_decode_round_trip() {
+        let dir = TempDir::new("round-trip");
+        let entries = scan(dir.path(), &[]).unwrap();
+        let paths: Vec<_> = entries.iter().map(|entry| entry.path.as_str()).collect();
+        assert_eq!(paths, ["a.txt", "sub/b.bin"]);
+        assert!(decode(&encode(&entries)).unwrap() == entries);
+        assert!(compare(&entries, &scan(dir.path(), &[]).unwrap()).is_empty());
+    }
+
+    #[test]
+    fn tampered_entries_show_as_modified() {
+        let dir = TempDir::new("tampered");
+        let manifest = manifest_of(&dir);
+        let actual = scan(dir.path(), &[]).unwrap();
+        let zero_hash = "00".repeat(32);
+        for (field, value) in [(0, "11"), (1, zero_hash.as_str()), (2, zero_hash.as_str())] {
+            let tampered = with_field(&manifest, "a.txt", field, value);
+            let diff = compare(&decode(tampered.as_bytes()).unwrap(), &actual);
+            assert_eq!(diff.modified, ["a.txt"], "field {}", field);
+            assert!(diff.added.is_empty() && diff.missing.is_empty());
+        }
+    }
+
+    #[test]
+    fn other_spellings_are_rejected() {
+        let dir = TempDir::new("spellings");
+        let manifest = manifest_of(&dir);
+        let sha256 = manifest.lines().nth(1).unwrap().split(' ').nth(1).unwrap();
+        for tampered in [
+            with_field(&manifest, "a.txt", 0, "010"),
+            with_field(&manifest, "a.txt", 1, &sha256.to_uppercase()),
+            with_field(&manifest, "a.txt", 2, "00"),
+            manifest.replace('\n', "\r\n"),
+            manifest.trim_end().to_string(),
+        ] {
+            assert!(decode(tampered.as_bytes()).is_none(), "{}", tampered);
+        }
+        //Unsorted or duplicate entries
+        let mut lines: Vec<_> = manifest.lines().collect();
+        lines.swap(1, 2);
+        assert!(decode(format!("{}\n", lines.join("\n")).as_bytes()).is_none());
+        lines[1] = lines[2];
+        assert!(decode(format!("{}\n", lines.join("\n")).as_bytes()).is_none());
+    }
+
+    #[test]
+    fn extra_and_missing_files_are_listed() {
+        let dir = TempDir::new("extra-missing");
+        let expected = scan(dir.path(), &[]).unwrap();
+        fs::remove_file(dir.0.join("a.txt")).unwrap();
+        fs::write(dir.0.join("sub").join("c.txt"), b"new").unwrap();
+        let diff = compare(&expected, &scan(dir.path(), &[]).unwrap());
+        assert_eq!(diff.added, ["sub/c.txt"]);
+        assert_eq!(diff.missing, ["a.txt"]);
+        assert!(diff.modified.is_empty());
+    }
+
+    #[test]
+    fn paths_out_of_the_directory_are_rejected() {
+        let dir = TempDir::new("traversal");
+        let manifest = manifest_of(&dir);
+        for path in [
+            "../a.txt",
+            "sub/../../a.txt",
+            "/etc/passwd",
+            "./a.txt",
+            "sub//b.bin",
+            "sub/",
+        ] {
+            let tampered = manifest.replace(" a.txt\n", &format!(" {}\n", path));
+            assert!(decode(tampered.as_bytes()).is_none(), "{}", path);
+        }
+    }
+}
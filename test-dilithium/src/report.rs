@@ -0,0 +1,107 @@
+//! What a command prints: the usual lines by default, or a single JSON object
+//! with `--format json`. Either way the exit code tells outcomes apart:
+//!
+//! - 0: the command succeeded, or the signature is valid
+//! - 1: the signature is invalid
+//! - 2: malformed input (arguments, keys, signatures, modules or manifests)
+//! - 3: a file could not be read or written
+//!
+//! JSON output always has `status` (`ok`, `valid`, `invalid`,
+//! `malformed_input` or `io_error`); reports add `algorithm`,
+//! `key_fingerprint` (SHA-256 of the raw public key), `file_digest` (SHA-256
+//! of the signed bytes), the text lines as `messages` and command-specific
+//! fields, errors add `error`.
+
+use std::str::FromStr;
+
+use serde_json::{json, Map, Value};
+
+use crate::error::Error;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum OutputFormat {
+    Text,
+    Json,
+}
+
+impl FromStr for OutputFormat {
+    type Err = String;
+
+    fn from_str(format: &str) -> Result<Self, Self::Err> {
+        match format {
+            "text" => Ok(OutputFormat::Text),
+            "json" => Ok(OutputFormat::Json),
+            _ => Err(format!(
+                "unknown output format {}; use text or json",
+                format
+            )),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Default, PartialEq, Eq)]
+pub enum Status {
+    /// A command other than a verification succeeded.
+    #[default]
+    Done,
+    Valid,
+    Invalid,
+}
+
+/// Outcome of a command that ran to completion.
+#[derive(Default)]
+pub struct Report {
+    pub status: Status,
+    pub algorithm: Option<&'static str>,
+    pub key_fingerprint: Option<String>,
+    pub file_digest: Option<String>,
+    pub lines: Vec<String>,
+    pub fields: Map<String, Value>,
+}
+
+impl Report {
+    pub fn exit_code(&self) -> i32 {
+        match self.status {
+            Status::Done | Status::Valid => 0,
+            Status::Invalid => 1,
+        }
+    }
+
+    pub fn print(&self, format: OutputFormat) {
+        if format == OutputFormat::Text {
+            for line in &self.lines {
+                println!("{}", line);
+            }
+            return;
+        }
+        let mut object = Map::new();
+        let status = match self.status {
+            Status::Done => "ok",
+            Status::Valid => "valid",
+            Status::Invalid => "invalid",
+        };
+        object.insert("status".into(), json!(status));
+        object.insert("algorithm".into(), json!(self.algorithm));
+        object.insert("key_fingerprint".into(), json!(self.key_fingerprint));
+        object.insert("file_digest".into(), json!(self.file_digest));
+        object.insert("messages".into(), json!(self.lines));
+        object.extend(self.fields.clone());
+        println!("{}", Value::Object(object));
+    }
+}
+
+/// Prints a failed command: to stderr as text, or to stdout as JSON so that
+/// callers always get one object to parse.
+pub fn print_error(e: &Error, format: OutputFormat) {
+    match format {
+        OutputFormat::Text => eprintln!("{}", e),
+        OutputFormat::Json => {
+            //The usage text is for people
+            let error = match e {
+                Error::Usage(_) => "wrong number of arguments".to_string(),
+                e => e.to_string(),
+            };
+            println!("{}", json!({ "status": e.status(), "error": error }))
+        }
+    }
+}
@@ -0,0 +1,55 @@
+//! Wasm modules as `sign-wasm` and `verify-wasm` read them. The signature
+//! section itself is `signing_formats::wasm_section`, shared with wasm-api.
+
+use signing_formats::wasm_section;
+pub use signing_formats::wasm_section::{key_id, Entry, Section};
+
+/// A module split into its signature section and everything else.
+pub struct SignedModule {
+    /// The module without its signature section, as hashed and signed.
+    pub unsigned: Vec<u8>,
+    /// Raw contents of the signature section, if the module has one.
+    pub section: Option<Vec<u8>>,
+}
+
+impl SignedModule {
+    pub fn parse(wasm: &[u8]) -> Result<SignedModule, String> {
+        //walrus only validates; the sections are split off the bytes as they are
+        walrus::Module::from_buffer(wasm)
+            .map_err(|e| format!("not a valid wasm module: {:#}", e))?;
+        let (unsigned, section) = wasm_section::split(wasm).ok_or("not a binary wasm module")?;
+        Ok(SignedModule {
+            unsigned,
+            section: section.map(<[u8]>::to_vec),
+        })
+    }
+
+    /// SHA-256 of the module without its signature section.
+    pub fn hash(&self) -> [u8; 32] {
+        wasm_section::hash(&self.unsigned)
+    }
+
+    /// Emits the module with `section` as its signature section.
+    pub fn emit(self, section: &Section) -> Vec<u8> {
+        wasm_section::emit(self.unsigned, section)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    //What walrus accepts must split the same way, so a module test-dilithium
+    //signs is one wasm-api verifies
+    #[test]
+    fn signed_modules_split_as_the_service_splits_them() {
+        let wasm = include_bytes!("../../wasm-api/kyber_kem_module.wasm.multivalue.wasm");
+        let module = SignedModule::parse(wasm).unwrap();
+        let (unsigned, section) = wasm_section::split(wasm).unwrap();
+        assert_eq!(module.unsigned, unsigned);
+        assert_eq!(module.section.as_deref(), section);
+        let section = Section::decode(section.unwrap()).unwrap();
+        assert_eq!(section.hash, module.hash());
+        assert_eq!(module.emit(&section), wasm);
+    }
+}
@@ -0,0 +1,283 @@
+//! Runs the built binary and checks the exit codes and JSON reports callers
+//! rely on: 0 valid, 1 invalid, 2 malformed input, 3 I/O error.
+
+use std::fs;
+use std::path::PathBuf;
+use std::process::Command;
+
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+
+//A directory of its own per test, removed when dropped
+struct TempDir(PathBuf);
+
+impl TempDir {
+    fn new(name: &str) -> TempDir {
+        let dir = std::env::temp_dir().join(format!(
+            "test-dilithium-cli-{}-{}",
+            std::process::id(),
+            name
+        ));
+        fs::create_dir_all(&dir).unwrap();
+        TempDir(dir)
+    }
+
+    //Runs the binary in this directory with `--format json`
+    fn run(&self, args: &[&str]) -> (i32, Value) {
+        let output = Command::new(env!("CARGO_BIN_EXE_test-dilithium"))
+            .current_dir(&self.0)
+            .args(args)
+            .args(["--format", "json"])
+            .output()
+            .unwrap();
+        let report = serde_json::from_slice(&output.stdout).expect("one JSON object");
+        (output.status.code().unwrap(), report)
+    }
+
+    //A file to sign and a key to sign it with
+    fn signed_file(&self) {
+        fs::write(self.0.join("release.txt"), b"release contents").unwrap();
+        let (code, _) = self.run(&[
+            "keygen",
+            "--no-encrypt",
+            "--seed",
+            &"07".repeat(32),
+            "--secret-key",
+            "release.sk",
+            "--public-key",
+            "release.pk",
+        ]);
+        assert_eq!(code, 0);
+        let (code, report) = self.run(&[
+            "sign",
+            "--secret-key",
+            "release.sk",
+            "release.txt",
+            "release.txt.sig",
+        ]);
+        assert_eq!(code, 0);
+        //The key is named without reading its public key file
+        let public_key = fs::read(self.0.join("release.pk")).unwrap();
+        assert_eq!(
+            report["key_fingerprint"],
+            hex::encode(Sha256::digest(public_key))
+        );
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.0);
+    }
+}
+
+fn verify(dir: &TempDir, signature: &str) -> (i32, Value) {
+    dir.run(&[
+        "verify",
+        "--public-key",
+        "release.pk",
+        "release.txt",
+        signature,
+    ])
+}
+
+#[test]
+fn valid_signature() {
+    let dir = TempDir::new("valid");
+    dir.signed_file();
+    let (code, report) = verify(&dir, "release.txt.sig");
+    assert_eq!(code, 0);
+    assert_eq!(report["status"], "valid");
+    assert!(report["algorithm"].is_string());
+    let public_key = fs::read(dir.0.join("release.pk")).unwrap();
+    assert_eq!(
+        report["key_fingerprint"],
+        hex::encode(Sha256::digest(public_key))
+    );
+    assert_eq!(
+        report["file_digest"],
+        hex::encode(Sha256::digest(b"release contents"))
+    );
+}
+
+#[test]
+fn invalid_signature() {
+    let dir = TempDir::new("invalid");
+    dir.signed_file();
+    fs::write(dir.0.join("release.txt"), b"release contentz").unwrap();
+    let (code, report) = verify(&dir, "release.txt.sig");
+    assert_eq!(code, 1);
+    assert_eq!(report["status"], "invalid");
+    assert_eq!(
+        report["file_digest"],
+        hex::encode(Sha256::digest(b"release contentz"))
+    );
+}
+
+#[test]
+fn malformed_input() {
+    let dir = TempDir::new("malformed");
+    dir.signed_file();
+    fs::write(dir.0.join("short.sig"), b"not a signature").unwrap();
+    let (code, report) = verify(&dir, "short.sig");
+    assert_eq!(code, 2);
+    assert_eq!(report["status"], "malformed_input");
+    assert!(report["error"].is_string());
+
+    let (code, report) = dir.run(&["sign", "--seed", "zz", "release.txt", "out.sig"]);
+    assert_eq!(code, 2);
+    assert_eq!(report["status"], "malformed_input");
+
+    let (code, report) = dir.run(&["verify", "release.txt"]);
+    assert_eq!(code, 2);
+    assert_eq!(report["status"], "malformed_input");
+}
+
+#[test]
+fn io_error() {
+    let dir = TempDir::new("io");
+    dir.signed_file();
+    let (code, report) = verify(&dir, "missing.sig");
+    assert_eq!(code, 3);
+    assert_eq!(report["status"], "io_error");
+    assert!(report["error"].as_str().unwrap().contains("missing.sig"));
+}
+
+#[test]
+fn levels_are_picked_at_run_time() {
+    let dir = TempDir::new("levels");
+    fs::write(dir.0.join("release.txt"), b"release contents").unwrap();
+    for (level, algorithm) in [
+        ("44", "ML-DSA-44"),
+        ("65", "ML-DSA-65"),
+        ("87", "ML-DSA-87"),
+    ] {
+        let secret_key = format!("{}.sk", level);
+        let public_key = format!("{}.pk", level);
+        let signature = format!("{}.sig", level);
+        let (code, report) = dir.run(&[
+            "keygen",
+            "--no-encrypt",
+            "--level",
+            level,
+            "--secret-key",
+            &secret_key,
+            "--public-key",
+            &public_key,
+        ]);
+        assert_eq!(code, 0);
+        assert_eq!(report["algorithm"], algorithm);
+        //The key brings its level, so signing needs no --level
+        let (code, report) = dir.run(&[
+            "sign",
+            "--secret-key",
+            &secret_key,
+            "release.txt",
+            &signature,
+        ]);
+        assert_eq!(code, 0);
+        assert_eq!(report["algorithm"], algorithm);
+        let (code, report) = dir.run(&[
+            "verify",
+            "--level",
+            level,
+            "--public-key",
+            &public_key,
+            "release.txt",
+            &signature,
+        ]);
+        assert_eq!((code, &report["algorithm"]), (0, &algorithm.into()));
+    }
+
+    //A key of another level than asked for is refused
+    let (code, report) = dir.run(&[
+        "sign",
+        "--level",
+        "87",
+        "--secret-key",
+        "44.sk",
+        "release.txt",
+        "mismatch.sig",
+    ]);
+    assert_eq!(code, 2);
+    assert!(report["error"].as_str().unwrap().contains("ML-DSA-44"));
+    assert!(!dir.0.join("mismatch.sig").exists());
+
+    let (code, _) = dir.run(&["keygen", "--no-encrypt", "--level", "66"]);
+    assert_eq!(code, 2);
+}
+
+#[test]
+fn misspelt_options_are_refused() {
+    let dir = TempDir::new("misspelt");
+    dir.signed_file();
+    let (code, report) = dir.run(&[
+        "sign",
+        "--contxt",
+        "release",
+        "--secret-key",
+        "release.sk",
+        "release.txt",
+        "typo.sig",
+    ]);
+    assert_eq!(code, 2);
+    assert_eq!(report["status"], "malformed_input");
+    assert!(report["error"].as_str().unwrap().contains("--contxt"));
+    assert!(!dir.0.join("typo.sig").exists());
+
+    let (code, report) = dir.run(&[
+        "sign",
+        "--secret-kye",
+        "release.sk",
+        "release.txt",
+        "typo.sig",
+    ]);
+    assert_eq!(code, 2);
+    assert!(report["error"].as_str().unwrap().contains("--secret-kye"));
+    //Without the check this would have signed with a one-off key
+    assert!(!dir.0.join("typo.sig").exists());
+    assert!(!dir.0.join("public_key.txt").exists());
+
+    //Options of other commands are refused too
+    let (code, _) = dir.run(&[
+        "verify",
+        "--secret-key",
+        "release.sk",
+        "release.txt",
+        "release.txt.sig",
+    ]);
+    assert_eq!(code, 2);
+}
+
+#[test]
+fn keygen_needs_a_passphrase_or_no_encrypt() {
+    let dir = TempDir::new("plaintext");
+    let (code, report) = dir.run(&["keygen", "--secret-key", "release.sk"]);
+    assert_eq!(code, 2);
+    assert!(report["error"].as_str().unwrap().contains("--no-encrypt"));
+    assert!(!dir.0.join("release.sk").exists());
+    assert!(!dir.0.join("public_key.txt").exists());
+
+    fs::write(dir.0.join("pass"), b"passphrase\n").unwrap();
+    let (code, _) = dir.run(&[
+        "keygen",
+        "--passphrase-file",
+        "pass",
+        "--no-encrypt",
+        "--secret-key",
+        "release.sk",
+    ]);
+    assert_eq!(code, 2);
+
+    let (code, _) = dir.run(&[
+        "keygen",
+        "--passphrase-file",
+        "pass",
+        "--secret-key",
+        "release.sk",
+    ]);
+    assert_eq!(code, 0);
+    assert!(fs::read(dir.0.join("release.sk"))
+        .unwrap()
+        .starts_with(b"PQSK"));
+}
@@ -1,20 +1,18 @@
-use std::{
-    io::{Read, Write},
-    net::TcpStream,
-};
+use std::{net::TcpStream, process};
 
-use pqc_kyber::encapsulate;
+use test_kyber::connect;
 
 fn main() {
     let mut stream = TcpStream::connect("127.0.0.1:8080").unwrap();
-    //Create a Random number generator
-    let mut rng = rand::thread_rng();
-    //Recieve Server's public key
-    let mut server_public_key = [0u8; 1184];
-    stream.read_exact(&mut server_public_key).unwrap();
-    //Encapsulate a shared secret
-    let (ciphertext, shared_secret_client) = encapsulate(&server_public_key, &mut rng).unwrap();
-    //Send ciphertext to server
-    stream.write_all(&ciphertext).unwrap();
-    println!("shared_secret_bob: {:?}", shared_secret_client);
+    //The server picks the parameter set; any set this build supports is accepted
+    match connect(&mut stream) {
+        Ok((param_set, shared_secret_client)) => {
+            println!("Negotiated {}", param_set);
+            println!("shared_secret_bob: {:?}", shared_secret_client);
+        }
+        Err(e) => {
+            println!("{}", e);
+            process::exit(1);
+        }
+    }
 }
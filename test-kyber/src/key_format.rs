@@ -0,0 +1,110 @@
+//! SubjectPublicKeyInfo and PKCS#8 encodings of ML-KEM keys, as DER or PEM.
+//!
+//! Keys use the FIPS 203 OIDs (2.16.840.1.101.3.4.4.1, .2 and .3) in the form
+//! OpenSSL 3.5 reads: the encapsulation key as the SPKI bit string, and the
+//! 64-byte seed as the `seed [0]` choice of the PKCS#8 private key. Keys in the
+//! `both` choice (seed and expanded key) are accepted as well. Round-3 Kyber has
+//! no standard OID and is not encoded.
+
+use pkcs8::der::asn1::{BitStringRef, OctetString, OctetStringRef};
+use pkcs8::der::pem::{self, LineEnding};
+use pkcs8::der::{Decode, Encode};
+use pkcs8::spki::{AlgorithmIdentifierRef, ObjectIdentifier, SubjectPublicKeyInfoRef};
+use pkcs8::PrivateKeyInfoRef;
+
+use crate::{mlkem, ParamSet};
+
+/// PEM label of SubjectPublicKeyInfo blocks.
+pub const PUBLIC_KEY_LABEL: &str = "PUBLIC KEY";
+/// PEM label of PKCS#8 blocks.
+pub const PRIVATE_KEY_LABEL: &str = "PRIVATE KEY";
+
+//Tag of the `seed [0] IMPLICIT OCTET STRING` choice
+const SEED_TAG: u8 = 0x80;
+
+const ML_KEM: [ParamSet; 3] = [ParamSet::MlKem512, ParamSet::MlKem768, ParamSet::MlKem1024];
+
+fn oid(param_set: ParamSet) -> Option<ObjectIdentifier> {
+    match param_set {
+        ParamSet::MlKem512 => Some(ObjectIdentifier::new_unwrap("2.16.840.1.101.3.4.4.1")),
+        ParamSet::MlKem768 => Some(ObjectIdentifier::new_unwrap("2.16.840.1.101.3.4.4.2")),
+        ParamSet::MlKem1024 => Some(ObjectIdentifier::new_unwrap("2.16.840.1.101.3.4.4.3")),
+        _ => None,
+    }
+}
+
+fn from_oid(oid: ObjectIdentifier) -> Option<ParamSet> {
+    ML_KEM
+        .into_iter()
+        .find(|param_set| self::oid(*param_set) == Some(oid))
+}
+
+fn identifier(param_set: ParamSet) -> Option<AlgorithmIdentifierRef<'static>> {
+    Some(AlgorithmIdentifierRef {
+        oid: oid(param_set)?,
+        parameters: None,
+    })
+}
+
+/// Encodes an ML-KEM encapsulation key as a DER SubjectPublicKeyInfo.
+pub fn public_key_to_der(param_set: ParamSet, public_key: &[u8]) -> Option<Vec<u8>> {
+    if public_key.len() != param_set.public_key_bytes() {
+        return None;
+    }
+    let spki = SubjectPublicKeyInfoRef {
+        algorithm: identifier(param_set)?,
+        subject_public_key: BitStringRef::from_bytes(public_key).ok()?,
+    };
+    spki.to_der().ok()
+}
+
+pub fn public_key_from_der(der: &[u8]) -> Option<(ParamSet, Vec<u8>)> {
+    let spki = SubjectPublicKeyInfoRef::from_der(der).ok()?;
+    let param_set = from_oid(spki.algorithm.oid)?;
+    let public_key = spki.subject_public_key.as_bytes()?;
+    (public_key.len() == param_set.public_key_bytes()).then(|| (param_set, public_key.to_vec()))
+}
+
+/// Encodes an ML-KEM secret key (its 64-byte seed) as a DER PKCS#8 structure.
+pub fn secret_key_to_der(param_set: ParamSet, secret_key: &[u8]) -> Option<Vec<u8>> {
+    if secret_key.len() != mlkem::SEED_BYTES {
+        return None;
+    }
+    let seed = [&[SEED_TAG, mlkem::SEED_BYTES as u8], secret_key].concat();
+    let pkcs8 = PrivateKeyInfoRef {
+        algorithm: identifier(param_set)?,
+        private_key: OctetStringRef::new(&seed).ok()?,
+        public_key: None,
+    };
+    pkcs8.to_der().ok()
+}
+
+pub fn secret_key_from_der(der: &[u8]) -> Option<(ParamSet, Vec<u8>)> {
+    let pkcs8 = PrivateKeyInfoRef::from_der(der).ok()?;
+    let param_set = from_oid(pkcs8.algorithm.oid)?;
+    let private_key = pkcs8.private_key.as_bytes();
+    //seed [0], or both: SEQUENCE { seed, expandedKey }; expanded-only keys carry no seed
+    let seed = match private_key.split_first()? {
+        (&SEED_TAG, seed) if seed.first() == Some(&(mlkem::SEED_BYTES as u8)) => seed[1..].to_vec(),
+        (0x30, _) => Vec::<OctetString>::from_der(private_key)
+            .ok()?
+            .first()?
+            .as_bytes()
+            .to_vec(),
+        _ => return None,
+    };
+    (seed.len() == mlkem::SEED_BYTES).then_some((param_set, seed))
+}
+
+/// Wraps DER in a PEM block with `label`.
+pub fn to_pem(label: &str, der: &[u8]) -> String {
+    pem::encode_string(label, LineEnding::LF, der).expect("label is valid")
+}
+
+/// Unwraps the DER of a PEM block, which must carry `label`.
+pub fn from_pem(label: &str, pem: &str) -> Option<Vec<u8>> {
+    match pem::decode_vec(pem.as_bytes()).ok()? {
+        (found, der) if found == label => Some(der),
+        _ => None,
+    }
+}
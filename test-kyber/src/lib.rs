@@ -0,0 +1,257 @@
+//! Handshake shared by the Kyber `server` and `client` binaries.
+//!
+//! The server opens every connection by announcing its parameter set as a
+//! single byte, followed by its public key. The client checks the announced
+//! set against the ones it supports before encapsulating, so peers built
+//! for different levels fail fast instead of misreading each other's keys.
+//!
+//! Two backends are available: round-3 Kyber from pqc_kyber, fixed at build
+//! time by the crate features, and FIPS 203 ML-KEM in all three sizes. They
+//! use distinct identifiers, so a client accepts either and follows the server.
+
+use std::io::{self, Read, Write};
+
+use rand::{CryptoRng, RngCore};
+
+pub mod key_format;
+mod mlkem;
+
+/// Length of the shared secret both backends agree on.
+pub const SHARED_SECRET_BYTES: usize = 32;
+
+/// KEM parameter sets and their identifiers on the wire.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ParamSet {
+    Kyber512,
+    Kyber768,
+    Kyber1024,
+    MlKem512,
+    MlKem768,
+    MlKem1024,
+}
+
+impl ParamSet {
+    /// The parameter set pqc_kyber was compiled with (see the crate features).
+    pub fn compiled() -> ParamSet {
+        match pqc_kyber::KYBER_K {
+            2 => ParamSet::Kyber512,
+            4 => ParamSet::Kyber1024,
+            _ => ParamSet::Kyber768,
+        }
+    }
+
+    /// The ML-KEM parameter set of the same strength.
+    pub fn ml_kem(self) -> ParamSet {
+        match self {
+            ParamSet::Kyber512 | ParamSet::MlKem512 => ParamSet::MlKem512,
+            ParamSet::Kyber768 | ParamSet::MlKem768 => ParamSet::MlKem768,
+            ParamSet::Kyber1024 | ParamSet::MlKem1024 => ParamSet::MlKem1024,
+        }
+    }
+
+    /// The ML-KEM parameter set of `size` (512, 768 or 1024), as in
+    /// `server --backend ml-kem --param-set 1024`.
+    pub fn ml_kem_of_size(size: &str) -> Option<ParamSet> {
+        match size {
+            "512" => Some(ParamSet::MlKem512),
+            "768" => Some(ParamSet::MlKem768),
+            "1024" => Some(ParamSet::MlKem1024),
+            _ => None,
+        }
+    }
+
+    /// Whether this build can run the parameter set: the compiled Kyber set
+    /// or any ML-KEM set.
+    pub fn is_supported(self) -> bool {
+        match self {
+            ParamSet::Kyber512 | ParamSet::Kyber768 | ParamSet::Kyber1024 => {
+                self == ParamSet::compiled()
+            }
+            ParamSet::MlKem512 | ParamSet::MlKem768 | ParamSet::MlKem1024 => true,
+        }
+    }
+
+    pub fn id(self) -> u8 {
+        match self {
+            ParamSet::Kyber512 => 0x01,
+            ParamSet::Kyber768 => 0x02,
+            ParamSet::Kyber1024 => 0x03,
+            ParamSet::MlKem512 => 0x11,
+            ParamSet::MlKem768 => 0x12,
+            ParamSet::MlKem1024 => 0x13,
+        }
+    }
+
+    pub fn from_id(id: u8) -> Option<ParamSet> {
+        match id {
+            0x01 => Some(ParamSet::Kyber512),
+            0x02 => Some(ParamSet::Kyber768),
+            0x03 => Some(ParamSet::Kyber1024),
+            0x11 => Some(ParamSet::MlKem512),
+            0x12 => Some(ParamSet::MlKem768),
+            0x13 => Some(ParamSet::MlKem1024),
+            _ => None,
+        }
+    }
+
+    //ML-KEM keeps the round-3 key and ciphertext sizes
+    pub fn public_key_bytes(self) -> usize {
+        match self {
+            ParamSet::Kyber512 | ParamSet::MlKem512 => 800,
+            ParamSet::Kyber768 | ParamSet::MlKem768 => 1184,
+            ParamSet::Kyber1024 | ParamSet::MlKem1024 => 1568,
+        }
+    }
+
+    pub fn ciphertext_bytes(self) -> usize {
+        match self {
+            ParamSet::Kyber512 | ParamSet::MlKem512 => 768,
+            ParamSet::Kyber768 | ParamSet::MlKem768 => 1088,
+            ParamSet::Kyber1024 | ParamSet::MlKem1024 => 1568,
+        }
+    }
+
+    /// Generates a (public key, secret key) pair. Panics for a Kyber set
+    /// other than the compiled one.
+    pub fn keypair<R: RngCore + CryptoRng>(self, rng: &mut R) -> (Vec<u8>, Vec<u8>) {
+        let mut seed = [0u8; mlkem::SEED_BYTES];
+        match self {
+            ParamSet::Kyber512 | ParamSet::Kyber768 | ParamSet::Kyber1024 => {
+                assert!(self.is_supported(), "{} is not compiled in", self);
+                let keys = pqc_kyber::keypair(rng).expect("randomness is available");
+                return (keys.public.to_vec(), keys.secret.to_vec());
+            }
+            _ => rng.fill_bytes(&mut seed),
+        }
+        let public_key = match self {
+            ParamSet::MlKem512 => mlkem::ml_kem_512::public_key(&seed),
+            ParamSet::MlKem768 => mlkem::ml_kem_768::public_key(&seed),
+            _ => mlkem::ml_kem_1024::public_key(&seed),
+        };
+        (public_key, seed.to_vec())
+    }
+
+    /// Encapsulates a fresh shared secret to `public_key`, returning the
+    /// ciphertext and the secret, or `None` for a malformed key.
+    pub fn encapsulate<R: RngCore + CryptoRng>(
+        self,
+        public_key: &[u8],
+        rng: &mut R,
+    ) -> Option<(Vec<u8>, [u8; SHARED_SECRET_BYTES])> {
+        if !self.is_supported() {
+            return None;
+        }
+        let mut m = [0u8; 32];
+        match self {
+            ParamSet::Kyber512 | ParamSet::Kyber768 | ParamSet::Kyber1024 => {
+                let (ciphertext, shared_secret) = pqc_kyber::encapsulate(public_key, rng).ok()?;
+                return Some((ciphertext.to_vec(), shared_secret));
+            }
+            _ => rng.fill_bytes(&mut m),
+        }
+        match self {
+            ParamSet::MlKem512 => mlkem::ml_kem_512::encapsulate(public_key, &m),
+            ParamSet::MlKem768 => mlkem::ml_kem_768::encapsulate(public_key, &m),
+            _ => mlkem::ml_kem_1024::encapsulate(public_key, &m),
+        }
+    }
+
+    /// Recovers the shared secret from `ciphertext`, or `None` for malformed
+    /// input.
+    pub fn decapsulate(
+        self,
+        ciphertext: &[u8],
+        secret_key: &[u8],
+    ) -> Option<[u8; SHARED_SECRET_BYTES]> {
+        if !self.is_supported() {
+            return None;
+        }
+        match self {
+            ParamSet::Kyber512 | ParamSet::Kyber768 | ParamSet::Kyber1024 => {
+                pqc_kyber::decapsulate(ciphertext, secret_key).ok()
+            }
+            ParamSet::MlKem512 => mlkem::ml_kem_512::decapsulate(ciphertext, secret_key),
+            ParamSet::MlKem768 => mlkem::ml_kem_768::decapsulate(ciphertext, secret_key),
+            ParamSet::MlKem1024 => mlkem::ml_kem_1024::decapsulate(ciphertext, secret_key),
+        }
+    }
+}
+
+impl std::fmt::Display for ParamSet {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            ParamSet::Kyber512 => "Kyber512",
+            ParamSet::Kyber768 => "Kyber768",
+            ParamSet::Kyber1024 => "Kyber1024",
+            ParamSet::MlKem512 => "ML-KEM-512",
+            ParamSet::MlKem768 => "ML-KEM-768",
+            ParamSet::MlKem1024 => "ML-KEM-1024",
+        };
+        f.write_str(name)
+    }
+}
+
+/// Server side of the handshake: announces `param_set`, sends a fresh public
+/// key and returns the secret decapsulated from the client's ciphertext.
+pub fn serve<S: Read + Write>(
+    stream: &mut S,
+    param_set: ParamSet,
+) -> io::Result<[u8; SHARED_SECRET_BYTES]> {
+    //Create a Random number generator
+    let mut rng = rand::thread_rng();
+    //Generate a keypair for server
+    let (public_key, secret_key) = param_set.keypair(&mut rng);
+    //Announce the parameter set, then send public key to client
+    stream.write_all(&[param_set.id()])?;
+    stream.write_all(&public_key)?;
+    //Receive Client Ciphertext
+    let mut ciphertext = vec![0u8; param_set.ciphertext_bytes()];
+    stream.read_exact(&mut ciphertext)?;
+    //Decapsulate shared secret
+    param_set
+        .decapsulate(&ciphertext, &secret_key)
+        .ok_or_else(|| invalid_data("malformed ciphertext".to_string()))
+}
+
+/// Client side of the handshake: validates the parameter set the server
+/// announces, encapsulates to its public key and returns the set together
+/// with the shared secret.
+pub fn connect<S: Read + Write>(
+    stream: &mut S,
+) -> io::Result<(ParamSet, [u8; SHARED_SECRET_BYTES])> {
+    //Create a Random number generator
+    let mut rng = rand::thread_rng();
+    //Receive and validate the server's parameter set
+    let mut announced = [0u8; 1];
+    stream.read_exact(&mut announced)?;
+    let param_set = match ParamSet::from_id(announced[0]) {
+        Some(param_set) if param_set.is_supported() => param_set,
+        Some(param_set) => {
+            return Err(invalid_data(format!(
+                "Server uses {} but this client was built for {}",
+                param_set,
+                ParamSet::compiled()
+            )))
+        }
+        None => {
+            return Err(invalid_data(format!(
+                "Server announced unknown parameter set {:#04x}",
+                announced[0]
+            )))
+        }
+    };
+    //Recieve Server's public key
+    let mut server_public_key = vec![0u8; param_set.public_key_bytes()];
+    stream.read_exact(&mut server_public_key)?;
+    //Encapsulate a shared secret
+    let (ciphertext, shared_secret) = param_set
+        .encapsulate(&server_public_key, &mut rng)
+        .ok_or_else(|| invalid_data("malformed public key".to_string()))?;
+    //Send ciphertext to server
+    stream.write_all(&ciphertext)?;
+    Ok((param_set, shared_secret))
+}
+
+fn invalid_data(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
@@ -0,0 +1,41 @@
+//! FIPS 203 ML-KEM backend. Secret keys are kept as the 64-byte seed the
+//! decapsulation key is derived from; randomness is supplied by the caller so
+//! that both backends draw from the same generator.
+
+use ml_kem::array::Array;
+use ml_kem::{Decapsulate, DecapsulationKey, EncapsulationKey, KeyExport};
+
+/// Length of the decapsulation key seed.
+pub const SEED_BYTES: usize = 64;
+
+macro_rules! parameter_set {
+    ($name:ident, $kem:ty) => {
+        pub mod $name {
+            use super::*;
+
+            /// Derives a keypair from `seed`, returning the encapsulation key.
+            pub fn public_key(seed: &[u8; SEED_BYTES]) -> Vec<u8> {
+                let dk = DecapsulationKey::<$kem>::from_seed(Array::from(*seed));
+                dk.encapsulation_key().to_bytes().to_vec()
+            }
+
+            /// Encapsulates to `public_key` with the 32 random bytes `m`.
+            pub fn encapsulate(public_key: &[u8], m: &[u8; 32]) -> Option<(Vec<u8>, [u8; 32])> {
+                let ek = EncapsulationKey::<$kem>::new(public_key.try_into().ok()?).ok()?;
+                let (ciphertext, shared_secret) = ek.encapsulate_deterministic(&Array::from(*m));
+                Some((ciphertext.to_vec(), shared_secret.into()))
+            }
+
+            /// Decapsulates `ciphertext` with the key derived from `seed`.
+            pub fn decapsulate(ciphertext: &[u8], seed: &[u8]) -> Option<[u8; 32]> {
+                let seed: [u8; SEED_BYTES] = seed.try_into().ok()?;
+                let dk = DecapsulationKey::<$kem>::from_seed(Array::from(seed));
+                Some(dk.decapsulate(ciphertext.try_into().ok()?).into())
+            }
+        }
+    };
+}
+
+parameter_set!(ml_kem_512, ml_kem::MlKem512);
+parameter_set!(ml_kem_768, ml_kem::MlKem768);
+parameter_set!(ml_kem_1024, ml_kem::MlKem1024);
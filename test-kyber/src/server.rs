@@ -1,24 +1,40 @@
-use std::{
-    io::{Read, Write},
-    net::TcpListener,
-};
+use std::{net::TcpListener, process};
 
-use pqc_kyber::{decapsulate, keypair};
+use test_kyber::{serve, ParamSet};
 fn main() {
+    //`--backend ml-kem` serves the FIPS 203 set of the same strength as the compiled Kyber
+    //set, or the one `--param-set` names; every ML-KEM set is available in every build
+    let args: Vec<String> = std::env::args().collect();
+    let param_set = match args.get(1..).unwrap_or_default() {
+        [] => Some(ParamSet::compiled()),
+        [flag, backend] if flag == "--backend" && backend == "kyber" => Some(ParamSet::compiled()),
+        [flag, backend] if flag == "--backend" && backend == "ml-kem" => {
+            Some(ParamSet::compiled().ml_kem())
+        }
+        [flag, backend, size_flag, size]
+            if flag == "--backend" && backend == "ml-kem" && size_flag == "--param-set" =>
+        {
+            ParamSet::ml_kem_of_size(size)
+        }
+        _ => None,
+    };
+    let Some(param_set) = param_set else {
+        println!(
+            "Usage: {} [--backend <kyber|ml-kem>] [--param-set <512|768|1024>]",
+            args[0]
+        );
+        println!("--param-set picks the ML-KEM set and needs --backend ml-kem");
+        process::exit(1);
+    };
     let listener = TcpListener::bind("127.0.0.1:8080").unwrap();
+    println!("Listening with {}", param_set);
     for stream in listener.incoming() {
-        let mut stream = stream.unwrap();
-        //Create a Random number generator
-        let mut rng = rand::thread_rng();
-        //Generate a keypair for server
-        let server_keys = keypair(&mut rng).unwrap();
-        //Send public key to client
-        stream.write_all(&server_keys.public).unwrap();
-        //Receive Client Ciphertext
-        let mut ciphertext = [0u8; 1088];
-        stream.read_exact(&mut ciphertext).unwrap();
-        //Decapsulate shared secret
-        let shared_secret_server = decapsulate(&ciphertext, &server_keys.secret).unwrap();
-        println!("shared_secret_server: {:?}", shared_secret_server);
+        //A client that hangs up mid-handshake must not take the server down
+        match serve(&mut stream.unwrap(), param_set) {
+            Ok(shared_secret_server) => {
+                println!("shared_secret_server: {:?}", shared_secret_server)
+            }
+            Err(e) => println!("Handshake failed: {}", e),
+        }
     }
 }
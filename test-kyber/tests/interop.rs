@@ -0,0 +1,97 @@
+use std::io::{self, Write};
+use std::net::{TcpListener, TcpStream};
+use std::process::Command;
+use std::thread;
+
+use test_kyber::{connect, serve, ParamSet};
+
+//Runs the server side on a loopback socket and the client side on this thread
+fn handshake(param_set: ParamSet) -> ([u8; 32], io::Result<(ParamSet, [u8; 32])>) {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let address = listener.local_addr().unwrap();
+    let server = thread::spawn(move || {
+        let (mut stream, _) = listener.accept().unwrap();
+        serve(&mut stream, param_set).unwrap()
+    });
+    let mut stream = TcpStream::connect(address).unwrap();
+    let client = connect(&mut stream);
+    (server.join().unwrap(), client)
+}
+
+#[test]
+fn peers_agree_on_shared_secret_for_every_supported_set() {
+    for param_set in [
+        ParamSet::compiled(),
+        ParamSet::MlKem512,
+        ParamSet::MlKem768,
+        ParamSet::MlKem1024,
+    ] {
+        let (server_secret, client) = handshake(param_set);
+        let (negotiated, client_secret) = client.unwrap();
+        assert_eq!(negotiated, param_set);
+        assert_eq!(server_secret, client_secret, "{}", param_set);
+    }
+}
+
+#[test]
+fn ml_kem_keys_and_ciphertexts_keep_round3_sizes() {
+    let mut rng = rand::thread_rng();
+    for param_set in [ParamSet::MlKem512, ParamSet::MlKem768, ParamSet::MlKem1024] {
+        let (public_key, secret_key) = param_set.keypair(&mut rng);
+        assert_eq!(public_key.len(), param_set.public_key_bytes());
+        let (ciphertext, shared_secret) = param_set.encapsulate(&public_key, &mut rng).unwrap();
+        assert_eq!(ciphertext.len(), param_set.ciphertext_bytes());
+        assert_eq!(
+            param_set.decapsulate(&ciphertext, &secret_key),
+            Some(shared_secret)
+        );
+    }
+}
+
+#[test]
+fn ml_kem_sets_are_picked_at_run_time() {
+    for (size, param_set) in [
+        ("512", ParamSet::MlKem512),
+        ("768", ParamSet::MlKem768),
+        ("1024", ParamSet::MlKem1024),
+    ] {
+        assert_eq!(ParamSet::ml_kem_of_size(size), Some(param_set));
+        let (server_secret, client) = handshake(param_set);
+        assert_eq!(client.unwrap(), (param_set, server_secret));
+    }
+
+    //The server refuses a size it does not know, or one given without the ML-KEM backend
+    for args in [
+        &["--backend", "ml-kem", "--param-set", "2048"][..],
+        &["--backend", "kyber", "--param-set", "1024"],
+        &["--param-set", "1024"],
+    ] {
+        let output = Command::new(env!("CARGO_BIN_EXE_server"))
+            .args(args)
+            .output()
+            .unwrap();
+        assert!(!output.status.success(), "{:?}", args);
+        assert!(String::from_utf8_lossy(&output.stdout).contains("--param-set"));
+    }
+}
+
+#[test]
+fn unsupported_and_unknown_sets_are_rejected() {
+    //A round-3 set other than the compiled one cannot be served by this build
+    let foreign = match ParamSet::compiled() {
+        ParamSet::Kyber512 => ParamSet::Kyber1024,
+        _ => ParamSet::Kyber512,
+    };
+    for id in [foreign.id(), 0x7f] {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        let server = thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            stream.write_all(&[id]).unwrap();
+        });
+        let mut stream = TcpStream::connect(address).unwrap();
+        let error = connect(&mut stream).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+        server.join().unwrap();
+    }
+}
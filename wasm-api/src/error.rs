@@ -0,0 +1,261 @@
+use std::fmt;
+use std::io;
+
+use rocket::http::Status;
+use rocket::response::{self, Responder, Response};
+use rocket::serde::{json::Json, Serialize};
+use rocket::Request;
+
+use crate::level::SecurityLevel;
+
+/// Everything that can go wrong while serving a request. Each variant maps to
+/// an HTTP status and a stable `code` in the JSON error body.
+#[derive(Debug)]
+pub enum ApiError {
+    /// A request field is not valid base64.
+    InvalidBase64 {
+        field: &'static str,
+        source: base64::DecodeError,
+    },
+    /// A key, signature or ciphertext has the wrong size for its parameter set.
+    InvalidLength {
+        field: &'static str,
+        expected: usize,
+        actual: usize,
+    },
+    /// A public key is neither raw nor a SubjectPublicKeyInfo of a supported level.
+    InvalidKey { field: &'static str, reason: String },
+    /// A context string is longer than the 255 bytes FIPS 204 allows.
+    ContextTooLong { actual: usize },
+    /// A request header is missing or malformed.
+    InvalidHeader { name: &'static str, reason: String },
+    /// A raw request body could not be read.
+    Body(io::Error),
+    /// A raw request body exceeds the configured limit.
+    PayloadTooLarge { limit: u64 },
+    /// No key with this id exists in the key store.
+    UnknownKey(String),
+    /// The requested level differs from the level of the selected key.
+    LevelMismatch {
+        key: SecurityLevel,
+        requested: SecurityLevel,
+    },
+    /// Contexts and prehashing need an ML-DSA level, see
+    /// `signing_formats::framing`.
+    UnsupportedLevel {
+        level: SecurityLevel,
+        what: &'static str,
+    },
+    /// The guest refused its input and returned a null pointer.
+    GuestRejected(&'static str),
+    /// The guest trapped while running an export.
+    GuestTrap(anyhow::Error),
+    /// The guest could not be instantiated or is missing an export.
+    Guest(anyhow::Error),
+    /// The key store could not be read or written.
+    KeyStore(io::Error),
+    /// An uploaded module is not signed by an allow-listed key.
+    UntrustedModule(String),
+    /// An uploaded module could not be compiled or linked.
+    InvalidModule(anyhow::Error),
+    /// Storing an uploaded module would exceed the configured limits.
+    ModuleStoreFull {
+        max_modules: usize,
+        max_bytes: usize,
+    },
+    /// No uploaded module with this id exists.
+    UnknownModule(String),
+    /// The module has no function export with this name.
+    UnknownExport(String),
+    /// Arguments that do not fit the export's signature, or an export taking
+    /// or returning values JSON cannot carry.
+    InvalidInvocation(String),
+}
+
+/// JSON body returned for every error response.
+#[derive(Serialize)]
+#[serde(crate = "rocket::serde")]
+pub struct ErrorBody {
+    pub code: &'static str,
+    pub message: String,
+    pub detail: Option<String>,
+}
+
+impl ApiError {
+    pub fn status(&self) -> Status {
+        match self {
+            ApiError::InvalidBase64 { .. }
+            | ApiError::InvalidLength { .. }
+            | ApiError::InvalidKey { .. }
+            | ApiError::ContextTooLong { .. }
+            | ApiError::InvalidHeader { .. }
+            | ApiError::Body(_)
+            | ApiError::LevelMismatch { .. }
+            | ApiError::UnsupportedLevel { .. }
+            | ApiError::GuestRejected(_)
+            | ApiError::InvalidModule(_)
+            | ApiError::InvalidInvocation(_) => Status::BadRequest,
+            ApiError::UntrustedModule(_) => Status::Forbidden,
+            ApiError::PayloadTooLarge { .. } => Status::PayloadTooLarge,
+            ApiError::ModuleStoreFull { .. } => Status::InsufficientStorage,
+            ApiError::UnknownKey(_) | ApiError::UnknownModule(_) | ApiError::UnknownExport(_) => {
+                Status::NotFound
+            }
+            ApiError::GuestTrap(_) | ApiError::Guest(_) | ApiError::KeyStore(_) => {
+                Status::InternalServerError
+            }
+        }
+    }
+
+    pub fn code(&self) -> &'static str {
+        match self {
+            ApiError::InvalidBase64 { .. } => "invalid_base64",
+            ApiError::InvalidLength { .. } => "invalid_length",
+            ApiError::InvalidKey { .. } => "invalid_key",
+            ApiError::ContextTooLong { .. } => "context_too_long",
+            ApiError::InvalidHeader { .. } => "invalid_header",
+            ApiError::Body(_) => "invalid_body",
+            ApiError::PayloadTooLarge { .. } => "payload_too_large",
+            ApiError::UnknownKey(_) => "unknown_key",
+            ApiError::LevelMismatch { .. } => "level_mismatch",
+            ApiError::UnsupportedLevel { .. } => "unsupported_level",
+            ApiError::GuestRejected(_) => "guest_rejected",
+            ApiError::GuestTrap(_) => "guest_trap",
+            ApiError::Guest(_) => "guest_error",
+            ApiError::KeyStore(_) => "key_store",
+            ApiError::UntrustedModule(_) => "untrusted_module",
+            ApiError::InvalidModule(_) => "invalid_module",
+            ApiError::ModuleStoreFull { .. } => "module_store_full",
+            ApiError::UnknownModule(_) => "unknown_module",
+            ApiError::UnknownExport(_) => "unknown_export",
+            ApiError::InvalidInvocation(_) => "invalid_invocation",
+        }
+    }
+
+    fn detail(&self) -> Option<String> {
+        match self {
+            ApiError::InvalidBase64 { source, .. } => Some(source.to_string()),
+            ApiError::InvalidHeader { reason, .. }
+            | ApiError::InvalidKey { reason, .. }
+            | ApiError::UntrustedModule(reason)
+            | ApiError::InvalidInvocation(reason) => Some(reason.clone()),
+            ApiError::GuestTrap(e) | ApiError::Guest(e) | ApiError::InvalidModule(e) => {
+                Some(format!("{:#}", e))
+            }
+            ApiError::Body(e) | ApiError::KeyStore(e) => Some(e.to_string()),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for ApiError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ApiError::InvalidBase64 { field, .. } => write!(f, "`{}` is not valid base64", field),
+            ApiError::InvalidLength {
+                field,
+                expected,
+                actual,
+            } => write!(f, "`{}` must be {} bytes, got {}", field, expected, actual),
+            ApiError::InvalidKey { field, .. } => {
+                write!(f, "`{}` is not a supported public key", field)
+            }
+            ApiError::ContextTooLong { actual } => {
+                write!(f, "`context` must be at most 255 bytes, got {}", actual)
+            }
+            ApiError::InvalidHeader { name, .. } => write!(f, "invalid `{}` header", name),
+            ApiError::Body(_) => f.write_str("request body could not be read"),
+            ApiError::PayloadTooLarge { limit } => {
+                write!(f, "request body exceeds the limit of {} bytes", limit)
+            }
+            ApiError::UnknownKey(id) => write!(f, "no key with id `{}`", id),
+            ApiError::LevelMismatch { key, requested } => {
+                write!(f, "key is {} but {} was requested", key, requested)
+            }
+            ApiError::UnsupportedLevel { level, what } => {
+                write!(f, "{} requires an ML-DSA level, got {}", what, level)
+            }
+            ApiError::GuestRejected(what) => write!(f, "guest module rejected the {}", what),
+            ApiError::GuestTrap(_) => f.write_str("guest module trapped"),
+            ApiError::Guest(_) => f.write_str("guest module is unavailable"),
+            ApiError::KeyStore(_) => f.write_str("key store is unavailable"),
+            ApiError::UntrustedModule(_) => {
+                f.write_str("module is not signed by an allow-listed key")
+            }
+            ApiError::InvalidModule(_) => f.write_str("module could not be compiled"),
+            ApiError::ModuleStoreFull {
+                max_modules,
+                max_bytes,
+            } => write!(
+                f,
+                "module store is full ({} modules or {} bytes at most)",
+                max_modules, max_bytes
+            ),
+            ApiError::UnknownModule(id) => write!(f, "no module with id `{}`", id),
+            ApiError::UnknownExport(name) => write!(f, "module has no function export `{}`", name),
+            ApiError::InvalidInvocation(_) => {
+                write!(f, "export cannot be called with these arguments")
+            }
+        }
+    }
+}
+
+impl std::error::Error for ApiError {}
+
+impl<'r> Responder<'r, 'static> for ApiError {
+    fn respond_to(self, request: &'r Request<'_>) -> response::Result<'static> {
+        let status = self.status();
+        if status.code >= 500 {
+            error!("{}: {}", self, self.detail().unwrap_or_default());
+        }
+        let body = ErrorBody {
+            code: self.code(),
+            message: self.to_string(),
+            detail: self.detail(),
+        };
+        Response::build_from(Json(body).respond_to(request)?)
+            .status(status)
+            .ok()
+    }
+}
+
+//Keeps Rocket's own failures (unparsable JSON, unknown routes, ...) in the same shape
+#[catch(default)]
+pub fn default_catcher(status: Status, _request: &Request) -> (Status, Json<ErrorBody>) {
+    let reason = status.reason().unwrap_or("Error");
+    let body = ErrorBody {
+        code: match status.code {
+            400 => "bad_request",
+            404 => "not_found",
+            413 => "payload_too_large",
+            415 => "unsupported_media_type",
+            422 => "unprocessable_entity",
+            _ if status.code >= 500 => "internal_error",
+            _ => "error",
+        },
+        message: reason.to_string(),
+        detail: None,
+    };
+    (status, Json(body))
+}
+
+/// Decodes a base64 request field, naming the field on failure.
+pub fn decode_base64(field: &'static str, encoded: &str) -> Result<Vec<u8>, ApiError> {
+    use base64::{engine::general_purpose, Engine as _};
+    general_purpose::STANDARD
+        .decode(encoded)
+        .map_err(|source| ApiError::InvalidBase64 { field, source })
+}
+
+/// Checks that a decoded field has exactly `expected` bytes.
+pub fn expect_length(field: &'static str, bytes: &[u8], expected: usize) -> Result<(), ApiError> {
+    if bytes.len() == expected {
+        Ok(())
+    } else {
+        Err(ApiError::InvalidLength {
+            field,
+            expected,
+            actual: bytes.len(),
+        })
+    }
+}
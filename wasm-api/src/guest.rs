@@ -0,0 +1,494 @@
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use rocket::serde::{Deserialize, Serialize};
+use wasmtime::*;
+use wasmtime_wasi::{WasiCtx, WasiCtxBuilder};
+
+use crate::error::ApiError;
+use crate::level::SecurityLevel;
+use crate::module_signature::{self, TrustedKey};
+
+//Compiled Kyber768 KEM module
+const KEM_MODULE_PATH: &str = "kyber_kem_module.wasm.multivalue.wasm";
+
+//Number of guest modules loaded by `Guests::load`
+const GUEST_COUNT: u32 = SecurityLevel::ALL.len() as u32 + 1;
+
+//How often the engine's epoch advances; deadlines are counted in these ticks
+const EPOCH_TICK: Duration = Duration::from_millis(10);
+
+/// Instance allocation settings, read from Rocket.toml or `ROCKET_*`
+/// environment variables.
+#[derive(Deserialize)]
+#[serde(crate = "rocket::serde")]
+pub struct PoolConfig {
+    /// Instance slots reserved by the pooling allocator, shared by the
+    /// service's own guest modules.
+    #[serde(default = "default_pool_size")]
+    pub pool_size: u32,
+    /// Maximum linear memory of each slot, in 64 KiB wasm pages.
+    #[serde(default = "default_max_memory_pages")]
+    pub max_memory_pages: u64,
+    /// Idle instances kept per module for reuse; 0 instantiates per request.
+    /// Only instances that never held secrets are kept: the guest's working
+    /// copies of keys and shared secrets are freed but not wiped.
+    #[serde(default)]
+    pub warm_instances: usize,
+    /// Instance slots of the separate pool uploaded modules are instantiated
+    /// from, so uploads can never take the slots of the service's own guests.
+    #[serde(default = "default_upload_pool_size")]
+    pub upload_pool_size: u32,
+}
+
+fn default_pool_size() -> u32 {
+    128
+}
+
+fn default_max_memory_pages() -> u64 {
+    1024
+}
+
+fn default_upload_pool_size() -> u32 {
+    16
+}
+
+/// Warm-pool counters for one module, as reported by `GET /metrics`.
+#[derive(Serialize)]
+#[serde(crate = "rocket::serde")]
+pub struct PoolMetrics {
+    /// Requests served by a previously used instance.
+    pub hits: u64,
+    /// Requests that had to instantiate the module.
+    pub misses: u64,
+    /// Instances currently waiting in the warm pool.
+    pub idle: usize,
+}
+
+/// Host state of every guest store.
+pub struct GuestState {
+    wasi: WasiCtx,
+    //Set once an export traps or guest memory cannot be accessed; such an
+    //instance never goes back to the warm pool
+    poisoned: bool,
+}
+
+pub type GuestStore = Store<GuestState>;
+
+/// A guest module compiled and linked against WASI once, so that serving a
+/// request only costs a new store and an instantiation, or nothing at all when
+/// a warm instance is available.
+pub struct Guest {
+    engine: Engine,
+    instance_pre: InstancePre<GuestState>,
+    warm: Mutex<Vec<(GuestStore, Instance)>>,
+    warm_capacity: usize,
+    //Epoch ticks a store may run for before it traps; the service's own
+    //guests have none
+    deadline: Option<u64>,
+    //Whether instances share the service's stdio and arguments; uploaded
+    //modules get an empty WASI context instead
+    inherit_stdio: bool,
+    hits: AtomicU64,
+    misses: AtomicU64,
+}
+
+impl Guest {
+    /// Compiles the module at `path`, once its signature by `trusted_key` has
+    /// been checked, and pre-links its WASI imports.
+    pub fn load(
+        engine: &Engine,
+        path: &str,
+        trusted_key: &TrustedKey,
+        warm_capacity: usize,
+    ) -> anyhow::Result<Guest> {
+        let wasm = module_signature::read_verified(path, trusted_key)?;
+        Guest::compile(engine, &wasm, warm_capacity, None, true)
+    }
+
+    /// Compiles a module whose signature has already been checked and
+    /// pre-links its WASI imports. Stores of a module with a `deadline` trap
+    /// once they have run for that many epoch ticks (the engine must have
+    /// epoch interruption enabled), and only instances of a module with
+    /// `inherit_stdio` see the service's stdio and arguments.
+    pub fn compile(
+        engine: &Engine,
+        wasm: &[u8],
+        warm_capacity: usize,
+        deadline: Option<u64>,
+        inherit_stdio: bool,
+    ) -> anyhow::Result<Guest> {
+        //A Module is a compiled in-memory representation of an input WebAssembly binary.
+        let module = Module::new(engine, wasm)?;
+
+        //Structure used to link wasm modules/instances together.
+        let mut linker = Linker::new(engine);
+        wasmtime_wasi::add_to_linker(&mut linker, |s: &mut GuestState| &mut s.wasi)?;
+
+        //Type-checks the imports up front so instantiation can skip it
+        let instance_pre = linker.instantiate_pre(&module)?;
+        Ok(Guest {
+            engine: engine.clone(),
+            instance_pre,
+            warm: Mutex::new(Vec::with_capacity(warm_capacity)),
+            warm_capacity,
+            deadline,
+            inherit_stdio,
+            hits: AtomicU64::new(0),
+            misses: AtomicU64::new(0),
+        })
+    }
+
+    /// Hands out a warm instance if one is idle, otherwise instantiates the
+    /// module in a fresh store with its own WASI context.
+    pub fn instantiate(&self) -> Result<Lease<'_>, ApiError> {
+        let warm = self.warm.lock().unwrap().pop();
+        let (store, instance) = match warm {
+            Some(warm) => {
+                self.hits.fetch_add(1, Ordering::Relaxed);
+                warm
+            }
+            None => {
+                self.misses.fetch_add(1, Ordering::Relaxed);
+                self.instantiate_fresh().map_err(ApiError::Guest)?
+            }
+        };
+        Ok(Lease {
+            guest: self,
+            store: Some(store),
+            instance,
+            served: false,
+            held_secrets: false,
+        })
+    }
+
+    /// Names of the functions the module exports.
+    pub fn function_exports(&self) -> Vec<String> {
+        self.instance_pre
+            .module()
+            .exports()
+            .filter(|export| matches!(export.ty(), ExternType::Func(_)))
+            .map(|export| export.name().to_string())
+            .collect()
+    }
+
+    pub fn metrics(&self) -> PoolMetrics {
+        PoolMetrics {
+            hits: self.hits.load(Ordering::Relaxed),
+            misses: self.misses.load(Ordering::Relaxed),
+            idle: self.warm.lock().unwrap().len(),
+        }
+    }
+
+    fn instantiate_fresh(&self) -> anyhow::Result<(GuestStore, Instance)> {
+        //WasiContext; an empty one grants no stdio, arguments, environment or directories
+        let mut wasi = WasiCtxBuilder::new();
+        if self.inherit_stdio {
+            wasi.inherit_stdio().inherit_args()?;
+        }
+        let wasi = wasi.build();
+
+        //A Store is a collection of WebAssembly instances and host-defined state.
+        //All WebAssembly instances and items will be attached to and refer to a Store.
+        //For example instances, functions, globals, and tables are all attached to a Store.
+        let mut store = Store::new(
+            &self.engine,
+            GuestState {
+                wasi,
+                poisoned: false,
+            },
+        );
+
+        //The deadline also covers the start function
+        if let Some(ticks) = self.deadline {
+            store.set_epoch_deadline(ticks);
+        }
+
+        //An instantiated WebAssembly module.
+        let instance = self.instance_pre.instantiate(&mut store)?;
+        Ok((store, instance))
+    }
+}
+
+#[cfg(test)]
+impl Guest {
+    /// Copies of the linear memory of the idle warm instances.
+    pub fn idle_memories(&self) -> Vec<Vec<u8>> {
+        self.warm
+            .lock()
+            .unwrap()
+            .iter_mut()
+            .map(|(store, instance)| {
+                let memory = instance.get_memory(&mut *store, "memory").unwrap();
+                memory.data(&*store).to_vec()
+            })
+            .collect()
+    }
+}
+
+/// An instance borrowed from a [`Guest`]. Dropping a lease that served its
+/// request returns the instance to the warm pool, unless the pool is full, the
+/// guest trapped (a trapped guest may have left its heap inconsistent) or the
+/// instance held secrets.
+pub struct Lease<'a> {
+    guest: &'a Guest,
+    store: Option<GuestStore>,
+    instance: Instance,
+    served: bool,
+    held_secrets: bool,
+}
+
+impl Lease<'_> {
+    pub fn parts(&mut self) -> (&mut GuestStore, Instance) {
+        (self.store.as_mut().unwrap(), self.instance)
+    }
+
+    /// Marks the request as served. A lease dropped without it, such as on an
+    /// early return with an error, discards its instance, which may still
+    /// hold blocks allocated for the request.
+    pub fn served(&mut self) {
+        self.served = true;
+    }
+
+    /// Marks the instance as holding secret keys or shared secrets. Blocks
+    /// the host reads back are wiped, but the guest's own working copies are
+    /// only freed and would outlive the request in a warm instance, so such an
+    /// instance is discarded with its memory instead.
+    pub fn held_secrets(&mut self) {
+        self.held_secrets = true;
+    }
+}
+
+impl Drop for Lease<'_> {
+    fn drop(&mut self) {
+        let store = self.store.take().unwrap();
+        if !self.served || self.held_secrets || store.data().poisoned || std::thread::panicking() {
+            return;
+        }
+        let mut warm = self.guest.warm.lock().unwrap();
+        if warm.len() < self.guest.warm_capacity {
+            warm.push((store, self.instance));
+        }
+    }
+}
+
+/// Every guest module the service drives, compiled by a single shared engine
+/// when Rocket ignites and kept in managed state. Uploaded modules are
+/// compiled by an engine of their own, whose instance pool is separate.
+pub struct Guests {
+    //One signing module per `SecurityLevel`, in `SecurityLevel::ALL` order
+    signing: Vec<Guest>,
+    kyber: Guest,
+    uploads: Engine,
+    //Stops with the guests
+    _ticker: EpochTicker,
+}
+
+impl Guests {
+    pub fn load(config: &PoolConfig, trusted_key: &TrustedKey) -> anyhow::Result<Guests> {
+        //Warm instances hold on to their slots, so they must leave room for fresh ones
+        if config.warm_instances as u64 * GUEST_COUNT as u64 >= config.pool_size as u64 {
+            anyhow::bail!(
+                "`pool_size` ({}) must exceed `warm_instances` ({}) times the {} guest modules",
+                config.pool_size,
+                config.warm_instances,
+                GUEST_COUNT
+            );
+        }
+
+        // Engines store global configuration preferences such as compilation settings, enabled features, etc.
+        let engine = pooled_engine(config.pool_size, config.max_memory_pages, false)?;
+        //Lets uploaded modules be stopped at their deadline
+        let uploads = pooled_engine(config.upload_pool_size, config.max_memory_pages, true)?;
+        let load = |path: &str| {
+            Guest::load(&engine, path, trusted_key, config.warm_instances)
+                .map_err(|e| e.context(format!("failed to load guest module `{}`", path)))
+        };
+        Ok(Guests {
+            signing: SecurityLevel::ALL
+                .iter()
+                .map(|level| load(level.module_path()))
+                .collect::<anyhow::Result<_>>()?,
+            kyber: load(KEM_MODULE_PATH)?,
+            uploads: uploads.clone(),
+            _ticker: EpochTicker::start(uploads),
+        })
+    }
+
+    /// The signing module built for `level`.
+    pub fn signing(&self, level: SecurityLevel) -> &Guest {
+        let index = SecurityLevel::ALL.iter().position(|l| *l == level).unwrap();
+        &self.signing[index]
+    }
+
+    /// The Kyber KEM module.
+    pub fn kem(&self) -> &Guest {
+        &self.kyber
+    }
+
+    /// Compiles an uploaded module with the uploads' engine. Uploaded modules
+    /// are instantiated per request, so they never hold on to pool slots, get
+    /// an empty WASI context and trap once an instance has run for `timeout`.
+    pub fn compile(&self, wasm: &[u8], timeout: Duration) -> anyhow::Result<Guest> {
+        let ticks = timeout.as_millis().div_ceil(EPOCH_TICK.as_millis()).max(1);
+        Guest::compile(&self.uploads, wasm, 0, Some(ticks as u64), false)
+    }
+
+    /// Warm-pool counters keyed by module name.
+    pub fn metrics(&self) -> BTreeMap<&'static str, PoolMetrics> {
+        let mut metrics: BTreeMap<_, _> = SecurityLevel::ALL
+            .iter()
+            .zip(&self.signing)
+            .map(|(level, guest)| (level.metrics_name(), guest.metrics()))
+            .collect();
+        metrics.insert("kyber", self.kyber.metrics());
+        metrics
+    }
+}
+
+//An engine whose instances are carved out of `slots` slots reserved up front
+//instead of being mmapped per request
+fn pooled_engine(
+    slots: u32,
+    memory_pages: u64,
+    epoch_interruption: bool,
+) -> anyhow::Result<Engine> {
+    let mut pooling = PoolingAllocationConfig::default();
+    pooling
+        .total_core_instances(slots)
+        .total_memories(slots)
+        .total_tables(slots)
+        .memory_pages(memory_pages);
+    let mut engine_config = Config::new();
+    engine_config.allocation_strategy(InstanceAllocationStrategy::Pooling(pooling));
+    engine_config.epoch_interruption(epoch_interruption);
+    Engine::new(&engine_config)
+}
+
+//Advances the engine's epoch every `EPOCH_TICK` until the guests are dropped
+struct EpochTicker {
+    stop: Arc<AtomicBool>,
+}
+
+impl EpochTicker {
+    fn start(engine: Engine) -> EpochTicker {
+        let stop = Arc::new(AtomicBool::new(false));
+        let stopped = stop.clone();
+        std::thread::spawn(move || {
+            while !stopped.load(Ordering::Relaxed) {
+                std::thread::sleep(EPOCH_TICK);
+                engine.increment_epoch();
+            }
+        });
+        EpochTicker { stop }
+    }
+}
+
+impl Drop for EpochTicker {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+    }
+}
+
+/// Looks up a typed export of `instance`.
+pub fn export<Params, Results>(
+    store: &mut GuestStore,
+    instance: &Instance,
+    name: &str,
+) -> Result<TypedFunc<Params, Results>, ApiError>
+where
+    Params: WasmParams,
+    Results: WasmResults,
+{
+    instance
+        .get_typed_func::<Params, Results>(&mut *store, name)
+        .map_err(|e| ApiError::Guest(e.context(format!("export `{}`", name))))
+}
+
+/// Calls a guest export, poisoning the store if it traps.
+pub fn call<Params, Results>(
+    store: &mut GuestStore,
+    func: &TypedFunc<Params, Results>,
+    params: Params,
+) -> Result<Results, ApiError>
+where
+    Params: WasmParams,
+    Results: WasmResults,
+{
+    func.call(&mut *store, params).map_err(|e| {
+        store.data_mut().poisoned = true;
+        ApiError::GuestTrap(e)
+    })
+}
+
+/// Calls an export whose signature is only known at runtime, poisoning the
+/// store if it traps.
+pub fn call_dynamic(
+    store: &mut GuestStore,
+    func: &Func,
+    params: &[Val],
+    results: &mut [Val],
+) -> Result<(), ApiError> {
+    func.call(&mut *store, params, results).map_err(|e| {
+        store.data_mut().poisoned = true;
+        ApiError::GuestTrap(e)
+    })
+}
+
+/// Turns the null pointer an export returns for malformed input into an error.
+pub fn non_null(ptr: i32, what: &'static str) -> Result<i32, ApiError> {
+    if ptr == 0 {
+        Err(ApiError::GuestRejected(what))
+    } else {
+        Ok(ptr)
+    }
+}
+
+//Allocates a block in guest memory with the `alloc` export and copies `bytes`
+//into it, poisoning the store if the block cannot be written
+pub fn write_to_guest(
+    store: &mut GuestStore,
+    instance: &Instance,
+    bytes: &[u8],
+) -> Result<i32, ApiError> {
+    let memory = memory(store, instance)?;
+    let alloc_fn = export::<i32, i32>(store, instance, "alloc")?;
+    let ptr = call(store, &alloc_fn, bytes.len() as i32)?;
+    memory
+        .write(&mut *store, ptr as usize, bytes)
+        .map_err(|e| {
+            store.data_mut().poisoned = true;
+            ApiError::GuestTrap(e.into())
+        })?;
+    Ok(ptr)
+}
+
+//Copies `len` bytes out of guest memory, wipes the block and releases it with
+//the `dealloc` export, poisoning the store if the block cannot be accessed
+pub fn read_from_guest(
+    store: &mut GuestStore,
+    instance: &Instance,
+    ptr: i32,
+    len: usize,
+) -> Result<Vec<u8>, ApiError> {
+    let memory = memory(store, instance)?;
+    let dealloc_fn = export::<(i32, i32), ()>(store, instance, "dealloc")?;
+    let mut bytes = vec![0u8; len];
+    memory
+        .read(&*store, ptr as usize, &mut bytes)
+        .and_then(|()| memory.write(&mut *store, ptr as usize, &vec![0u8; len]))
+        .map_err(|e| {
+            store.data_mut().poisoned = true;
+            ApiError::GuestTrap(e.into())
+        })?;
+    call(store, &dealloc_fn, (ptr, len as i32))?;
+    Ok(bytes)
+}
+
+fn memory(store: &mut GuestStore, instance: &Instance) -> Result<Memory, ApiError> {
+    instance
+        .get_memory(&mut *store, "memory")
+        .ok_or_else(|| ApiError::Guest(anyhow::format_err!("failed to find `memory` export")))
+}
@@ -0,0 +1,142 @@
+//! `/kem` routes, backed by the KEM guest. The guest is kyber-kem-module built
+//! with pqc_kyber's default parameter set, so the sizes below are Kyber768's
+//! and the routes serve Kyber768 only; another set needs the guest built with
+//! `kyber512` or `kyber1024` and these sizes changed to match.
+
+use base64::{engine::general_purpose, Engine as _};
+use rocket::serde::{json::Json, Deserialize, Serialize};
+use rocket::{Route, State};
+
+use crate::error::{decode_base64, expect_length, ApiError};
+use crate::guest::{call, export, non_null, read_from_guest, write_to_guest, Guests};
+
+//Sizes of the buffers the Kyber768 KEM module hands back and takes
+const PUBLIC_KEY_BYTES: usize = 1184;
+const SECRET_KEY_BYTES: usize = 2400;
+const CIPHERTEXT_BYTES: usize = 1088;
+const SHARED_SECRET_BYTES: usize = 32;
+
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "rocket::serde")]
+struct KeypairResponse {
+    public_key: String,
+    secret_key: String,
+}
+
+#[derive(Deserialize)]
+#[serde(crate = "rocket::serde")]
+struct EncapsulateRequest<'a> {
+    public_key: &'a str,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "rocket::serde")]
+struct EncapsulateResponse {
+    ciphertext: String,
+    shared_secret: String,
+}
+
+#[derive(Deserialize)]
+#[serde(crate = "rocket::serde")]
+struct DecapsulateRequest<'a> {
+    ciphertext: &'a str,
+    secret_key: &'a str,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "rocket::serde")]
+struct DecapsulateResponse {
+    shared_secret: String,
+}
+
+pub fn routes() -> Vec<Route> {
+    routes![keypair, encapsulate, decapsulate]
+}
+
+#[post("/keypair")]
+fn keypair(guests: &State<Guests>) -> Result<Json<KeypairResponse>, ApiError> {
+    let mut lease = guests.kem().instantiate()?;
+    lease.held_secrets();
+    let (store, instance) = lease.parts();
+    let keypair_fn = export::<(), (i32, i32)>(store, &instance, "keypair")?;
+
+    //Calling keypair returns public key and secret key pointers; null means the RNG failed
+    let (pk_ptr, sk_ptr) = call(store, &keypair_fn, ())?;
+    if pk_ptr == 0 {
+        return Err(ApiError::Guest(anyhow::format_err!(
+            "keypair generation failed"
+        )));
+    }
+    let public_key = read_from_guest(store, &instance, pk_ptr, PUBLIC_KEY_BYTES)?;
+    let secret_key = read_from_guest(store, &instance, sk_ptr, SECRET_KEY_BYTES)?;
+    lease.served();
+
+    Ok(Json(KeypairResponse {
+        public_key: general_purpose::STANDARD.encode(public_key),
+        secret_key: general_purpose::STANDARD.encode(secret_key),
+    }))
+}
+
+#[post("/encapsulate", data = "<request>")]
+fn encapsulate(
+    request: Json<EncapsulateRequest<'_>>,
+    guests: &State<Guests>,
+) -> Result<Json<EncapsulateResponse>, ApiError> {
+    let public_key = decode_base64("public_key", request.public_key)?;
+    expect_length("public_key", &public_key, PUBLIC_KEY_BYTES)?;
+
+    let mut lease = guests.kem().instantiate()?;
+    lease.held_secrets();
+    let (store, instance) = lease.parts();
+    let encapsulate_fn = export::<(i32, i32), (i32, i32)>(store, &instance, "encapsulate")?;
+
+    //Calling encapsulate returns ciphertext and shared secret pointers
+    let pk_ptr = write_to_guest(store, &instance, &public_key)?;
+    let (ct_ptr, ss_ptr) = call(store, &encapsulate_fn, (pk_ptr, public_key.len() as i32))?;
+    let ct_ptr = non_null(ct_ptr, "public key")?;
+    let ciphertext = read_from_guest(store, &instance, ct_ptr, CIPHERTEXT_BYTES)?;
+    let shared_secret = read_from_guest(store, &instance, ss_ptr, SHARED_SECRET_BYTES)?;
+    lease.served();
+
+    Ok(Json(EncapsulateResponse {
+        ciphertext: general_purpose::STANDARD.encode(ciphertext),
+        shared_secret: general_purpose::STANDARD.encode(shared_secret),
+    }))
+}
+
+#[post("/decapsulate", data = "<request>")]
+fn decapsulate(
+    request: Json<DecapsulateRequest<'_>>,
+    guests: &State<Guests>,
+) -> Result<Json<DecapsulateResponse>, ApiError> {
+    let ciphertext = decode_base64("ciphertext", request.ciphertext)?;
+    let secret_key = decode_base64("secret_key", request.secret_key)?;
+    expect_length("ciphertext", &ciphertext, CIPHERTEXT_BYTES)?;
+    expect_length("secret_key", &secret_key, SECRET_KEY_BYTES)?;
+
+    let mut lease = guests.kem().instantiate()?;
+    lease.held_secrets();
+    let (store, instance) = lease.parts();
+    let decapsulate_fn = export::<(i32, i32, i32, i32), i32>(store, &instance, "decapsulate")?;
+
+    //Calling decapsulate returns the shared secret pointer
+    let ct_ptr = write_to_guest(store, &instance, &ciphertext)?;
+    let sk_ptr = write_to_guest(store, &instance, &secret_key)?;
+    let ss_ptr = call(
+        store,
+        &decapsulate_fn,
+        (
+            ct_ptr,
+            ciphertext.len() as i32,
+            sk_ptr,
+            secret_key.len() as i32,
+        ),
+    )?;
+    let ss_ptr = non_null(ss_ptr, "ciphertext or secret key")?;
+    let shared_secret = read_from_guest(store, &instance, ss_ptr, SHARED_SECRET_BYTES)?;
+    lease.served();
+
+    Ok(Json(DecapsulateResponse {
+        shared_secret: general_purpose::STANDARD.encode(shared_secret),
+    }))
+}
@@ -0,0 +1,94 @@
+//! SubjectPublicKeyInfo encodings of public keys, as DER or PEM, so keys
+//! interoperate with OpenSSL 3.5 and other PQ tooling. The raw public key is
+//! the SPKI bit string, without the ML-DSA identifier byte, under the
+//! algorithm OID of its level (see `SecurityLevel::oid`).
+
+use base64::{engine::general_purpose, Engine as _};
+use pkcs8::der::asn1::BitStringRef;
+use pkcs8::der::pem::{self, LineEnding};
+use pkcs8::der::{Decode, Encode};
+use pkcs8::spki::{AlgorithmIdentifierRef, SubjectPublicKeyInfoRef};
+
+use crate::error::{decode_base64, ApiError};
+use crate::level::SecurityLevel;
+
+const PUBLIC_KEY_LABEL: &str = "PUBLIC KEY";
+
+/// Encoding of public keys in responses.
+#[derive(Clone, Copy, Default, FromFormField)]
+pub enum KeyFormat {
+    /// Base64 of the key as the guest encodes it
+    #[default]
+    Raw,
+    /// Base64 of the DER SubjectPublicKeyInfo
+    Der,
+    /// PEM `PUBLIC KEY` block
+    Pem,
+}
+
+pub fn encode_public_key(level: SecurityLevel, public_key: &[u8], format: KeyFormat) -> String {
+    if let KeyFormat::Raw = format {
+        return general_purpose::STANDARD.encode(public_key);
+    }
+    let untagged = match level.tag() {
+        Some(_) => &public_key[1..],
+        None => public_key,
+    };
+    let spki = SubjectPublicKeyInfoRef {
+        algorithm: AlgorithmIdentifierRef {
+            oid: level.oid(),
+            parameters: None,
+        },
+        subject_public_key: BitStringRef::from_bytes(untagged).expect("key fits a bit string"),
+    };
+    let der = spki.to_der().expect("key encodes as DER");
+    match format {
+        KeyFormat::Pem => {
+            pem::encode_string(PUBLIC_KEY_LABEL, LineEnding::LF, &der).expect("key encodes as PEM")
+        }
+        _ => general_purpose::STANDARD.encode(der),
+    }
+}
+
+/// Decodes a public key field given as base64 (of a raw or DER key) or as a
+/// PEM block.
+pub fn decode_public_key(field: &'static str, encoded: &str) -> Result<Vec<u8>, ApiError> {
+    if !encoded.starts_with("-----BEGIN") {
+        return decode_base64(field, encoded);
+    }
+    match pem::decode_vec(encoded.as_bytes()) {
+        Ok((PUBLIC_KEY_LABEL, der)) => Ok(der),
+        Ok((label, _)) => Err(ApiError::InvalidKey {
+            field,
+            reason: format!("expected a PUBLIC KEY block, got {}", label),
+        }),
+        Err(e) => Err(ApiError::InvalidKey {
+            field,
+            reason: e.to_string(),
+        }),
+    }
+}
+
+/// Whether `public_key` is a DER structure rather than a raw key, which is
+/// recognised by its length.
+pub fn is_spki(public_key: &[u8]) -> bool {
+    SecurityLevel::from_public_key(public_key).is_none() && public_key.first() == Some(&0x30)
+}
+
+/// Unwraps a DER SubjectPublicKeyInfo into its level and raw key.
+pub fn from_spki(field: &'static str, der: &[u8]) -> Result<(SecurityLevel, Vec<u8>), ApiError> {
+    let invalid = |reason: String| ApiError::InvalidKey { field, reason };
+    let spki = SubjectPublicKeyInfoRef::from_der(der).map_err(|e| invalid(e.to_string()))?;
+    let oid = spki.algorithm.oid;
+    let level = SecurityLevel::from_oid(oid)
+        .ok_or_else(|| invalid(format!("unsupported algorithm {}", oid)))?;
+    let key = spki
+        .subject_public_key
+        .as_bytes()
+        .ok_or_else(|| invalid("key is not a whole number of bytes".to_string()))?;
+    let public_key: Vec<u8> = level.tag().into_iter().chain(key.iter().copied()).collect();
+    if public_key.len() != level.public_key_bytes() {
+        return Err(invalid(format!("not a {} key", level)));
+    }
+    Ok((level, public_key))
+}
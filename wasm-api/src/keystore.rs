@@ -0,0 +1,201 @@
+use std::fs;
+use std::io::{self, ErrorKind};
+use std::path::PathBuf;
+
+use base64::{engine::general_purpose, Engine as _};
+use chacha20poly1305::aead::rand_core::RngCore;
+use chacha20poly1305::aead::{Aead, AeadCore, KeyInit, OsRng, Payload};
+use chacha20poly1305::{ChaCha20Poly1305, Key, Nonce};
+use rocket::serde::json::serde_json;
+use rocket::serde::{Deserialize, Serialize};
+use zeroize::Zeroizing;
+
+use crate::level::SecurityLevel;
+
+//Records of version 1 bind their level and public key to the sealed secret key
+const RECORD_VERSION: u8 = 1;
+
+/// A signing identity as it is stored on disk. The public key is kept in the
+/// clear so it can be served without decrypting anything; the secret key is
+/// sealed with ChaCha20-Poly1305 under the store's master key, with the key id,
+/// level and public key as associated data, so records cannot be swapped
+/// between files and neither clear field can be changed without unsealing
+/// failing.
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "rocket::serde")]
+struct StoredKey {
+    //Records without a version were sealed with the key id alone
+    #[serde(default)]
+    version: u8,
+    //Records written before levels were selectable are Dilithium3
+    #[serde(default = "legacy_level")]
+    level: SecurityLevel,
+    public_key: String,
+    nonce: String,
+    secret_key: String,
+}
+
+fn legacy_level() -> SecurityLevel {
+    SecurityLevel::Dilithium3
+}
+
+/// A decrypted signing identity. The secret key is wiped when dropped.
+pub struct SigningKey {
+    pub level: SecurityLevel,
+    pub public_key: Vec<u8>,
+    pub secret_key: Zeroizing<Vec<u8>>,
+}
+
+/// File-backed store of Dilithium signing identities, one JSON file per key.
+pub struct KeyStore {
+    dir: PathBuf,
+    cipher: ChaCha20Poly1305,
+}
+
+impl KeyStore {
+    /// Opens (creating if necessary) the key store rooted at `dir`, sealing
+    /// secret keys with the 32-byte `master_key`.
+    pub fn open(dir: impl Into<PathBuf>, master_key: &[u8]) -> io::Result<Self> {
+        if master_key.len() != 32 {
+            return Err(io::Error::new(
+                ErrorKind::InvalidInput,
+                "key store master key must be 32 bytes",
+            ));
+        }
+        let dir = dir.into();
+        fs::create_dir_all(&dir)?;
+        Ok(KeyStore {
+            dir,
+            cipher: ChaCha20Poly1305::new(Key::from_slice(master_key)),
+        })
+    }
+
+    /// Persists a keypair under a newly generated id and returns the id.
+    pub fn insert(
+        &self,
+        level: SecurityLevel,
+        public_key: &[u8],
+        secret_key: &[u8],
+    ) -> io::Result<String> {
+        let mut id_bytes = [0u8; 16];
+        OsRng.fill_bytes(&mut id_bytes);
+        let id = hex::encode(id_bytes);
+
+        let nonce = ChaCha20Poly1305::generate_nonce(&mut OsRng);
+        let sealed = self
+            .cipher
+            .encrypt(
+                &nonce,
+                Payload {
+                    msg: secret_key,
+                    aad: &associated_data(&id, RECORD_VERSION, level, public_key),
+                },
+            )
+            .map_err(|_| io::Error::other("failed to seal secret key"))?;
+
+        let record = StoredKey {
+            version: RECORD_VERSION,
+            level,
+            public_key: general_purpose::STANDARD.encode(public_key),
+            nonce: general_purpose::STANDARD.encode(nonce),
+            secret_key: general_purpose::STANDARD.encode(sealed),
+        };
+        fs::write(self.path(&id), serde_json::to_vec(&record)?)?;
+        Ok(id)
+    }
+
+    /// Returns the level and public key stored under `id`, if any.
+    pub fn public_key(&self, id: &str) -> io::Result<Option<(SecurityLevel, Vec<u8>)>> {
+        match self.load(id)? {
+            Some(record) => Ok(Some((record.level, decode(&record.public_key)?))),
+            None => Ok(None),
+        }
+    }
+
+    /// Returns the decrypted identity stored under `id`, if any.
+    pub fn keypair(&self, id: &str) -> io::Result<Option<SigningKey>> {
+        let record = match self.load(id)? {
+            Some(record) => record,
+            None => return Ok(None),
+        };
+        let public_key = decode(&record.public_key)?;
+        let nonce = decode(&record.nonce)?;
+        if nonce.len() != 12 {
+            return Err(invalid_data("stored nonce has the wrong length"));
+        }
+        if record.version > RECORD_VERSION {
+            return Err(invalid_data("stored key is of an unknown version"));
+        }
+        let secret_key = self
+            .cipher
+            .decrypt(
+                Nonce::from_slice(&nonce),
+                Payload {
+                    msg: &decode(&record.secret_key)?,
+                    aad: &associated_data(id, record.version, record.level, &public_key),
+                },
+            )
+            .map_err(|_| invalid_data("failed to unseal secret key"))?;
+        Ok(Some(SigningKey {
+            level: record.level,
+            public_key,
+            secret_key: Zeroizing::new(secret_key),
+        }))
+    }
+
+    /// Deletes the key stored under `id`, returning whether it existed.
+    pub fn remove(&self, id: &str) -> io::Result<bool> {
+        if !is_valid_id(id) {
+            return Ok(false);
+        }
+        match fs::remove_file(self.path(id)) {
+            Ok(()) => Ok(true),
+            Err(e) if e.kind() == ErrorKind::NotFound => Ok(false),
+            Err(e) => Err(e),
+        }
+    }
+
+    fn load(&self, id: &str) -> io::Result<Option<StoredKey>> {
+        if !is_valid_id(id) {
+            return Ok(None);
+        }
+        match fs::read(self.path(id)) {
+            Ok(bytes) => Ok(Some(serde_json::from_slice(&bytes)?)),
+            Err(e) if e.kind() == ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    fn path(&self, id: &str) -> PathBuf {
+        self.dir.join(format!("{}.json", id))
+    }
+}
+
+//What a record's sealed secret key is bound to: the id, then for current
+//records the level and the public key. Ids have a fixed length, so the fields
+//cannot run into each other.
+fn associated_data(id: &str, version: u8, level: SecurityLevel, public_key: &[u8]) -> Vec<u8> {
+    let mut aad = id.as_bytes().to_vec();
+    if version >= 1 {
+        aad.push(version);
+        aad.push(level.into());
+        aad.extend_from_slice(public_key);
+    }
+    aad
+}
+
+//Ids are generated as 32 lowercase hex characters; anything else is rejected
+//before it gets anywhere near the filesystem.
+fn is_valid_id(id: &str) -> bool {
+    id.len() == 32 && id.bytes().all(|b| matches!(b, b'0'..=b'9' | b'a'..=b'f'))
+}
+
+fn decode(encoded: &str) -> io::Result<Vec<u8>> {
+    general_purpose::STANDARD
+        .decode(encoded)
+        .map_err(|_| invalid_data("stored key is not valid base64"))
+}
+
+fn invalid_data(message: &str) -> io::Error {
+    io::Error::new(ErrorKind::InvalidData, message.to_string())
+}
@@ -0,0 +1,174 @@
+use std::fmt;
+
+use pkcs8::spki::ObjectIdentifier;
+use rocket::serde::{Deserialize, Serialize};
+
+/// Signature algorithm and security level: round-3 Dilithium (2, 3, 5) or
+/// FIPS 204 ML-DSA (44, 65, 87). Each level is served by its own build of the
+/// signing module, so the level also decides which artifact is loaded and how
+/// many bytes to read back for keys and signatures. ML-DSA-65 is the default,
+/// so contexts and prehashing work without choosing a level.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(crate = "rocket::serde", try_from = "u8", into = "u8")]
+pub enum SecurityLevel {
+    Dilithium2,
+    Dilithium3,
+    Dilithium5,
+    MlDsa44,
+    #[default]
+    MlDsa65,
+    MlDsa87,
+}
+
+impl SecurityLevel {
+    pub const ALL: [SecurityLevel; 6] = [
+        SecurityLevel::Dilithium2,
+        SecurityLevel::Dilithium3,
+        SecurityLevel::Dilithium5,
+        SecurityLevel::MlDsa44,
+        SecurityLevel::MlDsa65,
+        SecurityLevel::MlDsa87,
+    ];
+
+    pub fn module_path(self) -> &'static str {
+        match self {
+            SecurityLevel::Dilithium2 => "dilithium2_code_sign_module.wasm.multivalue.wasm",
+            SecurityLevel::Dilithium3 => "dilithium3_code_sign_module.wasm.multivalue.wasm",
+            SecurityLevel::Dilithium5 => "dilithium5_code_sign_module.wasm.multivalue.wasm",
+            SecurityLevel::MlDsa44 => "mldsa44_code_sign_module.wasm.multivalue.wasm",
+            SecurityLevel::MlDsa65 => "mldsa65_code_sign_module.wasm.multivalue.wasm",
+            SecurityLevel::MlDsa87 => "mldsa87_code_sign_module.wasm.multivalue.wasm",
+        }
+    }
+
+    /// Module name reported by `GET /metrics`.
+    pub fn metrics_name(self) -> &'static str {
+        match self {
+            SecurityLevel::Dilithium2 => "dilithium2",
+            SecurityLevel::Dilithium3 => "dilithium3",
+            SecurityLevel::Dilithium5 => "dilithium5",
+            SecurityLevel::MlDsa44 => "mldsa44",
+            SecurityLevel::MlDsa65 => "mldsa65",
+            SecurityLevel::MlDsa87 => "mldsa87",
+        }
+    }
+
+    /// Algorithm OID of SubjectPublicKeyInfo keys: FIPS 204 for ML-DSA, the
+    /// Open Quantum Safe assignments for round-3 Dilithium.
+    pub fn oid(self) -> ObjectIdentifier {
+        ObjectIdentifier::new_unwrap(match self {
+            SecurityLevel::Dilithium2 => "1.3.6.1.4.1.2.267.7.4.4",
+            SecurityLevel::Dilithium3 => "1.3.6.1.4.1.2.267.7.6.5",
+            SecurityLevel::Dilithium5 => "1.3.6.1.4.1.2.267.7.8.7",
+            SecurityLevel::MlDsa44 => "2.16.840.1.101.3.4.3.17",
+            SecurityLevel::MlDsa65 => "2.16.840.1.101.3.4.3.18",
+            SecurityLevel::MlDsa87 => "2.16.840.1.101.3.4.3.19",
+        })
+    }
+
+    pub fn from_oid(oid: ObjectIdentifier) -> Option<Self> {
+        SecurityLevel::ALL
+            .into_iter()
+            .find(|level| level.oid() == oid)
+    }
+
+    /// Identifier byte prefixed to ML-DSA keys and signatures.
+    pub fn tag(self) -> Option<u8> {
+        match self {
+            SecurityLevel::MlDsa44 => Some(0x44),
+            SecurityLevel::MlDsa65 => Some(0x65),
+            SecurityLevel::MlDsa87 => Some(0x87),
+            _ => None,
+        }
+    }
+
+    pub fn is_ml_dsa(self) -> bool {
+        self.tag().is_some()
+    }
+
+    //ML-DSA keys and signatures carry a one-byte algorithm identifier
+    pub fn public_key_bytes(self) -> usize {
+        match self {
+            SecurityLevel::Dilithium2 => 1312,
+            SecurityLevel::Dilithium3 => 1952,
+            SecurityLevel::Dilithium5 => 2592,
+            SecurityLevel::MlDsa44 => 1 + 1312,
+            SecurityLevel::MlDsa65 => 1 + 1952,
+            SecurityLevel::MlDsa87 => 1 + 2592,
+        }
+    }
+
+    //ML-DSA secret keys are stored as their 32-byte FIPS 204 seed
+    pub fn secret_key_bytes(self) -> usize {
+        match self {
+            SecurityLevel::Dilithium2 => 2528,
+            SecurityLevel::Dilithium3 => 4000,
+            SecurityLevel::Dilithium5 => 4864,
+            SecurityLevel::MlDsa44 | SecurityLevel::MlDsa65 | SecurityLevel::MlDsa87 => 1 + 32,
+        }
+    }
+
+    pub fn signature_bytes(self) -> usize {
+        match self {
+            SecurityLevel::Dilithium2 => 2420,
+            SecurityLevel::Dilithium3 => 3293,
+            SecurityLevel::Dilithium5 => 4595,
+            SecurityLevel::MlDsa44 => 1 + 2420,
+            SecurityLevel::MlDsa65 => 1 + 3309,
+            SecurityLevel::MlDsa87 => 1 + 4627,
+        }
+    }
+
+    /// Infers the level from the length of an encoded public key; tagged
+    /// ML-DSA keys are one byte longer than round-3 keys of the same strength.
+    pub fn from_public_key(public_key: &[u8]) -> Option<Self> {
+        SecurityLevel::ALL
+            .into_iter()
+            .find(|level| level.public_key_bytes() == public_key.len())
+    }
+}
+
+impl fmt::Display for SecurityLevel {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SecurityLevel::Dilithium2 => f.write_str("Dilithium2"),
+            SecurityLevel::Dilithium3 => f.write_str("Dilithium3"),
+            SecurityLevel::Dilithium5 => f.write_str("Dilithium5"),
+            SecurityLevel::MlDsa44 => f.write_str("ML-DSA-44"),
+            SecurityLevel::MlDsa65 => f.write_str("ML-DSA-65"),
+            SecurityLevel::MlDsa87 => f.write_str("ML-DSA-87"),
+        }
+    }
+}
+
+impl TryFrom<u8> for SecurityLevel {
+    type Error = String;
+
+    fn try_from(level: u8) -> Result<Self, Self::Error> {
+        match level {
+            2 => Ok(SecurityLevel::Dilithium2),
+            3 => Ok(SecurityLevel::Dilithium3),
+            5 => Ok(SecurityLevel::Dilithium5),
+            44 => Ok(SecurityLevel::MlDsa44),
+            65 => Ok(SecurityLevel::MlDsa65),
+            87 => Ok(SecurityLevel::MlDsa87),
+            _ => Err(format!(
+                "unsupported level {}, expected 2, 3 or 5 (Dilithium) or 44, 65 or 87 (ML-DSA)",
+                level
+            )),
+        }
+    }
+}
+
+impl From<SecurityLevel> for u8 {
+    fn from(level: SecurityLevel) -> u8 {
+        match level {
+            SecurityLevel::Dilithium2 => 2,
+            SecurityLevel::Dilithium3 => 3,
+            SecurityLevel::Dilithium5 => 5,
+            SecurityLevel::MlDsa44 => 44,
+            SecurityLevel::MlDsa65 => 65,
+            SecurityLevel::MlDsa87 => 87,
+        }
+    }
+}
@@ -1,16 +1,117 @@
 #[macro_use]
 extern crate rocket;
+mod error;
+mod guest;
+mod kem;
+mod key_format;
+mod keystore;
+mod level;
+mod module_signature;
+mod modules;
+mod prehash;
+mod raw;
+#[cfg(test)]
+mod tests;
+
 use base64::{engine::general_purpose, Engine as _};
+use error::{decode_base64, expect_length, ApiError};
+use guest::{
+    call, export, non_null, read_from_guest, write_to_guest, GuestStore, Guests, PoolConfig,
+    PoolMetrics,
+};
+use key_format::KeyFormat;
+use keystore::{KeyStore, SigningKey};
+use level::SecurityLevel;
+use module_signature::{ModuleTrustConfig, TrustedKey};
+use modules::{ModuleLimits, ModuleStore};
+use rocket::fairing::AdHoc;
+use rocket::http::Status;
+use rocket::response::status;
 use rocket::serde::{json::Json, Deserialize, Serialize};
-use wasmtime::*;
-use wasmtime_wasi::WasiCtxBuilder;
+use rocket::{Build, Rocket, State};
+use signing_formats::framing::MAX_CONTEXT_BYTES;
+use std::borrow::Cow;
+use std::collections::BTreeMap;
+use wasmtime::Instance;
+use zeroize::Zeroizing;
+
+//How the `data` field of a JSON request is encoded
+#[derive(Clone, Copy, Default, Deserialize)]
+#[serde(crate = "rocket::serde", rename_all = "lowercase")]
+enum Encoding {
+    //Signed as the UTF-8 bytes of the string
+    #[default]
+    Utf8,
+    //Arbitrary binary data, e.g. a wasm module or a tarball
+    Base64,
+}
+
+impl Encoding {
+    fn decode(self, data: &str) -> Result<Cow<'_, [u8]>, ApiError> {
+        match self {
+            Encoding::Utf8 => Ok(Cow::Borrowed(data.as_bytes())),
+            Encoding::Base64 => decode_base64("data", data).map(Cow::Owned),
+        }
+    }
+}
+
+//Length of the seed `generate_keypair_from_seed` derives a keypair from
+const SEED_BYTES: usize = 32;
+
+//What a signature covers
+#[derive(Clone, Copy)]
+enum Payload<'a> {
+    //The message bytes themselves
+    Message(&'a [u8]),
+    //The message bytes bound to a context string of at most 255 bytes
+    Contextual(&'a [u8], &'a [u8]),
+    //A digest computed by the host, with the guest's hash algorithm identifier
+    Prehashed(i32, &'a [u8]),
+}
+
+impl<'a> Payload<'a> {
+    //Message bytes, bound to `context` when one is given
+    fn new(data: &'a [u8], context: Option<&'a str>) -> Result<Self, ApiError> {
+        match context {
+            Some(context) if context.len() > MAX_CONTEXT_BYTES => Err(ApiError::ContextTooLong {
+                actual: context.len(),
+            }),
+            Some(context) => Ok(Payload::Contextual(data, context.as_bytes())),
+            None => Ok(Payload::Message(data)),
+        }
+    }
+
+    //What a round-3 level cannot sign or verify (see `signing_formats::framing`)
+    fn ml_dsa_only(self) -> Option<&'static str> {
+        match self {
+            Payload::Contextual(..) => Some("signing with a context"),
+            Payload::Prehashed(..) => Some("prehash signing"),
+            Payload::Message(_) => None,
+        }
+    }
+
+    fn check_level(self, level: SecurityLevel) -> Result<(), ApiError> {
+        match self.ml_dsa_only() {
+            Some(what) if !level.is_ml_dsa() => Err(ApiError::UnsupportedLevel { level, what }),
+            _ => Ok(()),
+        }
+    }
+}
 
 #[derive(Deserialize)]
 #[serde(crate = "rocket::serde")]
 struct VerifyRequest<'a> {
     data: &'a str,
+    #[serde(default)]
+    encoding: Encoding,
     signature: &'a str,
-    public_key: &'a str,
+    //Base64 of a raw or DER SubjectPublicKeyInfo key, or a PEM block (owned, as
+    //its line breaks are escaped in JSON)
+    public_key: String,
+    //Inferred from the public key when absent
+    level: Option<SecurityLevel>,
+    //Must match the context the data was signed under
+    context: Option<&'a str>,
 }
 #[derive(Serialize, Deserialize)]
 #[serde(crate = "rocket::serde")]
@@ -22,6 +123,15 @@ struct VerifyResponse {
 #[serde(crate = "rocket::serde")]
 struct SignRequest<'a> {
     data: &'a str,
+    #[serde(default)]
+    encoding: Encoding,
+    //Identity from the key store to sign with; a throwaway keypair is used when absent
+    key_id: Option<&'a str>,
+    //Defaults to the level of `key_id`, or ML-DSA-65 for a throwaway keypair
+    level: Option<SecurityLevel>,
+    //Binds the signature to a purpose (ML-DSA only); signatures without one stay
+    //verifiable as before
+    context: Option<&'a str>,
 }
 
 #[derive(Serialize, Deserialize)]
@@ -29,203 +139,517 @@ struct SignRequest<'a> {
 struct SignResponse {
     signature: String,
     public_key: String,
+    level: SecurityLevel,
+}
+
+#[derive(Deserialize)]
+#[serde(crate = "rocket::serde")]
+struct CreateKeyRequest {
+    #[serde(default)]
+    level: SecurityLevel,
+    //Base64 encoded 32-byte seed for a reproducible key, e.g. for test fixtures
+    seed: Option<String>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "rocket::serde")]
+struct KeyResponse {
+    id: String,
+    public_key: String,
+    level: SecurityLevel,
+}
+
+//Key store settings, read from Rocket.toml or `ROCKET_KEYSTORE_*` environment variables
+#[derive(Deserialize)]
+#[serde(crate = "rocket::serde")]
+struct KeyStoreConfig {
+    #[serde(default = "default_keystore_dir")]
+    keystore_dir: String,
+    //Base64 encoded 32-byte key used to encrypt secret keys at rest
+    keystore_key: String,
+}
+
+fn default_keystore_dir() -> String {
+    "keys".to_string()
 }
 
-#[post("/sign", data = "<sign_request>")]
-fn sign(sign_request: Json<SignRequest<'_>>) -> Json<SignResponse> {
-    // Engines store global configuration preferences such as compilation settings, enabled features, etc.
-    let engine = Engine::default();
-
-    //A Module is a compiled in-memory representation of an input WebAssembly binary.
-    let module =
-        Module::from_file(&engine, "dilithium_code_sign_module.wasm.multivalue.wasm").unwrap();
-
-    //Structure used to link wasm modules/instances together.
-    let mut linker = Linker::new(&engine);
-
-    //WasiContext
-    let wasi = WasiCtxBuilder::new()
-        .inherit_stdio()
-        .inherit_args()
-        .unwrap()
-        .build();
-
-    //A Store is a collection of WebAssembly instances and host-defined state.
-    //All WebAssembly instances and items will be attached to and refer to a Store.
-    //For example instances, functions, globals, and tables are all attached to a Store.
-    let mut store = Store::new(&engine, wasi);
-
-    wasmtime_wasi::add_to_linker(&mut linker, |s| s).unwrap();
-
-    //An instantiated WebAssembly module.
-    let instance = linker.instantiate(&mut store, &module).unwrap();
-
-    //Exported memory
-    let memory = instance
-        .get_memory(&mut store, "memory")
-        .ok_or(anyhow::format_err!("failed to find `memory` export"))
-        .unwrap();
-
-    //Exported Functions
-    let alloc_fn = instance
-        .get_typed_func::<i32, i32>(&mut store, "alloc")
-        .unwrap();
-    let dealloc_fn = instance
-        .get_typed_func::<(i32, i32), ()>(&mut store, "dealloc")
-        .unwrap();
-    let sign_data_fn = instance
-        .get_typed_func::<(i32, i32), (i32, i32)>(&mut store, "sign_data")
-        .unwrap();
-
-    //Allocating memory for data to be signed
-    let data_ptr = alloc_fn
-        .call(&mut store, sign_request.data.len() as i32)
-        .unwrap();
-
-    //Copying data to be signed into allocated memory
-    unsafe {
-        let raw = memory.data_ptr(&mut store).offset(data_ptr as isize);
-        raw.copy_from(
-            sign_request.data.as_bytes().as_ptr(),
-            sign_request.data.len(),
-        );
+//Raw `application/octet-stream` bodies are routed to `raw` first, everything else lands here
+#[post("/sign", data = "<sign_request>", rank = 2)]
+fn sign(
+    sign_request: Json<SignRequest<'_>>,
+    keystore: &State<KeyStore>,
+    guests: &State<Guests>,
+) -> Result<Json<SignResponse>, ApiError> {
+    let data = sign_request.encoding.decode(sign_request.data)?;
+    sign_bytes(
+        Payload::new(&data, sign_request.context)?,
+        sign_request.key_id,
+        sign_request.level,
+        keystore,
+        guests,
+    )
+    .map(Json)
+}
+
+//Signs `payload` with the stored identity `key_id`, or with a throwaway keypair when absent
+fn sign_bytes(
+    payload: Payload<'_>,
+    key_id: Option<&str>,
+    level: Option<SecurityLevel>,
+    keystore: &KeyStore,
+    guests: &Guests,
+) -> Result<SignResponse, ApiError> {
+    //Looking up the requested identity before spinning up the module
+    let keypair = match key_id {
+        Some(key_id) => Some(
+            keystore
+                .keypair(key_id)
+                .map_err(ApiError::KeyStore)?
+                .ok_or_else(|| ApiError::UnknownKey(key_id.to_string()))?,
+        ),
+        None => None,
     };
-    //Calling the sign_data Function returns signature and public key pointers
-    let (sig_ptr, pk_ptr) = sign_data_fn
-        .call(
-            &mut store,
-            (data_ptr as i32, sign_request.data.len() as i32),
-        )
-        .unwrap();
-
-    //Reading signature from memory
-    let mut sig_data = [0u8; 3293];
-    memory
-        .read(&store, sig_ptr as usize, &mut sig_data)
-        .unwrap();
-
-    //Reading public key from memory
-    let mut pk_data = [0u8; 1952];
-    memory.read(&store, pk_ptr as usize, &mut pk_data).unwrap();
-
-    //Calling dealloc function to deallocate memory allocated for data to be signature
-    dealloc_fn
-        .call(&mut store, (sig_ptr, sig_data.len() as i32))
-        .unwrap();
-    dealloc_fn
-        .call(&mut store, (pk_ptr, pk_data.len() as i32))
-        .unwrap();
-    //Encoding signature and public key to Base64
-    let sig_data_encoded = general_purpose::STANDARD.encode(&sig_data);
-    let pk_data_encoded = general_purpose::STANDARD.encode(&pk_data);
-
-    //Returning signature and public key as JSON response
-    Json(SignResponse {
-        signature: sig_data_encoded,
-        public_key: pk_data_encoded,
+    let level = match (&keypair, level) {
+        (Some(keypair), Some(level)) if keypair.level != level => {
+            return Err(ApiError::LevelMismatch {
+                key: keypair.level,
+                requested: level,
+            })
+        }
+        (Some(keypair), _) => keypair.level,
+        (None, level) => level.unwrap_or_default(),
+    };
+    payload.check_level(level)?;
+
+    let mut lease = guests.signing(level).instantiate()?;
+    lease.held_secrets();
+    let (store, instance) = lease.parts();
+
+    let (sig_data, pk_data) = match (keypair, payload) {
+        (None, Payload::Message(data)) => {
+            let sign_data_fn = export::<(i32, i32), (i32, i32)>(store, &instance, "sign_data")?;
+
+            //Calling the sign_data Function returns signature and public key pointers
+            let data_ptr = write_to_guest(store, &instance, data)?;
+            let (sig_ptr, pk_ptr) = call(store, &sign_data_fn, (data_ptr, data.len() as i32))?;
+            let sig_data = read_from_guest(store, &instance, sig_ptr, level.signature_bytes())?;
+            let pk_data = read_from_guest(store, &instance, pk_ptr, level.public_key_bytes())?;
+            (sig_data, pk_data)
+        }
+        (keypair, payload) => {
+            let keypair = match keypair {
+                Some(keypair) => keypair,
+                None => generate_keypair(store, &instance, level)?,
+            };
+            let sk_ptr = write_to_guest(store, &instance, &keypair.secret_key)?;
+            let sk_len = keypair.secret_key.len() as i32;
+
+            //All three exports return the signature pointer, or null for a bad key or digest
+            let sig_ptr = match payload {
+                Payload::Message(data) => {
+                    let sign_fn = export::<(i32, i32, i32, i32), i32>(
+                        store,
+                        &instance,
+                        "sign_with_secret_key",
+                    )?;
+                    let data_ptr = write_to_guest(store, &instance, data)?;
+                    let sig_ptr = call(
+                        store,
+                        &sign_fn,
+                        (data_ptr, data.len() as i32, sk_ptr, sk_len),
+                    )?;
+                    non_null(sig_ptr, "secret key")?
+                }
+                Payload::Contextual(data, context) => {
+                    let sign_fn = export::<(i32, i32, i32, i32, i32, i32), i32>(
+                        store,
+                        &instance,
+                        "sign_with_context",
+                    )?;
+                    let data_ptr = write_to_guest(store, &instance, data)?;
+                    let context_ptr = write_to_guest(store, &instance, context)?;
+                    let sig_ptr = call(
+                        store,
+                        &sign_fn,
+                        (
+                            data_ptr,
+                            data.len() as i32,
+                            context_ptr,
+                            context.len() as i32,
+                            sk_ptr,
+                            sk_len,
+                        ),
+                    )?;
+                    non_null(sig_ptr, "context or secret key")?
+                }
+                Payload::Prehashed(hash_alg, digest) => {
+                    let sign_fn = export::<(i32, i32, i32, i32, i32), i32>(
+                        store,
+                        &instance,
+                        "sign_prehashed",
+                    )?;
+                    let digest_ptr = write_to_guest(store, &instance, digest)?;
+                    let sig_ptr = call(
+                        store,
+                        &sign_fn,
+                        (digest_ptr, digest.len() as i32, hash_alg, sk_ptr, sk_len),
+                    )?;
+                    non_null(sig_ptr, "digest or secret key")?
+                }
+            };
+            let sig_data = read_from_guest(store, &instance, sig_ptr, level.signature_bytes())?;
+            (sig_data, keypair.public_key)
+        }
+    };
+    lease.served();
+
+    //Returning signature and public key as Base64
+    Ok(SignResponse {
+        signature: general_purpose::STANDARD.encode(sig_data),
+        public_key: general_purpose::STANDARD.encode(pk_data),
+        level,
     })
 }
 
-#[post("/verify", data = "<verify_request>")]
-fn verify(verify_request: Json<VerifyRequest<'_>>) -> Json<VerifyResponse> {
-    // Engines store global configuration preferences such as compilation settings, enabled features, etc.
-    let engine = Engine::default();
-
-    //A Module is a compiled in-memory representation of an input WebAssembly binary.
-    let module =
-        Module::from_file(&engine, "dilithium_code_sign_module.wasm.multivalue.wasm").unwrap();
-
-    //Structure used to link wasm modules/instances together.
-    let mut linker = Linker::new(&engine);
-
-    //WasiContext
-    let wasi = WasiCtxBuilder::new()
-        .inherit_stdio()
-        .inherit_args()
-        .unwrap()
-        .build();
-
-    //A Store is a collection of WebAssembly instances and host-defined state.
-    //All WebAssembly instances and items will be attached to and refer to a Store.
-    //For example instances, functions, globals, and tables are all attached to a Store.
-    let mut store = Store::new(&engine, wasi);
-
-    wasmtime_wasi::add_to_linker(&mut linker, |s| s).unwrap();
-
-    //An instantiated WebAssembly module.
-    let instance = linker.instantiate(&mut store, &module).unwrap();
-    //Exported memory
-    let memory = instance
-        .get_memory(&mut store, "memory")
-        .ok_or(anyhow::format_err!("failed to find `memory` export"))
-        .unwrap();
-    //Exported fucntions
-    let alloc_fn = instance
-        .get_typed_func::<i32, i32>(&mut store, "alloc")
-        .unwrap();
-    let verify_data_fn = instance
-        .get_typed_func::<(i32, i32, i32, i32, i32, i32), i32>(&mut store, "verify_data")
-        .unwrap();
-
+#[post("/verify", data = "<verify_request>", rank = 2)]
+fn verify(
+    verify_request: Json<VerifyRequest<'_>>,
+    guests: &State<Guests>,
+) -> Result<Json<VerifyResponse>, ApiError> {
     //decode signature, public key from Base64
-    let signature_decoded = general_purpose::STANDARD
-        .decode(&verify_request.signature)
-        .unwrap();
-    let public_key_decoded = general_purpose::STANDARD
-        .decode(&verify_request.public_key)
-        .unwrap();
-    let data_decoded = verify_request.data.as_bytes().to_vec();
-
-    //allocate memory for signature, public key, data
-    let signature_ptr = alloc_fn
-        .call(&mut store, signature_decoded.len() as i32)
-        .unwrap();
-    let public_key_ptr = alloc_fn
-        .call(&mut store, public_key_decoded.len() as i32)
-        .unwrap();
-    let data_ptr = alloc_fn
-        .call(&mut store, data_decoded.len() as i32)
-        .unwrap();
-
-    //copy signature, public key, data to wasm memory
-    unsafe {
-        let raw = memory.data_ptr(&mut store).offset(signature_ptr as isize);
-        raw.copy_from(signature_decoded.as_ptr(), signature_decoded.len());
-    };
+    let signature_decoded = decode_base64("signature", verify_request.signature)?;
+    let public_key_decoded =
+        key_format::decode_public_key("public_key", &verify_request.public_key)?;
+    let data_decoded = verify_request.encoding.decode(verify_request.data)?;
+    verify_bytes(
+        Payload::new(&data_decoded, verify_request.context)?,
+        &signature_decoded,
+        &public_key_decoded,
+        verify_request.level,
+        guests,
+    )
+    .map(Json)
+}
 
-    unsafe {
-        let raw = memory.data_ptr(&mut store).offset(public_key_ptr as isize);
-        raw.copy_from(public_key_decoded.as_ptr(), public_key_decoded.len());
+//Verifies `signature_decoded` over `payload`, inferring the level from the key if needed
+fn verify_bytes(
+    payload: Payload<'_>,
+    signature_decoded: &[u8],
+    public_key_decoded: &[u8],
+    level: Option<SecurityLevel>,
+    guests: &Guests,
+) -> Result<VerifyResponse, ApiError> {
+    //SubjectPublicKeyInfo keys name their level in the algorithm identifier
+    let spki_key;
+    let (level, public_key_decoded) = if key_format::is_spki(public_key_decoded) {
+        let (key_level, public_key) = key_format::from_spki("public_key", public_key_decoded)?;
+        if let Some(requested) = level.filter(|requested| *requested != key_level) {
+            return Err(ApiError::LevelMismatch {
+                key: key_level,
+                requested,
+            });
+        }
+        spki_key = public_key;
+        (Some(key_level), &spki_key[..])
+    } else {
+        (level, public_key_decoded)
     };
+    let level = level
+        .or_else(|| SecurityLevel::from_public_key(public_key_decoded))
+        .unwrap_or_default();
+    payload.check_level(level)?;
+    //A key of the wrong size is a malformed request rather than a failed verification
+    expect_length("public_key", public_key_decoded, level.public_key_bytes())?;
+
+    let mut lease = guests.signing(level).instantiate()?;
+    let (store, instance) = lease.parts();
+
+    //copy signature, public key to wasm memory
+    let signature_ptr = write_to_guest(store, &instance, signature_decoded)?;
+    let public_key_ptr = write_to_guest(store, &instance, public_key_decoded)?;
+    let signature_len = signature_decoded.len() as i32;
+    let public_key_len = public_key_decoded.len() as i32;
+
+    //Every export returns 1 if signature is valid and 0 if not
+    let response = match payload {
+        Payload::Message(data) => {
+            let verify_data_fn =
+                export::<(i32, i32, i32, i32, i32, i32), i32>(store, &instance, "verify_data")?;
+            let data_ptr = write_to_guest(store, &instance, data)?;
+            call(
+                store,
+                &verify_data_fn,
+                (
+                    data_ptr,
+                    data.len() as i32,
+                    signature_ptr,
+                    signature_len,
+                    public_key_ptr,
+                    public_key_len,
+                ),
+            )?
+        }
+        Payload::Contextual(data, context) => {
+            let verify_fn = export::<(i32, i32, i32, i32, i32, i32, i32, i32), i32>(
+                store,
+                &instance,
+                "verify_with_context",
+            )?;
+            let data_ptr = write_to_guest(store, &instance, data)?;
+            let context_ptr = write_to_guest(store, &instance, context)?;
+            call(
+                store,
+                &verify_fn,
+                (
+                    data_ptr,
+                    data.len() as i32,
+                    context_ptr,
+                    context.len() as i32,
+                    signature_ptr,
+                    signature_len,
+                    public_key_ptr,
+                    public_key_len,
+                ),
+            )?
+        }
+        Payload::Prehashed(hash_alg, digest) => {
+            let verify_fn = export::<(i32, i32, i32, i32, i32, i32, i32), i32>(
+                store,
+                &instance,
+                "verify_prehashed",
+            )?;
+            let digest_ptr = write_to_guest(store, &instance, digest)?;
+            call(
+                store,
+                &verify_fn,
+                (
+                    digest_ptr,
+                    digest.len() as i32,
+                    hash_alg,
+                    signature_ptr,
+                    signature_len,
+                    public_key_ptr,
+                    public_key_len,
+                ),
+            )?
+        }
+    };
+    lease.served();
 
-    unsafe {
-        let raw = memory.data_ptr(&mut store).offset(data_ptr as isize);
-        raw.copy_from(data_decoded.as_ptr(), data_decoded.len());
+    Ok(VerifyResponse {
+        is_verified: response != 0,
+    })
+}
+
+#[post("/keys", data = "<create_request>")]
+fn create_key(
+    create_request: Option<Json<CreateKeyRequest>>,
+    keystore: &State<KeyStore>,
+    guests: &State<Guests>,
+) -> Result<status::Created<Json<KeyResponse>>, ApiError> {
+    let CreateKeyRequest { level, seed } = match create_request {
+        Some(request) => request.into_inner(),
+        None => CreateKeyRequest {
+            level: SecurityLevel::default(),
+            seed: None,
+        },
+    };
+    let seed = match seed {
+        Some(seed) => {
+            let seed = decode_base64("seed", &seed)?;
+            expect_length("seed", &seed, SEED_BYTES)?;
+            Some(seed)
+        }
+        None => None,
     };
+    let mut lease = guests.signing(level).instantiate()?;
+    lease.held_secrets();
+    let (store, instance) = lease.parts();
+    let keypair = match seed {
+        Some(seed) => generate_keypair_from_seed(store, &instance, level, &seed)?,
+        None => generate_keypair(store, &instance, level)?,
+    };
+    lease.served();
+
+    let id = keystore
+        .insert(level, &keypair.public_key, &keypair.secret_key)
+        .map_err(ApiError::KeyStore)?;
+    Ok(
+        status::Created::new(format!("/keys/{}", id)).body(Json(KeyResponse {
+            id,
+            public_key: general_purpose::STANDARD.encode(keypair.public_key),
+            level,
+        })),
+    )
+}
 
-    //Calling verify_data function returns 0 if signature is valid and 1 if not
-    let response = verify_data_fn
-        .call(
-            &mut store,
-            (
-                data_ptr,
-                data_decoded.len() as i32,
-                signature_ptr,
-                signature_decoded.len() as i32,
-                public_key_ptr,
-                public_key_decoded.len() as i32,
-            ),
-        )
-        .unwrap();
+//Generates a keypair inside an already instantiated signing module
+fn generate_keypair(
+    store: &mut GuestStore,
+    instance: &Instance,
+    level: SecurityLevel,
+) -> Result<SigningKey, ApiError> {
+    let generate_keypair_fn = export::<(), (i32, i32)>(store, instance, "generate_keypair")?;
+
+    //Calling generate_keypair returns public key and secret key pointers
+    let (pk_ptr, sk_ptr) = call(store, &generate_keypair_fn, ())?;
+    Ok(SigningKey {
+        level,
+        public_key: read_from_guest(store, instance, pk_ptr, level.public_key_bytes())?,
+        secret_key: Zeroizing::new(read_from_guest(
+            store,
+            instance,
+            sk_ptr,
+            level.secret_key_bytes(),
+        )?),
+    })
+}
+
+//Derives the keypair of `seed` inside an already instantiated signing module
+fn generate_keypair_from_seed(
+    store: &mut GuestStore,
+    instance: &Instance,
+    level: SecurityLevel,
+    seed: &[u8],
+) -> Result<SigningKey, ApiError> {
+    let generate_keypair_fn =
+        export::<(i32, i32), (i32, i32)>(store, instance, "generate_keypair_from_seed")?;
+
+    let seed_ptr = write_to_guest(store, instance, seed)?;
+    let (pk_ptr, sk_ptr) = call(store, &generate_keypair_fn, (seed_ptr, seed.len() as i32))?;
+    let pk_ptr = non_null(pk_ptr, "seed")?;
+    Ok(SigningKey {
+        level,
+        public_key: read_from_guest(store, instance, pk_ptr, level.public_key_bytes())?,
+        secret_key: Zeroizing::new(read_from_guest(
+            store,
+            instance,
+            sk_ptr,
+            level.secret_key_bytes(),
+        )?),
+    })
+}
 
-    if response != 0 {
-        Json(VerifyResponse { is_verified: true })
+//`?format=der` or `?format=pem` exports the key as a SubjectPublicKeyInfo
+#[get("/keys/<id>?<format>")]
+fn get_key(
+    id: &str,
+    format: Option<KeyFormat>,
+    keystore: &State<KeyStore>,
+) -> Result<Json<KeyResponse>, ApiError> {
+    let (level, public_key) = keystore
+        .public_key(id)
+        .map_err(ApiError::KeyStore)?
+        .ok_or_else(|| ApiError::UnknownKey(id.to_string()))?;
+    Ok(Json(KeyResponse {
+        id: id.to_string(),
+        public_key: key_format::encode_public_key(level, &public_key, format.unwrap_or_default()),
+        level,
+    }))
+}
+
+#[delete("/keys/<id>")]
+fn delete_key(id: &str, keystore: &State<KeyStore>) -> Result<Status, ApiError> {
+    if keystore.remove(id).map_err(ApiError::KeyStore)? {
+        Ok(Status::NoContent)
     } else {
-        Json(VerifyResponse { is_verified: false })
+        Err(ApiError::UnknownKey(id.to_string()))
     }
 }
 
+#[get("/metrics")]
+fn metrics(guests: &State<Guests>) -> Json<BTreeMap<&'static str, PoolMetrics>> {
+    Json(guests.metrics())
+}
+
 #[launch]
 fn rocket() -> _ {
-    rocket::build().mount("/", routes![sign, verify])
+    app(rocket::build())
+}
+
+//Routes, catchers and fairings, shared by the launched server and the tests
+fn app(rocket: Rocket<Build>) -> Rocket<Build> {
+    rocket
+        .mount(
+            "/",
+            routes![sign, verify, create_key, get_key, delete_key, metrics],
+        )
+        .mount("/", raw::routes())
+        .mount("/kem", kem::routes())
+        .mount("/modules", modules::routes())
+        .register("/", catchers![error::default_catcher])
+        .attach(AdHoc::try_on_ignite("Guest modules", |rocket| async {
+            let config: PoolConfig = match rocket.figment().extract() {
+                Ok(config) => config,
+                Err(e) => {
+                    error!("Invalid instance pool configuration: {}", e);
+                    return Err(rocket);
+                }
+            };
+            let trust: ModuleTrustConfig = match rocket.figment().extract() {
+                Ok(trust) => trust,
+                Err(e) => {
+                    error!("No trusted module key is configured: {}", e);
+                    return Err(rocket);
+                }
+            };
+            let trusted_key = match TrustedKey::load(&trust.trusted_module_key) {
+                Ok(key) => key,
+                Err(e) => {
+                    error!("{:#}", e);
+                    return Err(rocket);
+                }
+            };
+            let allowed_keys = match trust
+                .allowed_module_keys
+                .iter()
+                .map(|path| TrustedKey::load(path))
+                .collect::<anyhow::Result<_>>()
+            {
+                Ok(keys) => keys,
+                Err(e) => {
+                    error!("{:#}", e);
+                    return Err(rocket);
+                }
+            };
+            let limits: ModuleLimits = match rocket.figment().extract() {
+                Ok(limits) => limits,
+                Err(e) => {
+                    error!("Invalid module limits: {}", e);
+                    return Err(rocket);
+                }
+            };
+            match Guests::load(&config, &trusted_key) {
+                Ok(guests) => Ok(rocket
+                    .manage(guests)
+                    .manage(ModuleStore::new(allowed_keys, limits))),
+                Err(e) => {
+                    error!("{:#}", e);
+                    Err(rocket)
+                }
+            }
+        }))
+        .attach(AdHoc::try_on_ignite("Key store", |rocket| async {
+            let config: KeyStoreConfig = match rocket.figment().extract() {
+                Ok(config) => config,
+                Err(e) => {
+                    error!("Key store is not configured: {}", e);
+                    return Err(rocket);
+                }
+            };
+            let master_key = match general_purpose::STANDARD.decode(&config.keystore_key) {
+                Ok(key) => key,
+                Err(e) => {
+                    error!("`keystore_key` is not valid base64: {}", e);
+                    return Err(rocket);
+                }
+            };
+            match KeyStore::open(&config.keystore_dir, &master_key) {
+                Ok(keystore) => Ok(rocket.manage(keystore)),
+                Err(e) => {
+                    error!("Failed to open key store `{}`: {}", config.keystore_dir, e);
+                    Err(rocket)
+                }
+            }
+        }))
 }
@@ -0,0 +1,153 @@
+//! Provenance of guest and uploaded modules. A module is only compiled if it
+//! carries a valid signature by a trusted key, either embedded in its
+//! `signature` custom section (`test-dilithium sign-wasm`) or detached over
+//! the whole module file (`test-dilithium sign`): a `<module>.sig` file for
+//! the service's own guests, the `X-Signature` header for uploads.
+//!
+//! Signatures are checked on the host with a native ML-DSA implementation, as
+//! pure ML-DSA with an empty context, so a tampered guest never gets to vouch
+//! for itself. The embedded section is read with the parser test-dilithium
+//! writes it with (`signing_formats::wasm_section`). What gets compiled is
+//! therefore exactly the hashed bytes plus custom sections named `signature`,
+//! which have no effect on the code.
+
+use std::fs;
+use std::io::ErrorKind;
+
+use anyhow::Context;
+use pkcs8::der::pem;
+use rocket::serde::Deserialize;
+use signing_formats::wasm_section::{self, Section};
+use signing_formats::{framing, mldsa};
+
+use crate::key_format;
+use crate::level::SecurityLevel;
+
+/// Where trusted module keys are read from, set in Rocket.toml or with
+/// `ROCKET_TRUSTED_MODULE_KEY` and `ROCKET_ALLOWED_MODULE_KEYS`. Keys are
+/// ML-DSA public keys, raw or as a DER or PEM SubjectPublicKeyInfo.
+#[derive(Deserialize)]
+#[serde(crate = "rocket::serde")]
+pub struct ModuleTrustConfig {
+    /// Key the service's own guests are signed with. There is no default:
+    /// without it the service does not start.
+    pub trusted_module_key: String,
+    /// Keys whose modules may be uploaded to `POST /modules`; none by default.
+    #[serde(default)]
+    pub allowed_module_keys: Vec<String>,
+}
+
+/// An ML-DSA public key modules may be signed with.
+pub struct TrustedKey {
+    level: SecurityLevel,
+    //Tagged, as the guests encode it
+    public_key: Vec<u8>,
+}
+
+impl TrustedKey {
+    pub fn load(path: &str) -> anyhow::Result<TrustedKey> {
+        let file = fs::read(path)
+            .with_context(|| format!("failed to read trusted module key `{}`", path))?;
+        let invalid = |reason: String| anyhow::anyhow!("trusted module key `{}`: {}", path, reason);
+        let (level, public_key) = if file.starts_with(b"-----BEGIN") {
+            let der = match pem::decode_vec(&file) {
+                Ok(("PUBLIC KEY", der)) => der,
+                Ok((label, _)) => return Err(invalid(format!("unexpected PEM block {}", label))),
+                Err(e) => return Err(invalid(e.to_string())),
+            };
+            key_format::from_spki("trusted_module_key", &der).map_err(|e| invalid(e.to_string()))?
+        } else if let Some(level) = SecurityLevel::from_public_key(&file) {
+            (level, file)
+        } else {
+            key_format::from_spki("trusted_module_key", &file)
+                .map_err(|e| invalid(e.to_string()))?
+        };
+        if level.tag().is_none() || level.tag() != public_key.first().copied() {
+            return Err(invalid(format!(
+                "{} keys are not supported, use ML-DSA",
+                level
+            )));
+        }
+        Ok(TrustedKey { level, public_key })
+    }
+
+    /// Key id as in embedded signatures: the SHA-256 of the public key, in hex.
+    pub fn id(&self) -> String {
+        hex::encode(wasm_section::key_id(&self.public_key))
+    }
+
+    //Keys and signatures are both tagged with the level, which must agree
+    fn verify(&self, message: &[u8], signature: &[u8]) -> bool {
+        let message = framing::context_message(&[], message).expect("empty context");
+        mldsa::verify(&message, signature, &self.public_key)
+    }
+}
+
+/// Reads the module at `path` and returns its bytes if its embedded or
+/// detached signature by `key` is valid. The bytes returned are the ones that
+/// were checked, so they must be compiled rather than the file re-read.
+pub fn read_verified(path: &str, key: &TrustedKey) -> anyhow::Result<Vec<u8>> {
+    let wasm = fs::read(path)?;
+    let detached_path = format!("{}.sig", path);
+    let detached = match fs::read(&detached_path) {
+        Ok(signature) => Some(signature),
+        Err(e) if e.kind() == ErrorKind::NotFound => None,
+        Err(e) => return Err(e).with_context(|| format!("failed to read `{}`", detached_path)),
+    };
+    verify(&wasm, detached.as_deref(), std::slice::from_ref(key)).map_err(|reason| {
+        anyhow::anyhow!(
+            "not signed by the trusted module key: {} (`{}`)",
+            reason,
+            detached_path
+        )
+    })?;
+    Ok(wasm)
+}
+
+/// Checks that `wasm` carries a valid signature by one of `keys`, embedded or
+/// `detached`, and returns the key that signed it or why none did.
+pub fn verify<'k>(
+    wasm: &[u8],
+    detached: Option<&[u8]>,
+    keys: &'k [TrustedKey],
+) -> Result<&'k TrustedKey, String> {
+    let embedded = match check_embedded(wasm, keys) {
+        Ok(key) => return Ok(key),
+        Err(reason) => reason,
+    };
+    match detached {
+        Some(signature) => keys
+            .iter()
+            .find(|key| key.verify(wasm, signature))
+            .ok_or_else(|| format!("{}, and the detached signature is not valid", embedded)),
+        None => Err(format!("{}, and there is no detached signature", embedded)),
+    }
+}
+
+fn check_embedded<'k>(wasm: &[u8], keys: &'k [TrustedKey]) -> Result<&'k TrustedKey, &'static str> {
+    let (unsigned, section) = wasm_section::split(wasm).ok_or("it is not a valid module")?;
+    let section = section.ok_or("it has no signature section")?;
+    let section = Section::decode(section).ok_or("its signature section is malformed")?;
+    if section.hash != wasm_section::hash(&unsigned) {
+        return Err("it was modified after it was signed");
+    }
+    let signed = section.signed_message();
+    let mut by_trusted_key = false;
+    for entry in &section.signatures {
+        let Some(key) = keys
+            .iter()
+            .find(|key| wasm_section::key_id(&key.public_key) == entry.key_id)
+        else {
+            continue;
+        };
+        if entry.algorithm == u8::from(key.level) && key.verify(&signed, &entry.signature) {
+            return Ok(key);
+        }
+        by_trusted_key = true;
+    }
+    Err(if by_trusted_key {
+        "its embedded signature by a trusted key is invalid"
+    } else {
+        "its embedded signatures are by other keys"
+    })
+}
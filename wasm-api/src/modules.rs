@@ -0,0 +1,302 @@
+//! Uploaded modules. `POST /modules` takes a wasm module signed by one of the
+//! allow-listed keys, compiles it with an engine and instance pool of its
+//! own, apart from the service's guests, and keeps it in memory under the hex
+//! SHA-256 of its bytes; `GET /modules/<id>` describes a module and
+//! `POST /modules/<id>/invoke/<export>` calls one of its functions in a
+//! fresh instance. Arguments and results are JSON numbers, so exports taking
+//! or returning only i32, i64, f32 and f64 can be called.
+//!
+//! Instances get an empty WASI context, so no stdio, arguments, environment
+//! or directories of the service. Invocations trap once they run longer than
+//! `module_timeout_ms`, and the store keeps at most `max_modules` modules of
+//! `max_module_bytes` in total, checked before a module is compiled.
+
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+
+use rocket::data::{Data, Limits};
+use rocket::request::{FromRequest, Outcome, Request};
+use rocket::response::status;
+use rocket::serde::json::{Json, Value};
+use rocket::serde::{Deserialize, Serialize};
+use rocket::{Route, State};
+use sha2::{Digest, Sha256};
+use wasmtime::{Val, ValType};
+
+use crate::error::{decode_base64, ApiError};
+use crate::guest::{call_dynamic, Guest, Guests};
+use crate::module_signature::{self, TrustedKey};
+use crate::raw::read_body;
+
+//Detached signature over the whole module, for modules without an embedded one
+const SIGNATURE: &str = "X-Signature";
+
+/// Limits on uploaded modules, read from Rocket.toml or `ROCKET_*`
+/// environment variables.
+#[derive(Deserialize)]
+#[serde(crate = "rocket::serde")]
+pub struct ModuleLimits {
+    /// Time an instance of an uploaded module may run, in milliseconds.
+    #[serde(default = "default_module_timeout_ms")]
+    pub module_timeout_ms: u64,
+    /// Uploaded modules kept at once.
+    #[serde(default = "default_max_modules")]
+    pub max_modules: usize,
+    /// Total size of the uploaded modules kept, in bytes.
+    #[serde(default = "default_max_module_bytes")]
+    pub max_module_bytes: usize,
+}
+
+fn default_module_timeout_ms() -> u64 {
+    1000
+}
+
+fn default_max_modules() -> usize {
+    64
+}
+
+fn default_max_module_bytes() -> usize {
+    64 * 1024 * 1024
+}
+
+/// Modules uploaded since the service started, and the keys allowed to sign them.
+pub struct ModuleStore {
+    allowed_keys: Vec<TrustedKey>,
+    limits: ModuleLimits,
+    modules: RwLock<HashMap<String, Arc<UploadedModule>>>,
+}
+
+struct UploadedModule {
+    guest: Guest,
+    //Key id of the allow-listed key that signed the module
+    signer: String,
+    size: usize,
+}
+
+impl ModuleStore {
+    pub fn new(allowed_keys: Vec<TrustedKey>, limits: ModuleLimits) -> Self {
+        ModuleStore {
+            allowed_keys,
+            limits,
+            modules: RwLock::new(HashMap::new()),
+        }
+    }
+
+    //Refuses a module of `size` bytes the store has no room for, before any
+    //work goes into compiling it; uploading a stored module again replaces it
+    fn check_room(&self, id: &str, size: usize) -> Result<(), ApiError> {
+        self.room(&self.modules.read().unwrap(), id, size)
+    }
+
+    //Stores a module, checking for room again since other uploads may have
+    //filled the store while it compiled
+    fn insert(&self, id: String, module: UploadedModule) -> Result<(), ApiError> {
+        let mut modules = self.modules.write().unwrap();
+        self.room(&modules, &id, module.size)?;
+        modules.insert(id, Arc::new(module));
+        Ok(())
+    }
+
+    fn room(
+        &self,
+        modules: &HashMap<String, Arc<UploadedModule>>,
+        id: &str,
+        size: usize,
+    ) -> Result<(), ApiError> {
+        if modules.contains_key(id) {
+            return Ok(());
+        }
+        let bytes: usize = modules.values().map(|module| module.size).sum();
+        if modules.len() >= self.limits.max_modules || bytes + size > self.limits.max_module_bytes {
+            return Err(ApiError::ModuleStoreFull {
+                max_modules: self.limits.max_modules,
+                max_bytes: self.limits.max_module_bytes,
+            });
+        }
+        Ok(())
+    }
+
+    fn get(&self, id: &str) -> Result<Arc<UploadedModule>, ApiError> {
+        self.modules
+            .read()
+            .unwrap()
+            .get(id)
+            .cloned()
+            .ok_or_else(|| ApiError::UnknownModule(id.to_string()))
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "rocket::serde")]
+struct ModuleResponse {
+    id: String,
+    signer: String,
+    exports: Vec<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(crate = "rocket::serde")]
+struct InvokeRequest {
+    #[serde(default)]
+    args: Vec<Value>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "rocket::serde")]
+struct InvokeResponse {
+    results: Vec<Value>,
+}
+
+struct DetachedSignature<'r>(Option<&'r str>);
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for DetachedSignature<'r> {
+    type Error = ();
+
+    async fn from_request(request: &'r Request<'_>) -> Outcome<Self, ()> {
+        Outcome::Success(DetachedSignature(request.headers().get_one(SIGNATURE)))
+    }
+}
+
+pub fn routes() -> Vec<Route> {
+    routes![upload, get_module, invoke]
+}
+
+#[post("/", data = "<data>")]
+async fn upload(
+    signature: DetachedSignature<'_>,
+    data: Data<'_>,
+    limits: &Limits,
+    guests: &State<Guests>,
+    modules: &State<ModuleStore>,
+) -> Result<status::Created<Json<ModuleResponse>>, ApiError> {
+    let signature = match signature.0 {
+        Some(signature) => Some(decode_base64(SIGNATURE, signature)?),
+        None => None,
+    };
+    let wasm = read_body(data, limits).await?;
+    let signer = module_signature::verify(&wasm, signature.as_deref(), &modules.allowed_keys)
+        .map_err(ApiError::UntrustedModule)?
+        .id();
+    let id = hex::encode(Sha256::digest(&wasm));
+    modules.check_room(&id, wasm.len())?;
+    let timeout = Duration::from_millis(modules.limits.module_timeout_ms);
+    let guest = guests
+        .compile(&wasm, timeout)
+        .map_err(ApiError::InvalidModule)?;
+
+    let response = ModuleResponse {
+        id: id.clone(),
+        signer: signer.clone(),
+        exports: guest.function_exports(),
+    };
+    modules.insert(
+        id.clone(),
+        UploadedModule {
+            guest,
+            signer,
+            size: wasm.len(),
+        },
+    )?;
+    Ok(status::Created::new(format!("/modules/{}", id)).body(Json(response)))
+}
+
+#[get("/<id>")]
+fn get_module(id: &str, modules: &State<ModuleStore>) -> Result<Json<ModuleResponse>, ApiError> {
+    let module = modules.get(id)?;
+    Ok(Json(ModuleResponse {
+        id: id.to_string(),
+        signer: module.signer.clone(),
+        exports: module.guest.function_exports(),
+    }))
+}
+
+#[post("/<id>/invoke/<export>", data = "<invoke_request>")]
+fn invoke(
+    id: &str,
+    export: &str,
+    invoke_request: Option<Json<InvokeRequest>>,
+    modules: &State<ModuleStore>,
+) -> Result<Json<InvokeResponse>, ApiError> {
+    let module = modules.get(id)?;
+    let args = invoke_request
+        .map(|request| request.into_inner().args)
+        .unwrap_or_default();
+
+    let mut lease = module.guest.instantiate()?;
+    let (store, instance) = lease.parts();
+    let func = instance
+        .get_func(&mut *store, export)
+        .ok_or_else(|| ApiError::UnknownExport(export.to_string()))?;
+    let ty = func.ty(&*store);
+    if let Some(ty) = ty.results().find(|ty| !is_number(ty)) {
+        return Err(ApiError::InvalidInvocation(format!(
+            "`{}` returns a {}",
+            export, ty
+        )));
+    }
+    if ty.params().len() != args.len() {
+        return Err(ApiError::InvalidInvocation(format!(
+            "`{}` takes {} arguments, got {}",
+            export,
+            ty.params().len(),
+            args.len()
+        )));
+    }
+    let params = ty
+        .params()
+        .zip(&args)
+        .enumerate()
+        .map(|(i, (ty, arg))| {
+            to_val(&ty, arg).ok_or_else(|| {
+                ApiError::InvalidInvocation(format!("argument {} is not a valid {}", i, ty))
+            })
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let mut results = vec![Val::I32(0); ty.results().len()];
+    call_dynamic(store, &func, &params, &mut results)?;
+    lease.served();
+    Ok(Json(InvokeResponse {
+        results: results.iter().map(to_json).collect(),
+    }))
+}
+
+fn is_number(ty: &ValType) -> bool {
+    matches!(
+        ty,
+        ValType::I32 | ValType::I64 | ValType::F32 | ValType::F64
+    )
+}
+
+//Integers must fit the type, either signed or unsigned; floats are rounded to f32 as needed
+fn to_val(ty: &ValType, arg: &Value) -> Option<Val> {
+    match ty {
+        ValType::I32 => {
+            let n = arg.as_i64()?;
+            i32::try_from(n)
+                .or_else(|_| u32::try_from(n).map(|n| n as i32))
+                .ok()
+                .map(Val::I32)
+        }
+        ValType::I64 => arg
+            .as_i64()
+            .or_else(|| arg.as_u64().map(|n| n as i64))
+            .map(Val::I64),
+        ValType::F32 => arg.as_f64().map(|n| Val::F32((n as f32).to_bits())),
+        ValType::F64 => arg.as_f64().map(|n| Val::F64(n.to_bits())),
+        _ => None,
+    }
+}
+
+//NaN and infinities have no JSON representation and come back as null
+fn to_json(val: &Val) -> Value {
+    match val {
+        Val::I32(n) => Value::from(*n),
+        Val::I64(n) => Value::from(*n),
+        Val::F32(bits) => Value::from(f32::from_bits(*bits)),
+        Val::F64(bits) => Value::from(f64::from_bits(*bits)),
+        _ => Value::Null,
+    }
+}
@@ -0,0 +1,43 @@
+use rocket::data::{ByteUnit, Data, ToByteUnit};
+use rocket::tokio::io::AsyncReadExt;
+use sha3::digest::{ExtendableOutput, Update, XofReader};
+use sha3::Shake256;
+
+use crate::error::ApiError;
+
+/// Length of the SHAKE256 digest that is signed in place of the payload.
+pub const DIGEST_BYTES: usize = 64;
+
+/// Identifier of SHAKE256 in the signing module's `sign_prehashed` and
+/// `verify_prehashed` exports, which frame the digest before signing it.
+pub const SHAKE256: i32 = signing_formats::framing::HASH_SHAKE256;
+
+//Size of the reads fed into the hash while the upload streams in
+const CHUNK_BYTES: usize = 64 * 1024;
+
+/// Hashes a request body with SHAKE256 as it arrives, so that bodies of any
+/// size are signed without being buffered. Fails if more than `limit` bytes
+/// are sent.
+pub async fn digest(data: Data<'_>, limit: ByteUnit) -> Result<[u8; DIGEST_BYTES], ApiError> {
+    //One byte past the limit tells an oversized body apart from one that fits exactly
+    let mut stream = data.open(limit + 1.bytes());
+    let mut hasher = Shake256::default();
+    let mut chunk = vec![0u8; CHUNK_BYTES];
+    let mut total = 0u64;
+    loop {
+        let read = stream.read(&mut chunk).await.map_err(ApiError::Body)?;
+        if read == 0 {
+            break;
+        }
+        total += read as u64;
+        if total > limit.as_u64() {
+            return Err(ApiError::PayloadTooLarge {
+                limit: limit.as_u64(),
+            });
+        }
+        hasher.update(&chunk[..read]);
+    }
+    let mut digest = [0u8; DIGEST_BYTES];
+    hasher.finalize_xof().read(&mut digest);
+    Ok(digest)
+}
@@ -0,0 +1,201 @@
+use rocket::data::{ByteUnit, Data, Limits, ToByteUnit};
+use rocket::request::{FromRequest, Outcome, Request};
+use rocket::serde::json::Json;
+use rocket::{Route, State};
+
+use crate::error::{decode_base64, ApiError};
+use crate::guest::Guests;
+use crate::keystore::KeyStore;
+use crate::level::SecurityLevel;
+use crate::prehash;
+use crate::{sign_bytes, verify_bytes, Payload, SignResponse, VerifyResponse};
+
+//Header names carrying the metadata that JSON requests put in the body
+const KEY_ID: &str = "X-Key-Id";
+const LEVEL: &str = "X-Level";
+const SIGNATURE: &str = "X-Signature";
+const PUBLIC_KEY: &str = "X-Public-Key";
+const CONTEXT: &str = "X-Context";
+
+//Body limit used when Rocket's `limits.artifact` is not configured, in MiB
+const DEFAULT_ARTIFACT_LIMIT: u64 = 16;
+
+//Streamed body limit used when Rocket's `limits.prehash` is not configured, in GiB
+const DEFAULT_PREHASH_LIMIT: u64 = 64;
+
+/// Signature metadata of a raw `application/octet-stream` request, taken from
+/// its headers. Values are validated by the handlers so that malformed headers
+/// produce the same JSON errors as malformed JSON fields.
+struct Metadata<'r> {
+    key_id: Option<&'r str>,
+    level: Option<&'r str>,
+    signature: Option<&'r str>,
+    public_key: Option<&'r str>,
+    context: Option<&'r str>,
+}
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for Metadata<'r> {
+    type Error = ();
+
+    async fn from_request(request: &'r Request<'_>) -> Outcome<Self, ()> {
+        let headers = request.headers();
+        Outcome::Success(Metadata {
+            key_id: headers.get_one(KEY_ID),
+            level: headers.get_one(LEVEL),
+            signature: headers.get_one(SIGNATURE),
+            public_key: headers.get_one(PUBLIC_KEY),
+            context: headers.get_one(CONTEXT),
+        })
+    }
+}
+
+impl Metadata<'_> {
+    fn level(&self) -> Result<Option<SecurityLevel>, ApiError> {
+        let level = match self.level {
+            Some(level) => level,
+            None => return Ok(None),
+        };
+        let invalid = |reason: String| ApiError::InvalidHeader {
+            name: LEVEL,
+            reason,
+        };
+        let level: u8 = level
+            .trim()
+            .parse()
+            .map_err(|e| invalid(format!("{}", e)))?;
+        SecurityLevel::try_from(level).map(Some).map_err(invalid)
+    }
+
+    //The prehash domain has no context, so one must not be silently dropped
+    fn no_context(&self) -> Result<(), ApiError> {
+        match self.context {
+            Some(_) => Err(ApiError::InvalidHeader {
+                name: CONTEXT,
+                reason: "contexts are not supported for prehash signatures".to_string(),
+            }),
+            None => Ok(()),
+        }
+    }
+
+    fn required(name: &'static str, value: Option<&str>) -> Result<Vec<u8>, ApiError> {
+        let value = value.ok_or_else(|| ApiError::InvalidHeader {
+            name,
+            reason: "header is required".to_string(),
+        })?;
+        decode_base64(name, value)
+    }
+}
+
+pub fn routes() -> Vec<Route> {
+    routes![sign, verify, sign_prehash, verify_prehash]
+}
+
+#[post("/sign", format = "binary", data = "<data>")]
+async fn sign(
+    metadata: Metadata<'_>,
+    data: Data<'_>,
+    limits: &Limits,
+    keystore: &State<KeyStore>,
+    guests: &State<Guests>,
+) -> Result<Json<SignResponse>, ApiError> {
+    let level = metadata.level()?;
+    let data = read_body(data, limits).await?;
+    sign_bytes(
+        Payload::new(&data, metadata.context)?,
+        metadata.key_id,
+        level,
+        keystore,
+        guests,
+    )
+    .map(Json)
+}
+
+#[post("/verify", format = "binary", data = "<data>")]
+async fn verify(
+    metadata: Metadata<'_>,
+    data: Data<'_>,
+    limits: &Limits,
+    guests: &State<Guests>,
+) -> Result<Json<VerifyResponse>, ApiError> {
+    let level = metadata.level()?;
+    let signature = Metadata::required(SIGNATURE, metadata.signature)?;
+    let public_key = Metadata::required(PUBLIC_KEY, metadata.public_key)?;
+    let data = read_body(data, limits).await?;
+    verify_bytes(
+        Payload::new(&data, metadata.context)?,
+        &signature,
+        &public_key,
+        level,
+        guests,
+    )
+    .map(Json)
+}
+
+//Signs the SHAKE256 digest of a streamed body in the guest's prehash domain
+#[post("/sign/prehash", format = "binary", data = "<data>")]
+async fn sign_prehash(
+    metadata: Metadata<'_>,
+    data: Data<'_>,
+    limits: &Limits,
+    keystore: &State<KeyStore>,
+    guests: &State<Guests>,
+) -> Result<Json<SignResponse>, ApiError> {
+    let level = metadata.level()?;
+    metadata.no_context()?;
+    let digest = prehash::digest(data, prehash_limit(limits)).await?;
+    sign_bytes(
+        Payload::Prehashed(prehash::SHAKE256, &digest),
+        metadata.key_id,
+        level,
+        keystore,
+        guests,
+    )
+    .map(Json)
+}
+
+#[post("/verify/prehash", format = "binary", data = "<data>")]
+async fn verify_prehash(
+    metadata: Metadata<'_>,
+    data: Data<'_>,
+    limits: &Limits,
+    guests: &State<Guests>,
+) -> Result<Json<VerifyResponse>, ApiError> {
+    let level = metadata.level()?;
+    let signature = Metadata::required(SIGNATURE, metadata.signature)?;
+    let public_key = Metadata::required(PUBLIC_KEY, metadata.public_key)?;
+    metadata.no_context()?;
+    let digest = prehash::digest(data, prehash_limit(limits)).await?;
+    verify_bytes(
+        Payload::Prehashed(prehash::SHAKE256, &digest),
+        &signature,
+        &public_key,
+        level,
+        guests,
+    )
+    .map(Json)
+}
+
+fn prehash_limit(limits: &Limits) -> ByteUnit {
+    limits
+        .get("prehash")
+        .unwrap_or_else(|| DEFAULT_PREHASH_LIMIT.gibibytes())
+}
+
+/// Reads the whole body, refusing artifacts larger than `limits.artifact`.
+pub async fn read_body(data: Data<'_>, limits: &Limits) -> Result<Vec<u8>, ApiError> {
+    let limit = limits
+        .get("artifact")
+        .unwrap_or_else(|| DEFAULT_ARTIFACT_LIMIT.mebibytes());
+    let body = data
+        .open(limit)
+        .into_bytes()
+        .await
+        .map_err(ApiError::Body)?;
+    if !body.is_complete() {
+        return Err(ApiError::PayloadTooLarge {
+            limit: limit.as_u64(),
+        });
+    }
+    Ok(body.into_inner())
+}
@@ -0,0 +1,1070 @@
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use base64::{engine::general_purpose, Engine as _};
+use rocket::figment::Figment;
+use rocket::http::{ContentType, Header, Status};
+use rocket::local::blocking::{Client, LocalResponse};
+use rocket::serde::json::{json, serde_json, Value};
+
+//Each client gets its own key store so tests can run in parallel
+fn keystore_dir() -> PathBuf {
+    static NEXT: AtomicUsize = AtomicUsize::new(0);
+    std::env::temp_dir().join(format!(
+        "wasm-api-test-{}-{}",
+        std::process::id(),
+        NEXT.fetch_add(1, Ordering::Relaxed)
+    ))
+}
+
+fn figment() -> Figment {
+    untrusted_figment().merge(("trusted_module_key", "guest-modules.pub.pem"))
+}
+
+//Configuration without a trusted module key
+fn untrusted_figment() -> Figment {
+    rocket::Config::figment()
+        .merge(("log_level", "off"))
+        .merge(("keystore_key", general_purpose::STANDARD.encode([7u8; 32])))
+        .merge(("keystore_dir", keystore_dir()))
+}
+
+fn client(figment: Figment) -> Client {
+    Client::tracked(super::app(rocket::custom(figment))).expect("valid rocket instance")
+}
+
+//Whether the service refuses to start; the error must be inspected or Rocket panics
+fn fails_to_ignite(figment: Figment) -> bool {
+    match Client::tracked(super::app(rocket::custom(figment))) {
+        Ok(_) => false,
+        Err(e) => matches!(e.kind(), rocket::error::ErrorKind::FailedFairings(_)),
+    }
+}
+
+fn error_code(response: LocalResponse<'_>) -> String {
+    let body: Value = response.into_json().expect("JSON error body");
+    assert!(body["message"].is_string());
+    body["code"].as_str().expect("error code").to_string()
+}
+
+#[test]
+fn malformed_base64_is_rejected() {
+    let client = client(figment());
+
+    let response = client
+        .post("/verify")
+        .json(&json!({ "data": "hello", "signature": "not base64!", "public_key": "AAAA" }))
+        .dispatch();
+    assert_eq!(response.status(), Status::BadRequest);
+    assert_eq!(error_code(response), "invalid_base64");
+
+    let response = client
+        .post("/kem/encapsulate")
+        .json(&json!({ "public_key": "%%%" }))
+        .dispatch();
+    assert_eq!(response.status(), Status::BadRequest);
+    assert_eq!(error_code(response), "invalid_base64");
+}
+
+#[test]
+fn wrong_key_length_is_rejected() {
+    let client = client(figment());
+    let short_key = general_purpose::STANDARD.encode([0u8; 100]);
+
+    let response = client
+        .post("/verify")
+        .json(&json!({ "data": "hello", "signature": "AAAA", "public_key": short_key }))
+        .dispatch();
+    assert_eq!(response.status(), Status::BadRequest);
+    assert_eq!(error_code(response), "invalid_length");
+
+    let response = client
+        .post("/kem/decapsulate")
+        .json(&json!({ "ciphertext": short_key, "secret_key": short_key }))
+        .dispatch();
+    assert_eq!(response.status(), Status::BadRequest);
+    assert_eq!(error_code(response), "invalid_length");
+}
+
+#[test]
+fn kem_round_trips_and_rejects_implicitly() {
+    let client = client(figment());
+    let keypair = || -> Value {
+        client
+            .post("/kem/keypair")
+            .dispatch()
+            .into_json()
+            .expect("keypair response")
+    };
+    let decapsulate = |ciphertext: &Value, secret_key: &Value| -> Value {
+        let response = client
+            .post("/kem/decapsulate")
+            .json(&json!({ "ciphertext": ciphertext, "secret_key": secret_key }))
+            .dispatch();
+        assert_eq!(response.status(), Status::Ok);
+        response.into_json().expect("decapsulate response")
+    };
+    let alice = keypair();
+    let bob = keypair();
+
+    let response = client
+        .post("/kem/encapsulate")
+        .json(&json!({ "public_key": alice["public_key"] }))
+        .dispatch();
+    assert_eq!(response.status(), Status::Ok);
+    let encapsulated: Value = response.into_json().expect("encapsulate response");
+    let shared_secret = &encapsulated["shared_secret"];
+    assert_eq!(
+        general_purpose::STANDARD
+            .decode(shared_secret.as_str().unwrap())
+            .unwrap()
+            .len(),
+        32
+    );
+
+    let decapsulated = decapsulate(&encapsulated["ciphertext"], &alice["secret_key"]);
+    assert_eq!(&decapsulated["shared_secret"], shared_secret);
+
+    //The wrong secret key is not an error: it derives an unrelated secret
+    let rejected = decapsulate(&encapsulated["ciphertext"], &bob["secret_key"]);
+    assert_ne!(&rejected["shared_secret"], shared_secret);
+    //...deterministically, from the secret key and ciphertext
+    let again = decapsulate(&encapsulated["ciphertext"], &bob["secret_key"]);
+    assert_eq!(again["shared_secret"], rejected["shared_secret"]);
+}
+
+#[test]
+fn unknown_key_is_not_found() {
+    let client = client(figment());
+
+    let response = client
+        .post("/sign")
+        .json(&json!({ "data": "hello", "key_id": "00000000000000000000000000000000" }))
+        .dispatch();
+    assert_eq!(response.status(), Status::NotFound);
+    assert_eq!(error_code(response), "unknown_key");
+
+    let response = client.get("/keys/missing").dispatch();
+    assert_eq!(response.status(), Status::NotFound);
+    assert_eq!(error_code(response), "unknown_key");
+}
+
+#[test]
+fn stored_keys_are_bound_to_their_level_and_public_key() {
+    use chacha20poly1305::aead::{Aead, KeyInit, Payload};
+    use chacha20poly1305::{ChaCha20Poly1305, Key, Nonce};
+
+    let dir = keystore_dir();
+    let client = client(figment().merge(("keystore_dir", &dir)));
+    let new_key = |level: u8| -> Value {
+        client
+            .post("/keys")
+            .json(&json!({ "level": level }))
+            .dispatch()
+            .into_json()
+            .expect("key response")
+    };
+    let key = new_key(44);
+    let other = new_key(65);
+    let id = key["id"].as_str().unwrap();
+    let file = dir.join(format!("{}.json", id));
+    let record: Value = serde_json::from_slice(&std::fs::read(&file).unwrap()).unwrap();
+    let sign = |record: &Value| {
+        std::fs::write(&file, serde_json::to_vec(record).unwrap()).unwrap();
+        client
+            .post("/sign")
+            .json(&json!({ "data": "hello", "key_id": id }))
+            .dispatch()
+    };
+
+    for (field, value) in [("public_key", &other["public_key"]), ("level", &json!(65))] {
+        let mut tampered = record.clone();
+        tampered[field] = value.clone();
+        let response = sign(&tampered);
+        assert_eq!(response.status(), Status::InternalServerError);
+        assert_eq!(error_code(response), "key_store");
+    }
+
+    //Records from before the binding were sealed under the id alone, and still open
+    let cipher = ChaCha20Poly1305::new(Key::from_slice(&[7u8; 32]));
+    let decode = |field: &str| {
+        general_purpose::STANDARD
+            .decode(record[field].as_str().unwrap())
+            .unwrap()
+    };
+    let nonce = decode("nonce");
+    let mut aad = id.as_bytes().to_vec();
+    aad.extend([1, 44]);
+    aad.extend(decode("public_key"));
+    let secret_key = cipher
+        .decrypt(
+            Nonce::from_slice(&nonce),
+            Payload {
+                msg: &decode("secret_key"),
+                aad: &aad,
+            },
+        )
+        .unwrap();
+    let legacy_sealed = cipher
+        .encrypt(
+            Nonce::from_slice(&nonce),
+            Payload {
+                msg: &secret_key,
+                aad: id.as_bytes(),
+            },
+        )
+        .unwrap();
+    let mut legacy = record.clone();
+    legacy.as_object_mut().unwrap().remove("version");
+    legacy["secret_key"] = general_purpose::STANDARD.encode(legacy_sealed).into();
+    let response = sign(&legacy);
+    assert_eq!(response.status(), Status::Ok);
+    let signed: Value = response.into_json().unwrap();
+    assert_eq!(signed["level"], 44);
+}
+
+#[test]
+fn guest_trap_is_reported_and_survived() {
+    //Too little linear memory for the guest to allocate a large payload
+    let client = client(figment().merge(("max_memory_pages", 18)));
+    let payload = "x".repeat(256 * 1024);
+
+    let response = client
+        .post("/sign")
+        .json(&json!({ "data": payload }))
+        .dispatch();
+    assert_eq!(response.status(), Status::InternalServerError);
+    assert_eq!(error_code(response), "guest_trap");
+
+    //The trapped instance is discarded and the next request succeeds
+    let response = client
+        .post("/sign")
+        .json(&json!({ "data": "hello" }))
+        .dispatch();
+    assert_eq!(response.status(), Status::Ok);
+}
+
+#[test]
+fn warm_instances_are_reused() {
+    let client = client(figment().merge(("warm_instances", 1)));
+    //Signing holds a secret key, so its instance is not kept
+    let signed: Value = client
+        .post("/sign")
+        .json(&json!({ "data": "hello", "level": 2 }))
+        .dispatch()
+        .into_json()
+        .expect("sign response");
+    for _ in 0..2 {
+        let response = client
+            .post("/verify")
+            .json(&json!({
+                "data": "hello",
+                "level": 2,
+                "signature": signed["signature"],
+                "public_key": signed["public_key"],
+            }))
+            .dispatch();
+        assert_eq!(response.status(), Status::Ok);
+    }
+
+    let response = client.get("/metrics").dispatch();
+    assert_eq!(response.status(), Status::Ok);
+    let metrics: Value = response.into_json().expect("metrics");
+    assert_eq!(
+        metrics["dilithium2"],
+        json!({ "hits": 1, "misses": 2, "idle": 1 })
+    );
+    assert_eq!(metrics["dilithium3"]["misses"], 0);
+}
+
+#[test]
+fn warm_instances_never_keep_secret_keys() {
+    let client = client(figment().merge(("warm_instances", 1)));
+    let key: Value = client
+        .post("/keys")
+        .json(&json!({ "level": 65 }))
+        .dispatch()
+        .into_json()
+        .expect("key response");
+    let id = key["id"].as_str().unwrap();
+    let secret_key = client
+        .rocket()
+        .state::<super::KeyStore>()
+        .unwrap()
+        .keypair(id)
+        .unwrap()
+        .unwrap()
+        .secret_key;
+    let guest = client
+        .rocket()
+        .state::<super::Guests>()
+        .unwrap()
+        .signing(super::SecurityLevel::MlDsa65);
+    let holds_seed = |memory: &Vec<u8>| memory.windows(32).any(|w| w == &secret_key[1..]);
+
+    //A warm instance from a verification serves the signature, and is then dropped
+    let signed: Value = client
+        .post("/sign")
+        .json(&json!({ "data": "hello" }))
+        .dispatch()
+        .into_json()
+        .expect("sign response");
+    let verify = || {
+        let response = client
+            .post("/verify")
+            .json(&json!({
+                "data": "hello",
+                "signature": signed["signature"],
+                "public_key": signed["public_key"],
+            }))
+            .dispatch();
+        assert_eq!(response.status(), Status::Ok);
+    };
+    verify();
+    assert_eq!(guest.idle_memories().len(), 1);
+    let response = client
+        .post("/sign")
+        .json(&json!({ "data": "hello", "key_id": id }))
+        .dispatch();
+    assert_eq!(response.status(), Status::Ok);
+    assert!(guest.idle_memories().is_empty());
+
+    verify();
+    let memories = guest.idle_memories();
+    assert_eq!(memories.len(), 1);
+    assert!(!memories.iter().any(holds_seed));
+}
+
+#[test]
+fn binary_payloads_round_trip() {
+    let client = client(figment());
+    let artifact: Vec<u8> = (0..=255u8).cycle().take(4096).collect();
+
+    //Raw body signed through headers, verified through base64 JSON
+    let response = client
+        .post("/sign")
+        .header(ContentType::Binary)
+        .header(Header::new("X-Level", "2"))
+        .body(&artifact)
+        .dispatch();
+    assert_eq!(response.status(), Status::Ok);
+    let signed: Value = response.into_json().expect("sign response");
+    assert_eq!(signed["level"], 2);
+
+    let response = client
+        .post("/verify")
+        .json(&json!({
+            "data": general_purpose::STANDARD.encode(&artifact),
+            "encoding": "base64",
+            "signature": signed["signature"],
+            "public_key": signed["public_key"],
+        }))
+        .dispatch();
+    let verified: Value = response.into_json().expect("verify response");
+    assert_eq!(verified["is_verified"], true);
+
+    //A tampered raw body fails verification
+    let mut tampered = artifact.clone();
+    tampered[0] ^= 1;
+    let response = client
+        .post("/verify")
+        .header(ContentType::Binary)
+        .header(Header::new(
+            "X-Signature",
+            signed["signature"].as_str().unwrap().to_string(),
+        ))
+        .header(Header::new(
+            "X-Public-Key",
+            signed["public_key"].as_str().unwrap().to_string(),
+        ))
+        .body(&tampered)
+        .dispatch();
+    let verified: Value = response.into_json().expect("verify response");
+    assert_eq!(verified["is_verified"], false);
+
+    let response = client
+        .post("/verify")
+        .header(ContentType::Binary)
+        .body(&artifact)
+        .dispatch();
+    assert_eq!(response.status(), Status::BadRequest);
+    assert_eq!(error_code(response), "invalid_header");
+}
+
+#[test]
+fn prehash_signs_bodies_larger_than_guest_memory() {
+    //The same memory cap that makes a buffered signature trap
+    let client = client(figment().merge(("max_memory_pages", 18)));
+    let artifact = vec![0x5au8; 4 * 1024 * 1024];
+
+    let response = client
+        .post("/sign/prehash")
+        .header(ContentType::Binary)
+        .body(&artifact)
+        .dispatch();
+    assert_eq!(response.status(), Status::Ok);
+    let signed: Value = response.into_json().expect("sign response");
+    let headers = |route: &'static str| {
+        client
+            .post(route)
+            .header(ContentType::Binary)
+            .header(Header::new(
+                "X-Signature",
+                signed["signature"].as_str().unwrap().to_string(),
+            ))
+            .header(Header::new(
+                "X-Public-Key",
+                signed["public_key"].as_str().unwrap().to_string(),
+            ))
+    };
+
+    let response = headers("/verify/prehash").body(&artifact).dispatch();
+    let verified: Value = response.into_json().expect("verify response");
+    assert_eq!(verified["is_verified"], true);
+
+    let mut tampered = artifact.clone();
+    tampered[artifact.len() - 1] ^= 1;
+    let response = headers("/verify/prehash").body(&tampered).dispatch();
+    let verified: Value = response.into_json().expect("verify response");
+    assert_eq!(verified["is_verified"], false);
+}
+
+#[test]
+fn prehash_signatures_never_verify_as_plain_ones() {
+    use sha3::digest::{ExtendableOutput, Update, XofReader};
+
+    let client = client(figment());
+    let artifact = b"layer.tar contents".repeat(1000);
+
+    //0x01 || 0x00 || DER(OID 2.16.840.1.101.3.4.2.12) || SHAKE256(artifact, 64)
+    let mut message = vec![0x01, 0x00];
+    message.extend_from_slice(&[
+        0x06, 0x09, 0x60, 0x86, 0x48, 0x01, 0x65, 0x03, 0x04, 0x02, 0x0c,
+    ]);
+    let mut hasher = sha3::Shake256::default();
+    hasher.update(&artifact);
+    let mut digest = [0u8; 64];
+    hasher.finalize_xof().read(&mut digest);
+    message.extend_from_slice(&digest);
+
+    let verify_plain = |signed: &Value| -> bool {
+        let verified: Value = client
+            .post("/verify")
+            .json(&json!({
+                "data": general_purpose::STANDARD.encode(&message),
+                "encoding": "base64",
+                "signature": signed["signature"],
+                "public_key": signed["public_key"],
+            }))
+            .dispatch()
+            .into_json()
+            .expect("verify response");
+        verified["is_verified"].as_bool().unwrap()
+    };
+    let verify_prehash = |signed: &Value| -> bool {
+        let verified: Value = client
+            .post("/verify/prehash")
+            .header(ContentType::Binary)
+            .header(Header::new(
+                "X-Signature",
+                signed["signature"].as_str().unwrap().to_string(),
+            ))
+            .header(Header::new(
+                "X-Public-Key",
+                signed["public_key"].as_str().unwrap().to_string(),
+            ))
+            .body(&artifact)
+            .dispatch()
+            .into_json()
+            .expect("verify response");
+        verified["is_verified"].as_bool().unwrap()
+    };
+
+    for level in [44, 65, 87] {
+        let prehashed: Value = client
+            .post("/sign/prehash")
+            .header(ContentType::Binary)
+            .header(Header::new("X-Level", level.to_string()))
+            .body(&artifact)
+            .dispatch()
+            .into_json()
+            .expect("sign response");
+        assert!(verify_prehash(&prehashed));
+        assert!(!verify_plain(&prehashed));
+
+        //Having the framed digest signed as a message must not forge a prehash signature
+        let plain: Value = client
+            .post("/sign")
+            .json(&json!({
+                "data": general_purpose::STANDARD.encode(&message),
+                "encoding": "base64",
+                "level": level,
+            }))
+            .dispatch()
+            .into_json()
+            .expect("sign response");
+        assert!(verify_plain(&plain));
+        assert!(!verify_prehash(&plain));
+    }
+
+    //Round-3 levels refuse digests (see `signing_formats::framing`)
+    for level in [2, 3, 5] {
+        let response = client
+            .post("/sign/prehash")
+            .header(ContentType::Binary)
+            .header(Header::new("X-Level", level.to_string()))
+            .body(&artifact)
+            .dispatch();
+        assert_eq!(response.status(), Status::BadRequest);
+        assert_eq!(error_code(response), "unsupported_level");
+    }
+}
+
+#[test]
+fn context_binds_signature_to_its_purpose() {
+    let client = client(figment());
+    let sign = |data: &[u8], context: Option<&str>, level: u8| -> Value {
+        client
+            .post("/sign")
+            .json(&json!({
+                "data": general_purpose::STANDARD.encode(data),
+                "encoding": "base64",
+                "context": context,
+                "level": level,
+            }))
+            .dispatch()
+            .into_json()
+            .expect("sign response")
+    };
+    let verify = |signed: &Value, context: Option<&str>| -> bool {
+        let verified: Value = client
+            .post("/verify")
+            .json(&json!({
+                "data": "same bytes",
+                "context": context,
+                "signature": signed["signature"],
+                "public_key": signed["public_key"],
+            }))
+            .dispatch()
+            .into_json()
+            .expect("verify response");
+        verified["is_verified"].as_bool().unwrap()
+    };
+
+    let config = sign(b"same bytes", Some("config-file"), 44);
+    assert!(verify(&config, Some("config-file")));
+    assert!(!verify(&config, Some("wasm-binary")));
+    assert!(!verify(&config, None));
+
+    //Having the framed message signed without a context must not forge a context signature
+    let mut framed = vec![0x00, "config-file".len() as u8];
+    framed.extend_from_slice(b"config-file");
+    framed.extend_from_slice(b"same bytes");
+    let forged = sign(&framed, None, 44);
+    assert!(!verify(&forged, Some("config-file")));
+
+    //Signatures without a context keep verifying as before, and only that way
+    let legacy = sign(b"same bytes", None, 2);
+    assert!(verify(&legacy, None));
+
+    //Round-3 levels refuse contexts, even an empty one
+    let response = client
+        .post("/verify")
+        .json(&json!({
+            "data": "same bytes",
+            "context": "",
+            "signature": legacy["signature"],
+            "public_key": legacy["public_key"],
+        }))
+        .dispatch();
+    assert_eq!(response.status(), Status::BadRequest);
+    assert_eq!(error_code(response), "unsupported_level");
+    let response = client
+        .post("/sign")
+        .json(&json!({ "data": "x", "context": "config-file", "level": 2 }))
+        .dispatch();
+    assert_eq!(response.status(), Status::BadRequest);
+    assert_eq!(error_code(response), "unsupported_level");
+
+    let response = client
+        .post("/sign")
+        .json(&json!({ "data": "x", "context": "c".repeat(256) }))
+        .dispatch();
+    assert_eq!(response.status(), Status::BadRequest);
+    assert_eq!(error_code(response), "context_too_long");
+}
+
+#[test]
+fn defaults_take_contexts_and_digests() {
+    let client = client(figment());
+    let signed: Value = client
+        .post("/sign")
+        .json(&json!({ "data": "same bytes", "context": "config-file" }))
+        .dispatch()
+        .into_json()
+        .expect("sign response");
+    assert_eq!(signed["level"], 65);
+
+    let response = client
+        .post("/sign/prehash")
+        .header(ContentType::Binary)
+        .body("artifact")
+        .dispatch();
+    assert_eq!(response.status(), Status::Ok);
+
+    let key: Value = client
+        .post("/keys")
+        .dispatch()
+        .into_json()
+        .expect("key response");
+    assert_eq!(key["level"], 65);
+}
+
+#[test]
+fn ml_dsa_outputs_are_tagged_and_verify() {
+    let client = client(figment());
+    for (level, tag) in [(44, 0x44), (65, 0x65), (87, 0x87)] {
+        let response = client
+            .post("/keys")
+            .json(&json!({ "level": level }))
+            .dispatch();
+        assert_eq!(response.status(), Status::Created);
+        let key: Value = response.into_json().expect("key response");
+
+        let signed: Value = client
+            .post("/sign")
+            .json(&json!({ "data": "release.tar", "key_id": key["id"], "context": "release" }))
+            .dispatch()
+            .into_json()
+            .expect("sign response");
+        assert_eq!(signed["level"], level);
+        let signature = general_purpose::STANDARD
+            .decode(signed["signature"].as_str().unwrap())
+            .unwrap();
+        assert_eq!(signature[0], tag);
+
+        //The level is inferred from the tagged key length
+        let verified: Value = client
+            .post("/verify")
+            .json(&json!({
+                "data": "release.tar",
+                "context": "release",
+                "signature": signed["signature"],
+                "public_key": key["public_key"],
+            }))
+            .dispatch()
+            .into_json()
+            .expect("verify response");
+        assert_eq!(verified["is_verified"], true);
+    }
+}
+
+#[test]
+fn seeded_keys_sign_reproducibly() {
+    let client = client(figment());
+    let seed = general_purpose::STANDARD.encode([42u8; 32]);
+    for level in [2, 65] {
+        let signed: Vec<Value> = (0..2)
+            .map(|_| {
+                let response = client
+                    .post("/keys")
+                    .json(&json!({ "level": level, "seed": seed }))
+                    .dispatch();
+                assert_eq!(response.status(), Status::Created);
+                let key: Value = response.into_json().expect("key response");
+                client
+                    .post("/sign")
+                    .json(&json!({ "data": "fixture", "key_id": key["id"] }))
+                    .dispatch()
+                    .into_json()
+                    .expect("sign response")
+            })
+            .collect();
+        assert_eq!(signed[0]["public_key"], signed[1]["public_key"]);
+        assert_eq!(signed[0]["signature"], signed[1]["signature"]);
+    }
+
+    let response = client
+        .post("/keys")
+        .json(&json!({ "seed": general_purpose::STANDARD.encode([42u8; 16]) }))
+        .dispatch();
+    assert_eq!(response.status(), Status::BadRequest);
+    assert_eq!(error_code(response), "invalid_length");
+}
+
+#[test]
+fn spki_keys_export_and_verify() {
+    let client = client(figment());
+    //RFC 8410 example Ed25519 key
+    let ed25519 = "-----BEGIN PUBLIC KEY-----\nMCowBQYDK2VwAyEAGb9ECWmEzf6FQbrBZ9w7lshQhqowtrbLDFw4rXAxZuE=\n-----END PUBLIC KEY-----\n";
+    //OID 2.16.840.1.101.3.4.3.18 (ML-DSA-65) and 1.3.6.1.4.1.2.267.7.4.4 (Dilithium2)
+    let oids: [(u8, &[u8]); 2] = [
+        (
+            65,
+            &[
+                0x06, 0x09, 0x60, 0x86, 0x48, 0x01, 0x65, 0x03, 0x04, 0x03, 0x12,
+            ],
+        ),
+        (
+            2,
+            &[
+                0x06, 0x0b, 0x2b, 0x06, 0x01, 0x04, 0x01, 0x02, 0x82, 0x0b, 0x07, 0x04, 0x04,
+            ],
+        ),
+    ];
+    for (level, oid) in oids {
+        let key: Value = client
+            .post("/keys")
+            .json(&json!({ "level": level }))
+            .dispatch()
+            .into_json()
+            .expect("key response");
+        let id = key["id"].as_str().unwrap();
+        let export = |format: &str| -> String {
+            let response = client
+                .get(format!("/keys/{}?format={}", id, format))
+                .dispatch();
+            assert_eq!(response.status(), Status::Ok);
+            let key: Value = response.into_json().expect("key response");
+            key["public_key"].as_str().unwrap().to_string()
+        };
+        assert_eq!(export("raw"), key["public_key"]);
+        let pem = export("pem");
+        assert!(pem.starts_with("-----BEGIN PUBLIC KEY-----\n"));
+        let der = general_purpose::STANDARD.decode(export("der")).unwrap();
+        assert!(der.windows(oid.len()).any(|window| window == oid));
+
+        let signed: Value = client
+            .post("/sign")
+            .json(&json!({ "data": "module.wasm", "key_id": id }))
+            .dispatch()
+            .into_json()
+            .expect("sign response");
+        let verify = |public_key: &str, level: Option<u8>| {
+            client
+                .post("/verify")
+                .json(&json!({
+                    "data": "module.wasm",
+                    "signature": signed["signature"],
+                    "public_key": public_key,
+                    "level": level,
+                }))
+                .dispatch()
+        };
+        for public_key in [pem.clone(), general_purpose::STANDARD.encode(&der)] {
+            let verified: Value = verify(&public_key, None)
+                .into_json()
+                .expect("verify response");
+            assert_eq!(verified["is_verified"], true);
+        }
+
+        //The key names its level, so a different requested level is a conflict
+        let response = verify(&pem, Some(if level == 2 { 3 } else { 2 }));
+        assert_eq!(response.status(), Status::BadRequest);
+        assert_eq!(error_code(response), "level_mismatch");
+
+        let response = verify(ed25519, None);
+        assert_eq!(response.status(), Status::BadRequest);
+        assert_eq!(error_code(response), "invalid_key");
+    }
+}
+
+//An ML-DSA-65 key other than the one the committed modules are signed with,
+//returning its seed-derived signing key and tagged public key
+fn module_signing_key() -> (ml_dsa::SigningKey<ml_dsa::MlDsa65>, Vec<u8>) {
+    let signing_key = ml_dsa::SigningKey::<ml_dsa::MlDsa65>::from_seed(&[9u8; 32].into());
+    let public_key = [
+        &[0x65][..],
+        &signing_key.expanded_key().verifying_key().encode(),
+    ]
+    .concat();
+    (signing_key, public_key)
+}
+
+#[test]
+fn guests_without_a_trusted_signature_are_refused() {
+    //No trusted key configured at all
+    assert!(fails_to_ignite(untrusted_figment()));
+
+    //Modules signed by someone else
+    let key_file = keystore_dir().with_extension("pub");
+    std::fs::write(&key_file, module_signing_key().1).unwrap();
+    assert!(fails_to_ignite(
+        untrusted_figment().merge(("trusted_module_key", &key_file))
+    ));
+    std::fs::remove_file(key_file).unwrap();
+}
+
+#[test]
+fn detached_module_signatures_are_checked() {
+    use super::module_signature::{read_verified, TrustedKey};
+
+    let (signing_key, public_key) = module_signing_key();
+    let dir = keystore_dir();
+    std::fs::create_dir_all(&dir).unwrap();
+    let key_file = dir.join("trusted.pub");
+    std::fs::write(&key_file, &public_key).unwrap();
+    let trusted_key = TrustedKey::load(key_file.to_str().unwrap()).unwrap();
+
+    //The embedded signature is by the project key, which is not trusted here
+    let mut wasm = std::fs::read("kyber_kem_module.wasm.multivalue.wasm").unwrap();
+    let module = dir.join("module.wasm");
+    let module_path = module.to_str().unwrap();
+    std::fs::write(&module, &wasm).unwrap();
+    assert!(read_verified(module_path, &trusted_key).is_err());
+
+    let signature = signing_key
+        .expanded_key()
+        .sign_deterministic(&wasm, &[])
+        .unwrap();
+    let detached = [&[0x65][..], &signature.encode()].concat();
+    std::fs::write(dir.join("module.wasm.sig"), detached).unwrap();
+    assert_eq!(read_verified(module_path, &trusted_key).unwrap(), wasm);
+
+    let last = wasm.len() - 1;
+    wasm[last] ^= 1;
+    std::fs::write(&module, &wasm).unwrap();
+    assert!(read_verified(module_path, &trusted_key).is_err());
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn sign_wasm_output_is_accepted() {
+    use super::module_signature::{verify, TrustedKey};
+
+    //Every guest is signed by `test-dilithium sign-wasm` (see build-guests.sh),
+    //which CI runs again with a fresh key before these tests
+    let trusted_key = [TrustedKey::load("guest-modules.pub.pem").unwrap()];
+    let paths = super::SecurityLevel::ALL
+        .iter()
+        .map(|level| level.module_path())
+        .chain(["kyber_kem_module.wasm.multivalue.wasm"]);
+    for path in paths {
+        let wasm = std::fs::read(path).unwrap();
+        let signer =
+            verify(&wasm, None, &trusted_key).unwrap_or_else(|e| panic!("{}: {}", path, e));
+        assert_eq!(signer.id(), trusted_key[0].id());
+    }
+}
+
+#[test]
+fn embedded_signatures_cover_the_compiled_bytes() {
+    use super::module_signature::{read_verified, TrustedKey};
+
+    let trusted_key = TrustedKey::load("guest-modules.pub.pem").unwrap();
+    let path = super::SecurityLevel::MlDsa65.module_path();
+    let wasm = std::fs::read(path).unwrap();
+    assert_eq!(read_verified(path, &trusted_key).unwrap(), wasm);
+
+    //Padding the first section's size to five bytes decodes to the same module,
+    //so only a hash over the file's own bytes notices it
+    let (mut size, mut end) = (0, 9);
+    loop {
+        size |= ((wasm[end] & 0x7f) as usize) << (7 * (end - 9));
+        end += 1;
+        if wasm[end - 1] & 0x80 == 0 {
+            break;
+        }
+    }
+    let padded: Vec<u8> = (0..5)
+        .map(|i| ((size >> (7 * i)) & 0x7f) as u8 | if i < 4 { 0x80 } else { 0 })
+        .collect();
+    let tampered = [&wasm[..9], &padded, &wasm[end..]].concat();
+    wasmtime::Module::validate(&wasmtime::Engine::default(), &tampered).unwrap();
+
+    let dir = keystore_dir();
+    std::fs::create_dir_all(&dir).unwrap();
+    let module = dir.join("module.wasm");
+    std::fs::write(&module, &tampered).unwrap();
+    assert!(read_verified(module.to_str().unwrap(), &trusted_key).is_err());
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+//(module (func (export "add") (param i32 i32) (result i32) local.get 0 local.get 1 i32.add))
+const ADD_MODULE: &[u8] = &[
+    0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00, 0x01, 0x07, 0x01, 0x60, 0x02, 0x7f, 0x7f, 0x01,
+    0x7f, 0x03, 0x02, 0x01, 0x00, 0x07, 0x07, 0x01, 0x03, 0x61, 0x64, 0x64, 0x00, 0x00, 0x0a, 0x09,
+    0x01, 0x07, 0x00, 0x20, 0x00, 0x20, 0x01, 0x6a, 0x0b,
+];
+
+//(module (func (export "spin") (loop br 0)))
+const SPIN_MODULE: &[u8] = &[
+    0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00, 0x01, 0x04, 0x01, 0x60, 0x00, 0x00, 0x03, 0x02,
+    0x01, 0x00, 0x07, 0x08, 0x01, 0x04, 0x73, 0x70, 0x69, 0x6e, 0x00, 0x00, 0x0a, 0x09, 0x01, 0x07,
+    0x00, 0x03, 0x40, 0x0c, 0x00, 0x0b, 0x0b,
+];
+
+//(module
+//  (import "wasi_snapshot_preview1" "args_sizes_get" (func (param i32 i32) (result i32)))
+//  (memory (export "memory") 1)
+//  (func (export "argc") (result i32)
+//    (drop (call 0 (i32.const 0) (i32.const 4))) (i32.load (i32.const 0))))
+const ARGC_MODULE: &[u8] = &[
+    0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00, 0x01, 0x0b, 0x02, 0x60, 0x02, 0x7f, 0x7f, 0x01,
+    0x7f, 0x60, 0x00, 0x01, 0x7f, 0x02, 0x29, 0x01, 0x16, 0x77, 0x61, 0x73, 0x69, 0x5f, 0x73, 0x6e,
+    0x61, 0x70, 0x73, 0x68, 0x6f, 0x74, 0x5f, 0x70, 0x72, 0x65, 0x76, 0x69, 0x65, 0x77, 0x31, 0x0e,
+    0x61, 0x72, 0x67, 0x73, 0x5f, 0x73, 0x69, 0x7a, 0x65, 0x73, 0x5f, 0x67, 0x65, 0x74, 0x00, 0x00,
+    0x03, 0x02, 0x01, 0x01, 0x05, 0x03, 0x01, 0x00, 0x01, 0x07, 0x11, 0x02, 0x06, 0x6d, 0x65, 0x6d,
+    0x6f, 0x72, 0x79, 0x02, 0x00, 0x04, 0x61, 0x72, 0x67, 0x63, 0x00, 0x01, 0x0a, 0x10, 0x01, 0x0e,
+    0x00, 0x41, 0x00, 0x41, 0x04, 0x10, 0x00, 0x1a, 0x41, 0x00, 0x28, 0x02, 0x00, 0x0b,
+];
+
+//`module` with a custom section of `len` bytes appended, giving it another id
+fn with_custom_section(module: &[u8], len: usize) -> Vec<u8> {
+    let mut contents = vec![1, b'x'];
+    contents.resize(contents.len() + len, 0);
+    let mut wasm = module.to_vec();
+    wasm.push(0);
+    let mut size = contents.len();
+    loop {
+        let byte = (size & 0x7f) as u8;
+        size >>= 7;
+        if size == 0 {
+            wasm.push(byte);
+            break;
+        }
+        wasm.push(byte | 0x80);
+    }
+    wasm.extend(contents);
+    wasm
+}
+
+#[test]
+fn uploaded_modules_are_bounded() {
+    let (signing_key, public_key) = module_signing_key();
+    let key_file = keystore_dir().with_extension("pub");
+    std::fs::write(&key_file, &public_key).unwrap();
+    let client = client(
+        figment()
+            .merge(("allowed_module_keys", [key_file.to_str().unwrap()]))
+            .merge(("module_timeout_ms", 100))
+            .merge(("max_modules", 3))
+            .merge(("max_module_bytes", 4096)),
+    );
+    let upload = |bytes: &[u8]| {
+        let signature = signing_key
+            .expanded_key()
+            .sign_deterministic(bytes, &[])
+            .unwrap();
+        let signature = [&[0x65][..], &signature.encode()].concat();
+        client
+            .post("/modules")
+            .header(Header::new(
+                "X-Signature",
+                general_purpose::STANDARD.encode(signature),
+            ))
+            .body(bytes)
+            .dispatch()
+    };
+
+    //An export that never returns traps at the deadline instead of pinning a worker
+    let response = upload(SPIN_MODULE);
+    assert_eq!(response.status(), Status::Created);
+    let module: Value = response.into_json().expect("module response");
+    let started = std::time::Instant::now();
+    let response = client
+        .post(format!(
+            "/modules/{}/invoke/spin",
+            module["id"].as_str().unwrap()
+        ))
+        .dispatch();
+    assert_eq!(response.status(), Status::InternalServerError);
+    assert_eq!(error_code(response), "guest_trap");
+    assert!(started.elapsed() < std::time::Duration::from_secs(10));
+
+    //Uploading a stored module again does not count twice
+    assert_eq!(upload(SPIN_MODULE).status(), Status::Created);
+    assert_eq!(upload(ADD_MODULE).status(), Status::Created);
+    let response = upload(&with_custom_section(ADD_MODULE, 5000));
+    assert_eq!(response.status(), Status::InsufficientStorage);
+    assert_eq!(error_code(response), "module_store_full");
+    assert_eq!(
+        upload(&with_custom_section(ADD_MODULE, 1)).status(),
+        Status::Created
+    );
+    let response = upload(&with_custom_section(ADD_MODULE, 2));
+    assert_eq!(response.status(), Status::InsufficientStorage);
+    assert_eq!(error_code(response), "module_store_full");
+    //A full store refuses modules before compiling them
+    let response = upload(b"not wasm");
+    assert_eq!(response.status(), Status::InsufficientStorage);
+    assert_eq!(error_code(response), "module_store_full");
+    std::fs::remove_file(key_file).unwrap();
+}
+
+#[test]
+fn allow_listed_modules_are_uploaded_and_invoked() {
+    let (signing_key, public_key) = module_signing_key();
+    let key_file = keystore_dir().with_extension("pub");
+    std::fs::write(&key_file, &public_key).unwrap();
+    let client = client(figment().merge((
+        "allowed_module_keys",
+        [key_file.to_str().unwrap(), "guest-modules.pub.pem"],
+    )));
+    let detached = |bytes: &[u8]| {
+        let signature = signing_key
+            .expanded_key()
+            .sign_deterministic(bytes, &[])
+            .unwrap();
+        general_purpose::STANDARD.encode([&[0x65][..], &signature.encode()].concat())
+    };
+    let upload = |bytes: &[u8], signature: Option<String>| {
+        let mut request = client.post("/modules").body(bytes);
+        if let Some(signature) = signature {
+            request = request.header(Header::new("X-Signature", signature));
+        }
+        request.dispatch()
+    };
+
+    let response = upload(ADD_MODULE, None);
+    assert_eq!(response.status(), Status::Forbidden);
+    assert_eq!(error_code(response), "untrusted_module");
+    let response = upload(b"not wasm", Some(detached(b"not wasm")));
+    assert_eq!(response.status(), Status::BadRequest);
+    assert_eq!(error_code(response), "invalid_module");
+
+    let response = upload(ADD_MODULE, Some(detached(ADD_MODULE)));
+    assert_eq!(response.status(), Status::Created);
+    let module: Value = response.into_json().expect("module response");
+    assert_eq!(module["exports"], json!(["add"]));
+    let id = module["id"].as_str().unwrap();
+    let invoke = |id: &str, export: &str, args: Value| {
+        client
+            .post(format!("/modules/{}/invoke/{}", id, export))
+            .json(&json!({ "args": args }))
+            .dispatch()
+    };
+    let response = invoke(id, "add", json!([40, 2]));
+    assert_eq!(response.status(), Status::Ok);
+    let invoked: Value = response.into_json().expect("invoke response");
+    assert_eq!(invoked["results"], json!([42]));
+
+    let response = invoke(id, "add", json!([1]));
+    assert_eq!(response.status(), Status::BadRequest);
+    assert_eq!(error_code(response), "invalid_invocation");
+    let response = invoke(id, "sub", json!([1, 2]));
+    assert_eq!(response.status(), Status::NotFound);
+    assert_eq!(error_code(response), "unknown_export");
+    let response = invoke("missing", "add", json!([1, 2]));
+    assert_eq!(response.status(), Status::NotFound);
+    assert_eq!(error_code(response), "unknown_module");
+
+    //Uploaded modules see none of the service's arguments, unlike its own guests
+    let response = upload(ARGC_MODULE, Some(detached(ARGC_MODULE)));
+    assert_eq!(response.status(), Status::Created);
+    let module: Value = response.into_json().expect("module response");
+    let response = invoke(module["id"].as_str().unwrap(), "argc", json!([]));
+    assert_eq!(response.status(), Status::Ok);
+    let invoked: Value = response.into_json().expect("invoke response");
+    assert_eq!(invoked["results"], json!([0]));
+
+    //Embedded signatures work as well, here by the project key
+    let kyber = std::fs::read("kyber_kem_module.wasm.multivalue.wasm").unwrap();
+    let response = upload(&kyber, None);
+    assert_eq!(response.status(), Status::Created);
+    let module: Value = response.into_json().expect("module response");
+    let response = invoke(module["id"].as_str().unwrap(), "alloc", json!([16]));
+    let invoked: Value = response.into_json().expect("invoke response");
+    assert_ne!(invoked["results"][0], 0);
+    std::fs::remove_file(key_file).unwrap();
+}